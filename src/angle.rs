@@ -55,6 +55,12 @@ impl Angle {
         Self::from_radians(dy.atan2(dx))
     }
 
+    /// The opposite direction (rotated by 180 degrees).
+    #[inline]
+    pub fn reversed(&self) -> Self {
+        Self::from_radians(self.0 + PI)
+    }
+
     #[inline]
     pub fn rad(&self) -> f64 {
         self.0
@@ -102,11 +108,13 @@ impl Sub<f64> for Angle {
     }
 }
 
+/// The negated angle (e.g. -30 degrees = 330 degrees) - NOT the reversed direction (see `reversed`).
 impl Neg for Angle {
     type Output = Self;
 
+    #[inline]
     fn neg(self) -> Self::Output {
-        Self::from_radians(self.0 + PI)
+        Self::from_radians(-self.0)
     }
 }
 
@@ -127,3 +135,22 @@ impl Div<f64> for Angle {
         Self::from_radians(self.0 / rhs)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negation_is_not_the_reversed_direction() {
+        let ang = Angle::from_degrees(30);
+        assert_eq!((-ang).deg(), 330);
+        assert_eq!(ang.reversed().deg(), 210);
+        assert_eq!((ang + (-ang)).deg(), 0);
+        assert_eq!(ang.reversed().reversed().deg(), 30);
+        // both give the same direction only for 90 and 270 degrees
+        assert_eq!((-Angle::with_90_deg()).deg(), 270);
+        assert_eq!(Angle::with_90_deg().reversed().deg(), 270);
+        assert_eq!((-Angle::with_180_deg()).deg(), 180);
+        assert_eq!(Angle::with_180_deg().reversed().deg(), 0);
+    }
+}
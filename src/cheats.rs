@@ -0,0 +1,110 @@
+//! Cheat codes (like IDKFA), detected from the recently typed characters.
+
+/// The supported cheat codes. In the codes, `#` stands for a digit (an argument of the cheat).
+const CHEAT_CODES: [&str; 7] = ["iddqd", "idkfa", "idfa", "idclip", "idspispopd", "idclev##", "iddt"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cheat {
+    /// IDDQD: toggle god mode.
+    God,
+    /// IDKFA: all the keys, weapons and ammo, plus full armor.
+    KeysWeaponsAmmo,
+    /// IDFA: all the weapons and ammo, plus full armor.
+    WeaponsAmmo,
+    /// IDCLIP or IDSPISPOPD: toggle walking through walls.
+    NoClip,
+    /// IDCLEVxy: warp to episode x, map y (E`x`M`y`), or to map `xy` (MAP`xy`) for DOOM 2 style maps.
+    Warp(u8, u8),
+    /// IDDT: cycle the automap reveal modes.
+    RevealMap,
+}
+
+impl Cheat {
+    /// Build the cheat from its (complete) code.
+    fn from_code(code: &str) -> Self {
+        let digit = |idx: usize| code.as_bytes()[idx] - b'0';
+        match code {
+            "iddqd" => Cheat::God,
+            "idkfa" => Cheat::KeysWeaponsAmmo,
+            "idfa" => Cheat::WeaponsAmmo,
+            "idclip" | "idspispopd" => Cheat::NoClip,
+            "iddt" => Cheat::RevealMap,
+            _ => Cheat::Warp(digit(6), digit(7)),
+        }
+    }
+}
+
+/// Accumulates the typed characters, as long as they are the start of a cheat code.
+/// Any other character resets the sequence.
+pub struct CheatDetector {
+    typed: String,
+}
+
+impl CheatDetector {
+    pub fn new() -> Self {
+        Self { typed: String::new() }
+    }
+
+    /// Add some typed text. Returns the cheat, if the text completed a cheat code.
+    pub fn type_text(&mut self, text: &str) -> Option<Cheat> {
+        let mut detected = None;
+        for ch in text.chars() {
+            self.typed.push(ch.to_ascii_lowercase());
+            if !is_code_prefix(&self.typed) {
+                // start over - but the character may be the start of a new code
+                self.typed.clear();
+                self.typed.push(ch.to_ascii_lowercase());
+                if !is_code_prefix(&self.typed) {
+                    self.typed.clear();
+                }
+            }
+            if CHEAT_CODES.iter().any(|code| code_matches(code, &self.typed)) {
+                detected = Some(Cheat::from_code(&self.typed));
+                self.typed.clear();
+            }
+        }
+        detected
+    }
+}
+
+/// Check if the typed text is the start of (at least) one of the cheat codes.
+fn is_code_prefix(typed: &str) -> bool {
+    CHEAT_CODES
+        .iter()
+        .any(|code| code.len() >= typed.len() && code_matches(&code[..typed.len()], typed))
+}
+
+fn code_matches(code: &str, typed: &str) -> bool {
+    code.len() == typed.len()
+        && code
+            .bytes()
+            .zip(typed.bytes())
+            .all(|(c, t)| c == t || (c == b'#' && t.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warp_is_detected_on_the_last_digit() {
+        let mut detector = CheatDetector::new();
+        let detected: Vec<_> = "IDCLEV12"
+            .chars()
+            .map(|ch| detector.type_text(&ch.to_string()))
+            .collect();
+        assert_eq!(detected[..7], [None; 7]);
+        assert_eq!(detected[7], Some(Cheat::Warp(1, 2)));
+    }
+
+    #[test]
+    fn other_characters_reset_the_sequence() {
+        let mut detector = CheatDetector::new();
+        assert_eq!(detector.type_text("idclevx2"), None);
+        assert_eq!(detector.type_text("1"), None);
+        // a wrong character may start a new code
+        assert_eq!(detector.type_text("ididdqd"), Some(Cheat::God));
+        assert_eq!(detector.type_text("xxidspispopd"), Some(Cheat::NoClip));
+        assert_eq!(detector.type_text("IDDT"), Some(Cheat::RevealMap));
+    }
+}
@@ -0,0 +1,75 @@
+//! The game clock: the total (unpaused) game time, plus a tic counter at vanilla DOOM's fixed rate.
+//! All the time-based animations should read it, so that they stay in sync.
+
+/// Vanilla DOOM runs the game logic at 35 tics per second.
+pub const TICS_PER_SECOND: f64 = 35.0;
+
+#[derive(Debug, Default, Clone)]
+pub struct GameClock {
+    time: f64,
+    tics: u64,
+    /// The time accumulated since the last tic.
+    tic_remainder: f64,
+}
+
+impl GameClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Advance the clock (only call this while the game is not paused).
+    /// Returns how many new tics have started.
+    pub fn advance(&mut self, elapsed_time: f64) -> u32 {
+        if elapsed_time <= 0.0 {
+            return 0;
+        }
+        self.time += elapsed_time;
+        self.tic_remainder += elapsed_time * TICS_PER_SECOND;
+        let new_tics = self.tic_remainder.floor();
+        self.tic_remainder -= new_tics;
+        self.tics += new_tics as u64;
+        new_tics as u32
+    }
+
+    /// The total game time, in seconds.
+    #[inline]
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// The number of whole tics since the game started.
+    #[inline]
+    pub fn tics(&self) -> u64 {
+        self.tics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_tic_per_1_35_of_a_second() {
+        let mut clock = GameClock::new();
+        for i in 1..=1000 {
+            assert_eq!(clock.advance(1.0 / TICS_PER_SECOND), 1);
+            assert_eq!(clock.tics(), i);
+        }
+        assert!((clock.time() - 1000.0 / TICS_PER_SECOND).abs() < 1e-9);
+
+        // shorter frames add up, until they make a whole tic
+        let mut clock = GameClock::new();
+        for i in 1..=100 {
+            assert_eq!(clock.advance(0.5 / TICS_PER_SECOND), (i % 2 == 0) as u32);
+        }
+        assert_eq!(clock.tics(), 50);
+        let mut clock = GameClock::new();
+        let new_tics: u32 = (0..600).map(|_| clock.advance(1.0 / 60.0)).sum();
+        assert_eq!((new_tics, clock.tics()), (350, 350));
+
+        // (no time passes while paused)
+        assert_eq!(clock.advance(0.0), 0);
+        assert_eq!(clock.advance(-1.0), 0);
+        assert_eq!(clock.tics(), 350);
+    }
+}
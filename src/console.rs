@@ -0,0 +1,197 @@
+//! Developer console: a drop-down text overlay, for typing in commands (like `map E1M3`).
+
+use crate::font::{Font, LINE_HEIGHT};
+use crate::settings::{FOV_MAX, FOV_MIN};
+use crate::*;
+
+/// How many log lines are kept (only as many as fit on screen are shown).
+const MAX_LOG_LINES: usize = 64;
+const CONSOLE_BG_COLOR: RGB = VERY_DARK_GREY;
+
+/// The commands which can be typed in the console.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConsoleCommand {
+    /// Warp to a map, by name (e.g. `map E1M3`).
+    Map(String),
+    /// Toggle walking through walls.
+    NoClip,
+    /// Give the player some items (e.g. `give keys`).
+    Give(GiveItems),
+    /// Change the field of view (e.g. `fov 100`).
+    Fov(i32),
+    /// Toggle measuring (and showing) the time spent in each painting phase.
+    Profile,
+    /// Toggle the "fake contrast" of the axis-aligned walls.
+    Contrast,
+    /// Toggle the classic automap arrow (instead of the simple player marker).
+    Arrow,
+    /// Toggle replacing all the wall textures with random ones (for stress testing the renderer).
+    RandomTextures,
+    /// Switch to the next screen resolution.
+    Resolution,
+    /// Toggle the view bobbing, while moving.
+    Bob,
+    /// Toggle filling the sub-sectors on the automap (for debugging the BSP traversal).
+    Subsectors,
+    /// Toggle painting only the automap lines inside the viewport (vs. all of them, for comparison).
+    Culling,
+    /// In automap mode, move the player to the center of the automap.
+    Jump,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GiveItems {
+    All,
+    Keys,
+    Weapons,
+    Health,
+    Armor,
+}
+
+impl ConsoleCommand {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default().to_ascii_lowercase();
+        let arg = words.next();
+        if words.next().is_some() {
+            return Err(format!("Too many arguments for `{command}`"));
+        }
+        match (command.as_str(), arg) {
+            ("map", Some(name)) => Ok(ConsoleCommand::Map(name.to_ascii_uppercase())),
+            ("map", None) => Err(String::from("Usage: map <name>")),
+            ("noclip", None) => Ok(ConsoleCommand::NoClip),
+            ("profile", None) => Ok(ConsoleCommand::Profile),
+            ("contrast", None) => Ok(ConsoleCommand::Contrast),
+            ("arrow", None) => Ok(ConsoleCommand::Arrow),
+            ("textures", None) => Ok(ConsoleCommand::RandomTextures),
+            ("resolution", None) => Ok(ConsoleCommand::Resolution),
+            ("bob", None) => Ok(ConsoleCommand::Bob),
+            ("subsectors", None) => Ok(ConsoleCommand::Subsectors),
+            ("culling", None) => Ok(ConsoleCommand::Culling),
+            ("jump", None) => Ok(ConsoleCommand::Jump),
+            ("give", what) => {
+                let items = match what.map(|w| w.to_ascii_lowercase()).as_deref() {
+                    None | Some("all") => GiveItems::All,
+                    Some("keys") => GiveItems::Keys,
+                    Some("weapons") => GiveItems::Weapons,
+                    Some("health") => GiveItems::Health,
+                    Some("armor") => GiveItems::Armor,
+                    Some(other) => return Err(format!("Unknown item: {other}")),
+                };
+                Ok(ConsoleCommand::Give(items))
+            }
+            ("fov", Some(value)) => match value.parse::<i32>() {
+                Ok(fov) if (FOV_MIN..=FOV_MAX).contains(&fov) => Ok(ConsoleCommand::Fov(fov)),
+                _ => Err(format!("FOV must be between {FOV_MIN} and {FOV_MAX}")),
+            },
+            ("fov", None) => Err(String::from("Usage: fov <degrees>")),
+            ("", _) => Err(String::from("No command")),
+            _ => Err(format!("Unknown command: {line}")),
+        }
+    }
+}
+
+/// The console's state: whether it is open, the text being typed and the log lines.
+pub struct Console {
+    open: bool,
+    input: String,
+    log: Vec<String>,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            input: String::new(),
+            log: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    #[inline]
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    /// Add some typed text to the input line.
+    pub fn type_text(&mut self, text: &str) {
+        // the console toggle key should not end up in the input
+        self.input
+            .extend(text.chars().filter(|ch| !ch.is_control() && *ch != '`' && *ch != '~'));
+    }
+
+    #[inline]
+    pub fn backspace(&mut self) {
+        self.input.pop();
+    }
+
+    /// Take the typed line (which also gets logged), clearing the input.
+    pub fn take_input(&mut self) -> String {
+        let line = std::mem::take(&mut self.input);
+        self.log(&format!("> {line}"));
+        line
+    }
+
+    pub fn log(&mut self, line: &str) {
+        self.log.push(line.to_string());
+        if self.log.len() > MAX_LOG_LINES {
+            self.log.remove(0);
+        }
+    }
+
+    /// Paint the console over the top half of the screen: the latest log lines, then the input line.
+    pub fn paint(&self, font: &Font, painter: &mut dyn Painter) {
+        if !self.open {
+            return;
+        }
+        let width = painter.get_screen_width();
+        let height = painter.get_screen_height() / 2;
+        painter.fill_rect(0, 0, width, height, CONSOLE_BG_COLOR);
+        painter.draw_horiz_line(0, width - 1, height, GREY);
+
+        let max_lines = ((height - 3) / LINE_HEIGHT - 1).max(0) as usize;
+        let first = self.log.len().saturating_sub(max_lines);
+        let mut y = 3;
+        for line in &self.log[first..] {
+            font.draw_text(3, y, line, LIGHT_GREY, painter);
+            y += LINE_HEIGHT;
+        }
+        let (x, _) = font.draw_text(3, y, &format!("> {}", self.input), WHITE, painter);
+        font.draw_text(x, y, "_", YELLOW, painter);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_command_is_parsed() {
+        let e1m3 = Ok(ConsoleCommand::Map(String::from("E1M3")));
+        assert_eq!(ConsoleCommand::parse("map e1m3"), e1m3);
+        assert_eq!(ConsoleCommand::parse("  MAP   E1M3 "), e1m3);
+        assert!(ConsoleCommand::parse("map").is_err());
+        assert!(ConsoleCommand::parse("map E1M3 E1M4").is_err());
+    }
+
+    #[test]
+    fn other_commands_are_parsed() {
+        assert_eq!(ConsoleCommand::parse("noclip"), Ok(ConsoleCommand::NoClip));
+        let give = |items| Ok(ConsoleCommand::Give(items));
+        assert_eq!(ConsoleCommand::parse("give"), give(GiveItems::All));
+        assert_eq!(ConsoleCommand::parse("give Keys"), give(GiveItems::Keys));
+        assert_eq!(ConsoleCommand::parse("fov 100"), Ok(ConsoleCommand::Fov(100)));
+        assert!(ConsoleCommand::parse("fov 500").is_err());
+        assert_eq!(ConsoleCommand::parse("contrast"), Ok(ConsoleCommand::Contrast));
+        assert_eq!(ConsoleCommand::parse("resolution"), Ok(ConsoleCommand::Resolution));
+        assert_eq!(ConsoleCommand::parse("subsectors"), Ok(ConsoleCommand::Subsectors));
+        assert_eq!(ConsoleCommand::parse("culling"), Ok(ConsoleCommand::Culling));
+        assert_eq!(ConsoleCommand::parse("jump"), Ok(ConsoleCommand::Jump));
+        assert!(ConsoleCommand::parse("").is_err());
+        assert!(ConsoleCommand::parse("fly").is_err());
+    }
+}
@@ -0,0 +1,90 @@
+//! Dynamic resolution: lower the screen resolution when the frame rate drops,
+//! and raise it back when the frame rate recovers.
+
+/// How many consecutive low FPS measurements (about one per second) lower the resolution.
+const SLOW_MEASUREMENTS: u32 = 3;
+/// How many consecutive high FPS measurements raise the resolution back.
+/// (more than for lowering it, and with a margin above the target FPS, to avoid oscillating)
+const FAST_MEASUREMENTS: u32 = 5;
+const FAST_FPS_MARGIN: f64 = 1.25;
+
+pub struct DynamicResolution {
+    /// Below this FPS, the resolution is lowered (0 = off).
+    target_fps: u32,
+    slow_cnt: u32,
+    fast_cnt: u32,
+}
+
+impl DynamicResolution {
+    pub fn new(target_fps: u32) -> Self {
+        Self {
+            target_fps,
+            slow_cnt: 0,
+            fast_cnt: 0,
+        }
+    }
+
+    pub fn set_target_fps(&mut self, target_fps: u32) {
+        *self = Self::new(target_fps);
+    }
+
+    /// Feed a new FPS measurement.
+    /// Returns -1 if the resolution should be lowered, +1 if it should be raised, 0 otherwise.
+    pub fn update(&mut self, fps: u32) -> i32 {
+        if self.target_fps == 0 {
+            return 0;
+        }
+        if fps < self.target_fps {
+            self.slow_cnt += 1;
+            self.fast_cnt = 0;
+        } else if (fps as f64) >= (self.target_fps as f64) * FAST_FPS_MARGIN {
+            self.fast_cnt += 1;
+            self.slow_cnt = 0;
+        } else {
+            self.slow_cnt = 0;
+            self.fast_cnt = 0;
+        }
+
+        // after a change, the FPS must be measured again from scratch
+        if self.slow_cnt >= SLOW_MEASUREMENTS {
+            self.slow_cnt = 0;
+            -1
+        } else if self.fast_cnt >= FAST_MEASUREMENTS {
+            self.fast_cnt = 0;
+            1
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn low_fps_scales_down_then_high_fps_scales_back_up() {
+        let mut dyn_res = DynamicResolution::new(60);
+        let changes = |dyn_res: &mut DynamicResolution, fps: &[u32]| -> Vec<i32> {
+            fps.iter().map(|&fps| dyn_res.update(fps)).collect()
+        };
+        assert_eq!(changes(&mut dyn_res, &[40, 40, 40]), [0, 0, -1]);
+        assert_eq!(changes(&mut dyn_res, &[40, 40, 40]), [0, 0, -1]);
+        // just above the target: not fast enough to raise the resolution back
+        assert_eq!(changes(&mut dyn_res, &[70; 8]), [0; 8]);
+        assert_eq!(changes(&mut dyn_res, &[90, 90, 90, 90, 90]), [0, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn mixed_fps_does_not_change_the_resolution() {
+        let mut dyn_res = DynamicResolution::new(60);
+        for fps in [40, 40, 70, 90, 90, 90, 90, 40, 40, 90].repeat(3) {
+            assert_eq!(dyn_res.update(fps), 0);
+        }
+        // (off)
+        let mut dyn_res = DynamicResolution::new(0);
+        for _ in 0..10 {
+            assert_eq!(dyn_res.update(1), 0);
+        }
+    }
+}
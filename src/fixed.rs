@@ -0,0 +1,96 @@
+//! 16.16 fixed-point numbers, like the ones used by vanilla DOOM for all the movement math.
+//! Only used when the `fixed_point` feature is enabled (for exact vanilla movement).
+//! - see: https://doomwiki.org/wiki/Fixed_point
+
+// TODO the rest of the movement code (monsters, thing collisions) does not use fixed-point yet
+
+use std::ops::{Add, Shl, Shr, Sub};
+
+pub const FRACBITS: u32 = 16;
+pub const FRACUNIT: i32 = 1 << FRACBITS;
+
+/// A 16.16 fixed-point number. Overflows wrap around, just like the 32-bit ints in vanilla DOOM.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Fixed(pub i32);
+
+impl Fixed {
+    #[inline]
+    pub fn from_int(value: i32) -> Self {
+        Self(value) << FRACBITS
+    }
+
+    /// Convert a float to fixed-point, truncating (towards zero) the extra precision.
+    #[inline]
+    pub fn from_f64(value: f64) -> Self {
+        Self((value * FRACUNIT as f64) as i32)
+    }
+
+    #[inline]
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / FRACUNIT as f64
+    }
+
+    /// The integer part (rounded down, like `x >> FRACBITS` in vanilla).
+    #[inline]
+    pub fn to_int(self) -> i32 {
+        (self >> FRACBITS).0
+    }
+
+    /// Vanilla `FixedMul`.
+    #[inline]
+    pub fn mul(self, rhs: Self) -> Self {
+        Self(((self.0 as i64 * rhs.0 as i64) >> FRACBITS) as i32)
+    }
+}
+
+impl Add for Fixed {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, rhs: Self) -> Self::Output {
+        Self(self.0.wrapping_add(rhs.0))
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self(self.0.wrapping_sub(rhs.0))
+    }
+}
+
+impl Shl<u32> for Fixed {
+    type Output = Self;
+
+    #[inline]
+    fn shl(self, rhs: u32) -> Self::Output {
+        Self(self.0 << rhs)
+    }
+}
+
+/// Arithmetic shift right (keeps the sign, like in vanilla).
+impl Shr<u32> for Fixed {
+    type Output = Self;
+
+    #[inline]
+    fn shr(self, rhs: u32) -> Self::Output {
+        Self(self.0 >> rhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shifts_keep_the_sign_and_round_down() {
+        assert_eq!(Fixed::from_int(-3), Fixed(-3 * FRACUNIT));
+        assert_eq!(Fixed(-1) >> 8, Fixed(-1));
+        assert_eq!(Fixed(FRACUNIT) << 2, Fixed::from_int(4));
+        // like `x >> FRACBITS`: towards minus infinity (unlike `as i32`)
+        assert_eq!(Fixed::from_f64(2.75).to_int(), 2);
+        assert_eq!(Fixed::from_f64(-2.25).to_int(), -3);
+    }
+}
@@ -5,6 +5,9 @@ use crate::utils::*;
 use crate::*;
 use bytes::Bytes;
 
+/// The vertical distance between two lines of text.
+pub const LINE_HEIGHT: i32 = 12;
+
 pub struct Font {
     font: Vec<PixMap>,
     grayscale: Box<[u8; 256]>,
@@ -60,12 +63,22 @@ impl Font {
         (0..=57).all(|i| !self.font[i].is_empty())
     }
 
-    pub fn draw_text(&self, x: i32, y: i32, text: &str, color: RGB, painter: &mut dyn Painter) {
+    /// Draw a text, starting at (x, y). New lines start again from x, one line height lower,
+    /// and tabs advance to the next tab stop.
+    /// Returns the position right after the last character, so more text can be drawn from there.
+    pub fn draw_text(&self, x: i32, y: i32, text: &str, color: RGB, painter: &mut dyn Painter) -> (i32, i32) {
         const SPACE_WIDTH: i32 = 6;
+        const TAB_WIDTH: i32 = 4 * SPACE_WIDTH;
         let mapper = FontColorMapper(color, self.grayscale.as_ref());
         let mut dx = 0;
+        let mut dy = 0;
         for byte in text.bytes() {
-            if byte <= 32 {
+            if byte == b'\n' {
+                dx = 0;
+                dy += LINE_HEIGHT;
+            } else if byte == b'\t' {
+                dx = (dx / TAB_WIDTH + 1) * TAB_WIDTH;
+            } else if byte <= 32 {
                 dx += SPACE_WIDTH;
             } else {
                 let idx = match byte {
@@ -80,11 +93,12 @@ impl Font {
                 };
                 let char_pixmap = &self.font[idx];
                 if !char_pixmap.is_empty() {
-                    char_pixmap.paint(x + dx, y, painter, &mapper);
+                    char_pixmap.paint(x + dx, y + dy, painter, &mapper);
                     dx += char_pixmap.width() as i32;
                 }
             }
         }
+        (x + dx, y + dy)
     }
 }
 
@@ -102,3 +116,51 @@ impl<'a> ColorMapper for FontColorMapper<'a> {
         RGB::from(r as u8, g as u8, b as u8)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::Layer;
+
+    /// The (min, max) rows with painted pixels.
+    fn painted_rows(layer: &Layer) -> (i32, i32) {
+        let rows: Vec<i32> = (0..layer.get_screen_height())
+            .filter(|&y| (0..layer.get_screen_width()).any(|x| layer.read_pixel(x, y).is_some()))
+            .collect();
+        (rows[0], rows[rows.len() - 1])
+    }
+
+    #[test]
+    fn new_line_starts_again_one_line_lower() {
+        let wad_data = WadData::load("DOOM1.WAD", true).unwrap();
+        let font = wad_data.font();
+        let mut layer = Layer::new(64, 64);
+        let end = font.draw_text(2, 2, "A\nB", WHITE, &mut layer);
+        assert!(end.0 > 2);
+        assert_eq!(end.1, 2 + LINE_HEIGHT);
+
+        // the same as painting each letter by itself, on its own line
+        let mut layer_a = Layer::new(64, 64);
+        font.draw_text(2, 2, "A", WHITE, &mut layer_a);
+        let mut layer_b = Layer::new(64, 64);
+        font.draw_text(2, 2 + LINE_HEIGHT, "B", WHITE, &mut layer_b);
+        for y in 0..64 {
+            for x in 0..64 {
+                let expected = layer_a.read_pixel(x, y).or(layer_b.read_pixel(x, y));
+                assert!(layer.read_pixel(x, y) == expected, "pixel {x},{y}");
+            }
+        }
+        // ... and the two rows do not overlap
+        assert!(painted_rows(&layer_a).1 < painted_rows(&layer_b).0);
+    }
+
+    #[test]
+    fn tab_advances_to_the_next_tab_stop() {
+        let wad_data = WadData::load("DOOM1.WAD", true).unwrap();
+        let font = wad_data.font();
+        let mut layer = Layer::new(64, 64);
+        assert_eq!(font.draw_text(0, 0, "\t", WHITE, &mut layer), (24, 0));
+        assert_eq!(font.draw_text(0, 0, "  \t", WHITE, &mut layer), (24, 0));
+        assert_eq!(font.draw_text(0, 0, "    \t", WHITE, &mut layer), (48, 0));
+    }
+}
@@ -11,12 +11,40 @@ NICE TO HAVE:
     - doc comments !!
  */
 
-use crate::level::ActiveLevel;
+use crate::cheats::{Cheat, CheatDetector};
+use crate::clock::TICS_PER_SECOND;
+use crate::console::{Console, ConsoleCommand, GiveItems};
+use crate::dynres::DynamicResolution;
+use crate::hud::HudMessages;
+use crate::level::{ActiveLevel, PhaseTimer};
+use crate::map_titles;
+use crate::menu::LevelMenu;
+use crate::specials::LevelExit;
+use crate::walls::DistanceFog;
 use crate::*;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
+use std::collections::HashMap;
 
-// key flags - for ALL keys (some only act once on press => they need 2 bits !!)
+/// The screen resolutions to cycle through, at runtime.
+const SCREEN_SIZES: [(i32, i32); 3] = [(320, 200), (480, 360), (640, 400)];
+
+/// Without "always run", the player walks at this fraction of the running speed (like in vanilla DOOM).
+const WALK_SPEED_FACTOR: f64 = 0.5;
+
+/// "Hurt me plenty" - the default skill level in vanilla DOOM.
+const DEFAULT_SKILL: u8 = 3;
+
+/// How long the intermission lasts, unless the "use" key skips it (in seconds).
+const INTERMISSION_TIME: f64 = 4.0;
+
+/// How many sound events are kept, until they are taken by the embedder.
+const MAX_QUEUED_SOUNDS: usize = 64;
+
+/// The seed for the "random textures" developer mode.
+const RANDOM_TEXTURES_SEED: u64 = 0x5EED_D00D;
+
+// key flags - for ALL keys (some only act once on press => they are also latched, see `pressed_key_flags`)
 const KEY_MOVE_FWD: u32 = 1 << 0;
 const KEY_MOVE_BACK: u32 = 1 << 1;
 const KEY_STRAFE_LEFT: u32 = 1 << 2;
@@ -29,12 +57,88 @@ const KEY_USE: u32 = 1 << 8;
 const KEY_SHOOT: u32 = 1 << 9;
 const KEY_ZOOM_IN: u32 = 1 << 10;
 const KEY_ZOOM_OUT: u32 = 1 << 11;
+const KEY_SLOW: u32 = 1 << 12;
+/// The keys which act once per press (instead of while held).
+const KEY_PRESS_ACTIONS: u32 = KEY_USE | KEY_SHOOT;
+
+/// The bindable actions, with their names (as used in the settings file).
+const KEY_ACTIONS: [(&str, u32); 13] = [
+    ("move_fwd", KEY_MOVE_FWD),
+    ("move_back", KEY_MOVE_BACK),
+    ("strafe_left", KEY_STRAFE_LEFT),
+    ("strafe_right", KEY_STRAFE_RIGHT),
+    ("curs_up", KEY_CURS_UP),
+    ("curs_down", KEY_CURS_DOWN),
+    ("curs_left", KEY_CURS_LEFT),
+    ("curs_right", KEY_CURS_RIGHT),
+    ("use", KEY_USE),
+    ("shoot", KEY_SHOOT),
+    ("zoom_in", KEY_ZOOM_IN),
+    ("zoom_out", KEY_ZOOM_OUT),
+    ("slow", KEY_SLOW),
+];
+
+/// The default keys for each bindable action.
+pub(crate) fn default_key_bindings() -> Vec<(&'static str, Vec<Keycode>)> {
+    KEY_ACTIONS
+        .iter()
+        .map(|(action, flag)| {
+            let keys = match *flag {
+                KEY_MOVE_FWD => vec![Keycode::W],
+                KEY_MOVE_BACK => vec![Keycode::S],
+                KEY_STRAFE_LEFT => vec![Keycode::A],
+                KEY_STRAFE_RIGHT => vec![Keycode::D],
+                KEY_CURS_UP => vec![Keycode::Up],
+                KEY_CURS_DOWN => vec![Keycode::Down],
+                KEY_CURS_LEFT => vec![Keycode::Left],
+                KEY_CURS_RIGHT => vec![Keycode::Right],
+                KEY_USE => vec![Keycode::Space, Keycode::E],
+                KEY_SHOOT => vec![Keycode::RCtrl, Keycode::LAlt],
+                KEY_ZOOM_IN => vec![Keycode::KpPlus],
+                KEY_SLOW => vec![Keycode::LShift, Keycode::RShift],
+                _ => vec![Keycode::KpMinus],
+            };
+            (*action, keys)
+        })
+        .collect()
+}
 
 pub struct DoomGame {
     cfg: GameConfig,
     map_idx: usize,
     level: ActiveLevel,
     key_flags: u32,
+    /// Key presses not yet handled by an update: a press is remembered even if the key is released
+    /// before the next update, so that very fast taps are not lost.
+    pressed_key_flags: u32,
+    key_bindings: HashMap<Keycode, u32>,
+    turn_sensitivity: f64,
+    always_run: bool,
+    slow_factor: f64,
+    turn_ramp_time: f64,
+    turn_start_factor: f64,
+    hud: HudMessages,
+    random_textures: bool,
+    requested_screen_size: Option<(i32, i32)>,
+    console: Console,
+    cheats: CheatDetector,
+    dyn_resolution: DynamicResolution,
+    sounds: Vec<SoundEvent>,
+    skill: u8,
+    intermission: Option<Intermission>,
+    menu: LevelMenu,
+    automap_thickness: i32,
+    teleport_keeps_momentum: bool,
+    fog: Option<DistanceFog>,
+    clock: GameClock,
+    /// The live user settings (including the changes made while playing).
+    settings: UserSettings,
+}
+
+/// Between two levels: show which level was finished and which one is next.
+struct Intermission {
+    next_map_idx: usize,
+    time_left: f64,
 }
 
 impl DoomGame {
@@ -45,39 +149,542 @@ impl DoomGame {
             map_idx: 0,
             level,
             key_flags: 0,
+            pressed_key_flags: 0,
+            key_bindings: HashMap::new(),
+            turn_sensitivity: 1.0,
+            always_run: true,
+            slow_factor: 1.0,
+            turn_ramp_time: 0.0,
+            turn_start_factor: 1.0,
+            hud: HudMessages::new(),
+            random_textures: false,
+            requested_screen_size: None,
+            console: Console::new(),
+            cheats: CheatDetector::new(),
+            dyn_resolution: DynamicResolution::new(0),
+            sounds: Vec::new(),
+            skill: DEFAULT_SKILL,
+            intermission: None,
+            menu: LevelMenu::new(),
+            automap_thickness: 1,
+            teleport_keeps_momentum: false,
+            fog: None,
+            clock: GameClock::new(),
+            settings: UserSettings::default(),
         };
+        engine.log_wad_warnings();
+        engine.apply_settings(&UserSettings::default());
         engine.load_map(0);
         engine.update_state(0.0);
         Ok(engine)
     }
 
+    /// Apply the user settings which can be changed while the game is running.
+    /// (the FOV is applied when building the `GameConfig`)
+    pub fn apply_settings(&mut self, settings: &UserSettings) {
+        self.settings = UserSettings {
+            fov: self.cfg.fov(),
+            ..settings.clone()
+        };
+        self.key_bindings.clear();
+        for (action, flag) in KEY_ACTIONS {
+            if let Some(keys) = settings.key_bindings.get(action) {
+                for key in keys {
+                    self.key_bindings.insert(*key, flag);
+                }
+            }
+        }
+        self.turn_sensitivity = settings.turn_sensitivity;
+        self.always_run = settings.always_run;
+        self.slow_factor = settings.slow_factor;
+        self.turn_ramp_time = settings.turn_ramp_time;
+        self.turn_start_factor = settings.turn_start_factor;
+        self.dyn_resolution.set_target_fps(settings.min_fps);
+        self.automap_thickness = settings.automap_thickness;
+        self.cfg.palette().set_gamma(settings.gamma);
+        self.teleport_keeps_momentum = settings.teleport_keeps_momentum;
+        self.fog = (settings.fog_density > 0.0).then(|| {
+            let [r, g, b] = settings.fog_color;
+            DistanceFog {
+                color: RGB::from(r, g, b),
+                density: settings.fog_density,
+            }
+        });
+        self.apply_level_options();
+    }
+
+    /// The user settings currently in use, including the ones changed while playing
+    /// (e.g. the FOV, via the console) - to be saved when the game ends.
+    #[inline]
+    pub fn settings(&self) -> &UserSettings {
+        &self.settings
+    }
+
+    /// Apply the options which live in the level (so they must be re-applied for each new level).
+    fn apply_level_options(&mut self) {
+        self.level.set_automap_thickness(self.automap_thickness);
+        self.level.set_teleport_keeps_momentum(self.teleport_keeps_momentum);
+        self.level.set_fog(self.fog);
+    }
+
     pub fn load_map(&mut self, idx: usize) {
         if self.map_idx != idx && idx < self.cfg.wad().map_count() {
+            let player_state = self.level.player_state().for_next_level();
             self.map_idx = idx;
             self.level = ActiveLevel::new(self.cfg.clone(), idx);
+            *self.level.player_state_mut() = player_state;
+            self.apply_level_options();
+            if self.random_textures {
+                self.level.randomize_textures(RANDOM_TEXTURES_SEED);
+            }
+        }
+    }
+
+    /// Move the player to the given position, facing the given angle.
+    pub fn place_player(&mut self, pos: Vertex, angle: Angle) {
+        self.level.place_player(pos, angle);
+    }
+
+    pub fn load_map_by_name(&mut self, name: &str) -> Result<(), String> {
+        let idx = self.cfg.wad().map_index(name).ok_or(format!("Map not found: {name}"))?;
+        self.load_map(idx);
+        Ok(())
+    }
+
+    /// The game clock: it only runs while the game is not paused (e.g. by the menu).
+    #[inline]
+    pub fn clock(&self) -> &GameClock {
+        &self.clock
+    }
+
+    /// The skill level, from 1 (easiest) to 5 (nightmare).
+    #[inline]
+    pub fn skill(&self) -> u8 {
+        self.skill
+    }
+
+    // TODO use the skill, once the level spawns things (see `MapData::single_player_things`)
+    pub fn set_skill(&mut self, skill: u8) {
+        self.skill = skill.clamp(1, 5);
+    }
+
+    /// Run a command, typed in the console. Returns the message to show for it.
+    pub fn execute_command(&mut self, command: ConsoleCommand) -> Result<String, String> {
+        match command {
+            ConsoleCommand::Map(name) => {
+                self.load_map_by_name(&name)?;
+                Ok(format!("Map: {}", self.cfg.wad().map_title(self.level.name())))
+            }
+            ConsoleCommand::NoClip => {
+                let noclip = self.level.toggle_noclip();
+                Ok(String::from(if noclip { "No clipping ON" } else { "No clipping OFF" }))
+            }
+            ConsoleCommand::Give(items) => {
+                let player = self.level.player_state_mut();
+                match items {
+                    GiveItems::All => player.give_all(),
+                    GiveItems::Keys => player.give_all_keys(),
+                    GiveItems::Weapons => player.give_weapons_and_ammo(),
+                    GiveItems::Health => player.health = 100,
+                    GiveItems::Armor => player.armor = 200,
+                }
+                Ok(String::from("Items given"))
+            }
+            ConsoleCommand::Profile => {
+                let profiling = self.level.toggle_profiling();
+                Ok(String::from(if profiling {
+                    "Render profiling ON"
+                } else {
+                    "Render profiling OFF"
+                }))
+            }
+            ConsoleCommand::Contrast => {
+                let contrast = self.level.toggle_fake_contrast();
+                Ok(String::from(if contrast {
+                    "Fake contrast ON"
+                } else {
+                    "Fake contrast OFF"
+                }))
+            }
+            ConsoleCommand::Arrow => {
+                let classic = self.level.toggle_automap_arrow_style();
+                Ok(String::from(if classic {
+                    "Classic automap arrow ON"
+                } else {
+                    "Classic automap arrow OFF"
+                }))
+            }
+            ConsoleCommand::RandomTextures => {
+                self.random_textures = !self.random_textures;
+                if self.random_textures {
+                    self.level.randomize_textures(RANDOM_TEXTURES_SEED);
+                    Ok(String::from("Random textures ON"))
+                } else {
+                    self.level.clear_texture_overrides();
+                    Ok(String::from("Random textures OFF"))
+                }
+            }
+            ConsoleCommand::Resolution => {
+                let (width, height) = self.cycle_screen_size();
+                Ok(format!("Resolution: {width}x{height}"))
+            }
+            ConsoleCommand::Bob => {
+                let bob = self.level.toggle_view_bob();
+                Ok(String::from(if bob { "View bobbing ON" } else { "View bobbing OFF" }))
+            }
+            ConsoleCommand::Jump => {
+                if !self.level.is_automap_full() {
+                    Err(String::from("Jumping only works in the automap"))
+                } else if self.level.jump_to_automap_center() {
+                    let pos = self.level.player_pos();
+                    Ok(format!("Jumped to ({}, {})", pos.x, pos.y))
+                } else {
+                    Err(String::from("Cannot jump there: outside the map"))
+                }
+            }
+            ConsoleCommand::Subsectors => {
+                let subsectors = self.level.toggle_automap_subsectors();
+                Ok(String::from(if subsectors {
+                    "Automap sub-sectors ON"
+                } else {
+                    "Automap sub-sectors OFF"
+                }))
+            }
+            ConsoleCommand::Culling => {
+                let culling = self.level.toggle_automap_culling();
+                Ok(String::from(if culling {
+                    "Automap culling ON"
+                } else {
+                    "Automap culling OFF"
+                }))
+            }
+            ConsoleCommand::Fov(fov) => {
+                self.cfg = self.cfg.with_changed_fov(fov);
+                self.level.set_game_config(self.cfg.clone());
+                self.settings.fov = fov;
+                Ok(format!("FOV: {fov}"))
+            }
+        }
+    }
+
+    fn apply_cheat(&mut self, cheat: Cheat) {
+        let player = self.level.player_state_mut();
+        let msg = match cheat {
+            Cheat::God => {
+                player.god_mode = !player.god_mode;
+                if player.god_mode {
+                    player.health = 100;
+                    "Degreelessness Mode On"
+                } else {
+                    "Degreelessness Mode Off"
+                }
+            }
+            Cheat::KeysWeaponsAmmo => {
+                player.give_weapons_and_ammo();
+                player.give_all_keys();
+                "Very Happy Ammo Added"
+            }
+            Cheat::WeaponsAmmo => {
+                player.give_weapons_and_ammo();
+                "Ammo (no keys) Added"
+            }
+            Cheat::NoClip => {
+                if self.level.toggle_noclip() {
+                    "No Clipping Mode ON"
+                } else {
+                    "No Clipping Mode OFF"
+                }
+            }
+            Cheat::Warp(d1, d2) => {
+                // DOOM 2 style maps (MAPxy) or DOOM 1 style maps (ExMy)
+                let wad = self.cfg.wad();
+                let idx = wad
+                    .map_index(&format!("MAP{d1}{d2}"))
+                    .or_else(|| wad.map_index(&format!("E{d1}M{d2}")));
+                match idx {
+                    Some(idx) => {
+                        self.load_map(idx);
+                        "Changing Level..."
+                    }
+                    None => "Impossible Selection",
+                }
+            }
+            Cheat::RevealMap => self.level.cycle_automap_reveal(),
+        };
+        self.hud.push(msg);
+    }
+
+    fn handle_console_event(&mut self, event: &Event) {
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Return | Keycode::KpEnter),
+                ..
+            } => {
+                let line = self.console.take_input();
+                let result = ConsoleCommand::parse(&line).and_then(|command| self.execute_command(command));
+                match result {
+                    Ok(msg) | Err(msg) => self.console.log(&msg),
+                }
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::Backspace),
+                ..
+            } => self.console.backspace(),
+            Event::TextInput { text, .. } => self.console.type_text(text),
+            // the text being composed (via an IME) only counts once it is committed, as `TextInput`
+            Event::TextEditing { .. } => {}
+            _ => {}
         }
     }
+
+    fn handle_menu_event(&mut self, event: &Event) {
+        if let Event::KeyDown { keycode: Some(key), .. } = event {
+            match key {
+                Keycode::Up => self.menu.move_selection(-1),
+                Keycode::Down => self.menu.move_selection(1),
+                Keycode::PageUp => self.menu.move_selection(-10),
+                Keycode::PageDown => self.menu.move_selection(10),
+                Keycode::Return | Keycode::KpEnter => {
+                    if let Some(name) = self.menu.selected_map().map(str::to_string) {
+                        if let Err(err) = self.load_map_by_name(&name) {
+                            self.hud.push(&err);
+                        }
+                    }
+                    self.menu.close();
+                }
+                Keycode::M | Keycode::Backspace => self.menu.close(),
+                _ => {}
+            }
+        }
+    }
+
+    /// Show the WAD's load warnings in the console.
+    fn log_wad_warnings(&mut self) {
+        for warning in self.cfg.wad().warnings() {
+            self.console.log(&format!("WARNING: {warning}"));
+        }
+    }
+
+    /// A snapshot of the player's camera, for painting the 3D view with another renderer.
+    #[inline]
+    pub fn camera(&self) -> Camera {
+        self.level.camera()
+    }
+
+    /// Project a world point (at height `z`) on the screen: (x, y, depth), or `None` if it is not in view
+    /// (e.g. for placing markers over the 3D view).
+    #[inline]
+    pub fn project_point(&self, world: Vertex, z: f64) -> Option<(i32, i32, f64)> {
+        self.level.project_point(world, z)
+    }
+
+    /// Advance the game by exactly `n` tics (fixed updates, at 35 per second), with the current input,
+    /// without painting anything - e.g. for scripted tests, which should not depend on the real time.
+    pub fn run_tics(&mut self, n: usize) {
+        let tic_time = 1.0 / TICS_PER_SECOND;
+        for _ in 0..n {
+            if !self.update_state(tic_time) {
+                break;
+            }
+        }
+        // the camera should show the latest state (not something in between)
+        self.set_interpolation(1.0);
+    }
+
+    /// Take the sound events since the last call, for the embedder to play them.
+    pub fn take_sound_events(&mut self) -> Vec<SoundEvent> {
+        std::mem::take(&mut self.sounds)
+    }
+
+    /// The statistics of the last painted frame.
+    #[inline]
+    pub fn render_stats(&self) -> RenderStats {
+        self.level.render_stats()
+    }
+
+    /// Switch to another screen resolution, rebuilding the game config for it.
+    pub fn set_screen_size(&mut self, scr_width: i32, scr_height: i32) {
+        self.cfg = self.cfg.with_resolution(scr_width, scr_height);
+        self.level.set_game_config(self.cfg.clone());
+        self.requested_screen_size = Some((scr_width, scr_height));
+    }
+
+    /// Dynamic resolution: step down/up through `SCREEN_SIZES`, based on the frame rate.
+    fn change_screen_size_for_fps(&mut self, fps: u32) {
+        let step = self.dyn_resolution.update(fps);
+        let current = (self.cfg.scr_width(), self.cfg.scr_height());
+        if let Some(idx) = SCREEN_SIZES.iter().position(|size| *size == current) {
+            let new_idx = (idx as i32 + step).clamp(0, SCREEN_SIZES.len() as i32 - 1) as usize;
+            if new_idx != idx {
+                let (width, height) = SCREEN_SIZES[new_idx];
+                self.set_screen_size(width, height);
+                self.hud.push(&format!("Resolution: {width}x{height} (at {fps} FPS)"));
+            }
+        }
+    }
+
+    /// The level was exited => go to the intermission, before the next map.
+    /// For the original games, the next map is the one from the original game order,
+    /// otherwise it is simply the next map in play order (wrapping around after the last one).
+    fn start_intermission(&mut self, exit: LevelExit) {
+        let wad = self.cfg.wad();
+        let next_map_idx = map_titles::next_builtin_map(self.level.name(), exit)
+            .and_then(|name| wad.map_index(&name))
+            .unwrap_or_else(|| {
+                let order = wad.ordered_map_indices();
+                let pos = order.iter().position(|idx| *idx == self.map_idx).unwrap_or(0);
+                order[(pos + 1) % order.len()]
+            });
+        self.intermission = Some(Intermission {
+            next_map_idx,
+            time_left: INTERMISSION_TIME,
+        });
+        // no damage tints etc on the intermission screen
+        self.cfg.palette().reset_selection();
+        self.key_flags = 0;
+        self.pressed_key_flags = 0;
+    }
+
+    /// Check if a key was pressed since the last check (even if it was already released).
+    fn take_key_press(&mut self, flag: u32) -> bool {
+        let was_pressed = self.pressed_key_flags & flag != 0;
+        self.pressed_key_flags &= !flag;
+        was_pressed
+    }
+
+    /// Returns true while the intermission is on (i.e. the level is paused).
+    /// When the time is up, or when the "use" key is pressed, the next map is loaded.
+    fn update_intermission(&mut self, elapsed_time: f64) -> bool {
+        if self.intermission.is_none() {
+            return false;
+        }
+        let skip = self.take_key_press(KEY_USE);
+        let Some(intermission) = &mut self.intermission else {
+            return false;
+        };
+        intermission.time_left -= elapsed_time;
+        if intermission.time_left <= 0.0 || skip {
+            let next_map_idx = intermission.next_map_idx;
+            self.intermission = None;
+            self.load_map(next_map_idx);
+        }
+        true
+    }
+
+    // TODO (later) show the proper intermission screen (WIMAP, stats etc)
+    fn paint_intermission(&self, intermission: &Intermission, painter: &mut dyn Painter) {
+        let wad = self.cfg.wad();
+        let finished = format!("Finished: {}", wad.map_title(self.level.name()));
+        let entering = format!("Entering: {}", wad.map_title(wad.map_name(intermission.next_map_idx)));
+        painter.clear(BLACK);
+        let font = self.cfg.font();
+        let y = self.cfg.scr_height() / 2 - 12;
+        font.draw_text(20, y, &finished, RED, painter);
+        font.draw_text(20, y + 16, &entering, WHITE, painter);
+    }
+
+    /// Switch to the next screen resolution from `SCREEN_SIZES`. Returns the new resolution.
+    fn cycle_screen_size(&mut self) -> (i32, i32) {
+        let current = (self.cfg.scr_width(), self.cfg.scr_height());
+        let idx = SCREEN_SIZES
+            .iter()
+            .position(|size| *size == current)
+            .map_or(0, |idx| idx + 1);
+        let (width, height) = SCREEN_SIZES[idx % SCREEN_SIZES.len()];
+        self.set_screen_size(width, height);
+        (width, height)
+    }
+
+    /// Reload the WAD file from disk (useful when iterating on a map),
+    /// keeping the current map, the player's position and state and the automap mode,
+    /// if the map still exists. If the reload fails, the old data is kept.
+    /// Shows how many lumps were changed (or added, or removed) in the WAD file.
+    pub fn reload_wad(&mut self) -> Result<(), String> {
+        let wad_data = self.cfg.wad().reload()?;
+        let changed_lump_count = self.cfg.wad().changed_lumps(&wad_data).len();
+        self.cfg = GameConfig::with_fov(wad_data, self.cfg.scr_width(), self.cfg.scr_height(), self.cfg.fov());
+        // (the palette comes with the new WAD data)
+        self.cfg.palette().set_gamma(self.settings.gamma);
+        self.log_wad_warnings();
+
+        let automap_mode = self.level.automap_mode();
+        match self.cfg.wad().map_index(self.level.name()) {
+            Some(idx) => {
+                let player_state = self.level.player_state().clone();
+                let pos = self.level.player_pos();
+                let angle = self.level.player_angle();
+                self.level = ActiveLevel::new(self.cfg.clone(), idx);
+                self.level.place_player(pos, angle);
+                *self.level.player_state_mut() = player_state;
+                self.map_idx = idx;
+            }
+            None => {
+                let player_state = self.level.player_state().for_next_level();
+                self.level = ActiveLevel::new(self.cfg.clone(), 0);
+                *self.level.player_state_mut() = player_state;
+                self.map_idx = 0;
+            }
+        }
+        self.level.set_automap_mode(automap_mode);
+        self.apply_level_options();
+        if self.random_textures {
+            self.level.randomize_textures(RANDOM_TEXTURES_SEED);
+        }
+        self.hud
+            .push(&format!("WAD reloaded: {changed_lump_count} lump(s) changed"));
+        Ok(())
+    }
 }
 
 impl GraphicsLoop for DoomGame {
     fn handle_event(&mut self, event: &Event) -> bool {
+        // the console toggle key
+        if let Event::KeyDown {
+            keycode: Some(Keycode::Backquote),
+            ..
+        } = event
+        {
+            self.console.toggle();
+            // no more movement while typing
+            self.key_flags = 0;
+            self.pressed_key_flags = 0;
+            return true;
+        }
+        // while the console is open, it gets all the input
+        if self.console.is_open() {
+            self.handle_console_event(event);
+            return true;
+        }
+        // same for the level select menu
+        if self.menu.is_open() {
+            self.handle_menu_event(event);
+            return true;
+        }
+
         // check keys
         match event {
             Event::KeyDown { keycode: Some(key), .. } => {
+                if let Some(&flag) = self.key_bindings.get(key) {
+                    // (key repeats are not new presses)
+                    if self.key_flags & flag == 0 {
+                        self.pressed_key_flags |= flag & KEY_PRESS_ACTIONS;
+                    }
+                    self.key_flags |= flag;
+                    return true;
+                }
                 match key {
                     Keycode::Tab => self.level.toggle_automap(),
-                    Keycode::KpPlus => self.key_flags |= KEY_ZOOM_IN,
-                    Keycode::KpMinus => self.key_flags |= KEY_ZOOM_OUT,
-                    Keycode::Up => self.key_flags |= KEY_CURS_UP,
-                    Keycode::Down => self.key_flags |= KEY_CURS_DOWN,
-                    Keycode::Left => self.key_flags |= KEY_CURS_LEFT,
-                    Keycode::Right => self.key_flags |= KEY_CURS_RIGHT,
-                    Keycode::W => self.key_flags |= KEY_MOVE_FWD,
-                    Keycode::S => self.key_flags |= KEY_MOVE_BACK,
-                    Keycode::A => self.key_flags |= KEY_STRAFE_LEFT,
-                    Keycode::D => self.key_flags |= KEY_STRAFE_RIGHT,
-                    Keycode::Space | Keycode::E => self.key_flags |= KEY_USE,
-                    Keycode::RCtrl | Keycode::LAlt => self.key_flags |= KEY_SHOOT,
+                    Keycode::M => {
+                        self.menu.open(self.cfg.wad(), self.level.name());
+                        // no more movement while in the menu
+                        self.key_flags = 0;
+                        self.pressed_key_flags = 0;
+                    }
+                    Keycode::F5 => {
+                        if let Err(err) = self.reload_wad() {
+                            self.hud.push(&format!("WAD reload failed: {err}"));
+                        }
+                    }
                     Keycode::PageUp => {
                         // TODO temp
                         if self.map_idx > 0 {
@@ -95,41 +702,73 @@ impl GraphicsLoop for DoomGame {
                     _ => {}
                 }
             }
-            Event::KeyUp { keycode: Some(key), .. } => match key {
-                Keycode::KpPlus => self.key_flags &= !KEY_ZOOM_IN,
-                Keycode::KpMinus => self.key_flags &= !KEY_ZOOM_OUT,
-                Keycode::Up => self.key_flags &= !KEY_CURS_UP,
-                Keycode::Down => self.key_flags &= !KEY_CURS_DOWN,
-                Keycode::Left => self.key_flags &= !KEY_CURS_LEFT,
-                Keycode::Right => self.key_flags &= !KEY_CURS_RIGHT,
-                Keycode::W => self.key_flags &= !KEY_MOVE_FWD,
-                Keycode::S => self.key_flags &= !KEY_MOVE_BACK,
-                Keycode::A => self.key_flags &= !KEY_STRAFE_LEFT,
-                Keycode::D => self.key_flags &= !KEY_STRAFE_RIGHT,
-                Keycode::Space | Keycode::E => self.key_flags &= KEY_USE,
-                Keycode::RCtrl | Keycode::LAlt => self.key_flags |= KEY_SHOOT,
-                _ => {}
-            },
+            Event::KeyUp { keycode: Some(key), .. } => {
+                // (the key press, if not handled yet, is kept until the next update)
+                if let Some(flag) = self.key_bindings.get(key) {
+                    self.key_flags &= !*flag;
+                }
+            }
+            // outside the console, the typed text is only checked for cheat codes
+            // (the keys already were handled above)
+            Event::TextInput { text, .. } => {
+                if let Some(cheat) = self.cheats.type_text(text) {
+                    self.apply_cheat(cheat);
+                }
+            }
+            Event::TextEditing { .. } => {}
             _ => {}
         }
         true
     }
 
     fn update_state(&mut self, elapsed_time: f64) -> bool {
+        self.level.begin_tick();
+        self.hud.update(elapsed_time);
+        if self.update_intermission(elapsed_time) {
+            return true;
+        }
+        // the menu pauses the game
+        if self.menu.is_open() {
+            return true;
+        }
+        self.clock.advance(elapsed_time);
+        self.level.set_game_time(self.clock.time());
+
+        // "use" only acts once per key press
+        if self.take_key_press(KEY_USE) && !self.level.is_automap_full() {
+            self.level.use_line();
+        }
+        // TODO shoot, once there are weapons
+        self.take_key_press(KEY_SHOOT);
+
+        // with "always run", the "slow" modifier makes the player move and turn slower (for precise positioning),
+        // otherwise the player walks, and the modifier makes them run
+        let is_modifier_held = self.key_flags & KEY_SLOW != 0;
+        let move_time = match (self.always_run, is_modifier_held) {
+            (true, true) => elapsed_time * self.slow_factor,
+            (false, false) => elapsed_time * WALK_SPEED_FACTOR,
+            _ => elapsed_time,
+        };
+
         // cursor always rotates and moves player
+        let turn_time = move_time * self.turn_sensitivity;
         match self.key_flags & (KEY_CURS_LEFT | KEY_CURS_RIGHT) {
-            KEY_CURS_LEFT => self.level.rotate_player(elapsed_time),
-            KEY_CURS_RIGHT => self.level.rotate_player(-elapsed_time),
-            _ => {}
+            KEY_CURS_LEFT => self
+                .level
+                .turn_player(turn_time, self.turn_ramp_time, self.turn_start_factor),
+            KEY_CURS_RIGHT => self
+                .level
+                .turn_player(-turn_time, self.turn_ramp_time, self.turn_start_factor),
+            _ => self.level.stop_turning(),
         }
         match self.key_flags & (KEY_CURS_UP | KEY_CURS_DOWN) {
-            KEY_CURS_UP => self.level.move_player(elapsed_time),
-            KEY_CURS_DOWN => self.level.move_player(-elapsed_time),
+            KEY_CURS_UP => self.level.move_player(move_time),
+            KEY_CURS_DOWN => self.level.move_player(-move_time),
             _ => {}
         }
 
-        // automap vs player specific movements
-        if self.level.is_automap_on() {
+        // automap vs player specific movements (the overlay automap follows the player instead)
+        if self.level.is_automap_full() {
             // in automap mode
             match self.key_flags & (KEY_STRAFE_LEFT | KEY_STRAFE_RIGHT) {
                 KEY_STRAFE_LEFT => self.level.move_automap_x(-elapsed_time),
@@ -149,21 +788,407 @@ impl GraphicsLoop for DoomGame {
         } else {
             // in 3D view mode
             match self.key_flags & (KEY_STRAFE_LEFT | KEY_STRAFE_RIGHT) {
-                KEY_STRAFE_LEFT => self.level.strafe_player(-elapsed_time),
-                KEY_STRAFE_RIGHT => self.level.strafe_player(elapsed_time),
+                KEY_STRAFE_LEFT => self.level.strafe_player(-move_time),
+                KEY_STRAFE_RIGHT => self.level.strafe_player(move_time),
                 _ => {}
             }
             match self.key_flags & (KEY_MOVE_FWD | KEY_MOVE_BACK) {
-                KEY_MOVE_FWD => self.level.move_player(elapsed_time),
-                KEY_MOVE_BACK => self.level.move_player(-elapsed_time),
+                KEY_MOVE_FWD => self.level.move_player(move_time),
+                KEY_MOVE_BACK => self.level.move_player(-move_time),
                 _ => {}
             }
         }
 
+        self.level.apply_player_momentum(elapsed_time);
+
+        for msg in self.level.take_messages() {
+            self.hud.push(&msg);
+        }
+        if let Some(exit) = self.level.take_exit() {
+            self.start_intermission(exit);
+        }
+        self.sounds.extend(self.level.take_sound_events());
+        // if nobody takes the sound events, only keep the most recent ones
+        if self.sounds.len() > MAX_QUEUED_SOUNDS {
+            self.sounds.drain(..self.sounds.len() - MAX_QUEUED_SOUNDS);
+        }
         true
     }
 
+    fn set_interpolation(&mut self, alpha: f64) {
+        self.level.set_interpolation(alpha);
+    }
+
+    fn notify_fps(&mut self, fps: u32) {
+        self.change_screen_size_for_fps(fps);
+    }
+
+    fn take_requested_screen_size(&mut self) -> Option<(i32, i32)> {
+        self.requested_screen_size.take()
+    }
+
     fn paint(&self, painter: &mut dyn Painter) {
-        self.level.paint(painter);
+        match &self.intermission {
+            Some(intermission) => self.paint_intermission(intermission, painter),
+            None => self.level.paint(painter),
+        }
+        let timer = PhaseTimer::start(self.level.is_profiling());
+        self.menu.paint(self.cfg.font(), painter);
+        self.hud.paint(self.cfg.font(), painter);
+        self.console.paint(self.cfg.font(), painter);
+        self.level.add_hud_time(timer.elapsed_us());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::AutomapMode;
+    use crate::map_items::LineDef;
+
+    fn test_game() -> DoomGame {
+        let wad_data = WadData::load("DOOM1.WAD", true).unwrap();
+        DoomGame::new(GameConfig::new(wad_data, 320, 200)).unwrap()
+    }
+
+    fn key_down(key: Keycode) -> Event {
+        Event::KeyDown {
+            timestamp: 0,
+            window_id: 0,
+            keycode: Some(key),
+            scancode: None,
+            keymod: sdl2::keyboard::Mod::NOMOD,
+            repeat: false,
+        }
+    }
+
+    fn key_up(key: Keycode) -> Event {
+        Event::KeyUp {
+            timestamp: 0,
+            window_id: 0,
+            keycode: Some(key),
+            scancode: None,
+            keymod: sdl2::keyboard::Mod::NOMOD,
+            repeat: false,
+        }
+    }
+
+    /// How far the player gets from the start of E1M1 in 20 tics, holding some keys
+    /// (the "slow" modifier, if held, moves at half speed).
+    fn walked_distance(always_run: bool, key_flags: u32) -> f64 {
+        let mut game = test_game();
+        game.always_run = always_run;
+        game.slow_factor = 0.5;
+        let start = game.level.player_pos();
+        game.key_flags = key_flags;
+        for _ in 0..20 {
+            game.update_state(1.0 / TICS_PER_SECOND);
+        }
+        let end = game.level.player_pos();
+        ((end.x - start.x) as f64).hypot((end.y - start.y) as f64)
+    }
+
+    /// Place the player on the front side of a line, close to its middle, facing it.
+    fn face_line_front(game: &mut DoomGame, line: &LineDef) -> Vertex {
+        let mid = Vertex {
+            x: (line.v1.x + line.v2.x) / 2,
+            y: (line.v1.y + line.v2.y) / 2,
+        };
+        let front = Angle::from_vector(line.v1, line.v2) - Angle::from_degrees(90);
+        let pos = mid.polar_translate(16.0, front);
+        game.place_player(pos, Angle::from_vector(pos, mid));
+        pos
+    }
+
+    /// Press the "use" key, for one tic.
+    fn press_use(game: &mut DoomGame) {
+        let (&use_key, _) = game.key_bindings.iter().find(|(_, &flag)| flag == KEY_USE).unwrap();
+        game.handle_event(&key_down(use_key));
+        game.update_state(1.0 / TICS_PER_SECOND);
+    }
+
+    fn text_input(text: &str) -> Event {
+        Event::TextInput {
+            timestamp: 0,
+            window_id: 0,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn live_settings_include_the_console_changes() {
+        let mut game = test_game();
+        let settings = UserSettings {
+            slow_factor: 0.3,
+            ..Default::default()
+        };
+        game.apply_settings(&settings);
+        assert_eq!(*game.settings(), settings);
+
+        game.execute_command(ConsoleCommand::Fov(100)).unwrap();
+        assert_eq!(game.settings().fov, 100);
+        assert_eq!(game.settings().slow_factor, 0.3);
+    }
+
+    #[test]
+    fn gamma_setting_is_applied_to_the_palette() {
+        let mut game = test_game();
+        assert_eq!(game.cfg.palette().gamma(), 0);
+        game.apply_settings(&UserSettings {
+            gamma: 3,
+            ..Default::default()
+        });
+        assert_eq!(game.cfg.palette().gamma(), 3);
+        // also for the reloaded WAD
+        game.reload_wad().unwrap();
+        assert_eq!(game.cfg.palette().gamma(), 3);
+    }
+
+    #[test]
+    fn wad_reload_keeps_the_map_player_and_automap() {
+        let mut game = test_game();
+        game.load_map_by_name("E1M3").unwrap();
+        let map_idx = game.map_idx;
+        game.level.player_state_mut().give_all_keys();
+        game.level.set_automap_mode(AutomapMode::Overlay);
+        let camera = game.camera();
+
+        game.reload_wad().unwrap();
+        assert_eq!(game.map_idx, map_idx);
+        assert_eq!(game.level.name(), "E1M3");
+        assert_eq!(game.level.automap_mode(), AutomapMode::Overlay);
+        assert_eq!(game.camera().pos, camera.pos);
+        assert_eq!(game.camera().angle, camera.angle);
+        assert_ne!(game.level.player_state().keys, 0);
+    }
+
+    #[test]
+    fn changing_the_resolution_recomputes_the_projection() {
+        let mut game = test_game();
+        let half_fov = game.cfg.half_fov();
+        let dist = game.cfg.dist_from_screen();
+        assert_eq!(game.take_requested_screen_size(), None);
+
+        game.execute_command(ConsoleCommand::Resolution).unwrap();
+        assert_eq!(game.take_requested_screen_size(), Some((480, 360)));
+        assert_eq!((game.cfg.scr_width(), game.cfg.scr_height()), (480, 360));
+        assert!((game.cfg.dist_from_screen() - dist * 1.8).abs() < 1e-9);
+
+        game.set_screen_size(640, 400);
+        assert_eq!(game.take_requested_screen_size(), Some((640, 400)));
+        assert!((game.cfg.dist_from_screen() - dist * 2.0).abs() < 1e-9);
+        // same aspect ratio => same FOV, and the screen edges are still at the FOV's edges
+        assert_eq!(game.cfg.half_fov(), half_fov);
+        assert_eq!(game.cfg.screen_x_to_angle(0), half_fov);
+        assert_eq!(game.cfg.screen_x_to_angle(640), -half_fov);
+        // the level projects with the new resolution too
+        assert_eq!(game.camera().dist_from_screen, game.cfg.dist_from_screen());
+    }
+
+    #[test]
+    fn typed_text_goes_to_the_open_console() {
+        let mut game = test_game();
+        // while the console is closed, the text is ignored
+        assert!(game.handle_event(&text_input("x")));
+
+        game.handle_event(&key_down(Keycode::Backquote));
+        assert!(game.console.is_open());
+        for text in ["map", " E1M", "4"] {
+            game.handle_event(&text_input(text));
+        }
+        game.handle_event(&key_down(Keycode::Backspace));
+        game.handle_event(&text_input("2"));
+        // the movement keys are typed, instead of moving the player
+        let (&forward, _) = game
+            .key_bindings
+            .iter()
+            .find(|(_, &flag)| flag == KEY_MOVE_FWD)
+            .unwrap();
+        game.handle_event(&key_down(forward));
+        assert_eq!(game.key_flags, 0);
+
+        game.handle_event(&key_down(Keycode::Return));
+        assert_eq!(game.level.name(), "E1M2");
+    }
+
+    #[test]
+    fn loading_a_map_resets_the_palette_selection() {
+        use crate::palette::Palette;
+        use crate::pixmap::ColorMapper;
+
+        let mut game = test_game();
+        let palette = game.cfg.palette();
+        let colors = |palette: &Palette| (0..=255).map(|c| palette.byte2rgb(c)).collect::<Vec<_>>();
+        let normal_colors = colors(palette);
+        palette.select_palette(3).unwrap();
+        palette.select_colormap(20).unwrap();
+        assert!(colors(palette) != normal_colors);
+
+        game.load_map_by_name("E1M2").unwrap();
+        assert!(colors(game.cfg.palette()) == normal_colors);
+        // same when entering the intermission
+        game.cfg.palette().select_colormap(20).unwrap();
+        game.start_intermission(LevelExit::Normal);
+        assert!(colors(game.cfg.palette()) == normal_colors);
+    }
+
+    #[test]
+    fn slow_modifier_halves_the_movement() {
+        let normal = walked_distance(true, KEY_MOVE_FWD);
+        let slow = walked_distance(true, KEY_MOVE_FWD | KEY_SLOW);
+        assert!(normal > 50.0, "{normal}");
+        assert!((slow - normal / 2.0).abs() <= 1.5, "{slow} vs {normal}");
+    }
+
+    #[test]
+    fn always_run_runs_without_the_modifier() {
+        let running = walked_distance(true, KEY_MOVE_FWD);
+        assert_eq!(walked_distance(false, KEY_MOVE_FWD | KEY_SLOW), running);
+        let walking = walked_distance(false, KEY_MOVE_FWD);
+        assert!(
+            (walking - running * WALK_SPEED_FACTOR).abs() <= 1.5,
+            "{walking} vs {running}"
+        );
+    }
+
+    #[test]
+    fn typing_ammo_cheats_fills_the_player_state() {
+        use crate::player::{KeyColor, AMMO_MAX};
+
+        let mut game = test_game();
+        for text in ["id", "f", "a"] {
+            game.handle_event(&text_input(text));
+        }
+        let player = game.level.player_state();
+        assert_eq!((player.ammo, player.armor, player.keys), (AMMO_MAX, 200, 0));
+
+        game.handle_event(&text_input("idkfa"));
+        let player = game.level.player_state();
+        assert_eq!(player.ammo, AMMO_MAX);
+        for color in [KeyColor::Blue, KeyColor::Yellow, KeyColor::Red] {
+            assert!(player.has_key(color));
+        }
+        assert!(!player.god_mode);
+    }
+
+    #[test]
+    fn warp_cheat_loads_the_map() {
+        let mut game = test_game();
+        for ch in "IDCLEV12".chars() {
+            game.handle_event(&text_input(&ch.to_string()));
+        }
+        assert_eq!(game.level.name(), "E1M2");
+    }
+
+    #[test]
+    fn using_a_plain_wall_queues_a_sound_event() {
+        let mut game = test_game();
+        // face the middle of a one-sided wall, without any special
+        let line = game
+            .cfg
+            .wad()
+            .map(0)
+            .linedefs()
+            .find(|line| !line.has_both_sides() && line.special_type == 0)
+            .unwrap();
+        let pos = face_line_front(&mut game, &line);
+        assert!(game.take_sound_events().is_empty());
+
+        press_use(&mut game);
+        assert_eq!(game.take_sound_events(), vec![SoundEvent::at(SOUND_NO_WAY, pos)]);
+        // (the events are only taken once)
+        assert!(game.take_sound_events().is_empty());
+    }
+
+    #[test]
+    fn exit_switches_go_to_the_next_map_after_the_intermission() {
+        // (E1M3 has both a normal and a secret exit)
+        for (special, next_map) in [(11, "E1M4"), (51, "E1M9")] {
+            let mut game = test_game();
+            game.load_map_by_name("E1M3").unwrap();
+            let line = game
+                .cfg
+                .wad()
+                .map(game.map_idx)
+                .linedefs()
+                .find(|line| line.special_type == special)
+                .unwrap();
+            face_line_front(&mut game, &line);
+            press_use(&mut game);
+            assert!(game.intermission.is_some());
+            assert_eq!(game.level.name(), "E1M3");
+
+            // the level is paused during the intermission
+            game.update_state(INTERMISSION_TIME / 2.0);
+            assert_eq!(game.level.name(), "E1M3");
+            game.update_state(INTERMISSION_TIME / 2.0);
+            assert!(game.intermission.is_none());
+            assert_eq!(game.level.name(), next_map);
+            assert_eq!(game.map_idx, game.cfg.wad().map_index(next_map).unwrap());
+        }
+    }
+
+    #[test]
+    fn selecting_a_menu_entry_loads_its_map() {
+        let mut game = test_game();
+        game.handle_event(&key_down(Keycode::M));
+        assert!(game.menu.is_open());
+        // the menu pauses the game
+        let time = game.clock.time();
+        game.update_state(1.0);
+        assert_eq!(game.clock.time(), time);
+
+        // (the current map is selected first)
+        for key in [Keycode::Down, Keycode::Down, Keycode::Down, Keycode::Up] {
+            game.handle_event(&key_down(key));
+        }
+        game.handle_event(&key_down(Keycode::Return));
+        assert!(!game.menu.is_open());
+        assert_eq!(game.level.name(), "E1M3");
+    }
+
+    #[test]
+    fn quick_use_tap_between_updates_acts_once() {
+        let mut game = test_game();
+        let line = game
+            .cfg
+            .wad()
+            .map(0)
+            .linedefs()
+            .find(|line| !line.has_both_sides() && line.special_type == 0)
+            .unwrap();
+        let pos = face_line_front(&mut game, &line);
+
+        // the key is pressed and released before the next update
+        let (&use_key, _) = game.key_bindings.iter().find(|(_, &flag)| flag == KEY_USE).unwrap();
+        game.handle_event(&key_down(use_key));
+        game.handle_event(&key_up(use_key));
+        assert_eq!(game.key_flags & KEY_USE, 0);
+        game.update_state(1.0 / TICS_PER_SECOND);
+        assert_eq!(game.take_sound_events(), vec![SoundEvent::at(SOUND_NO_WAY, pos)]);
+        game.update_state(1.0 / TICS_PER_SECOND);
+        assert!(game.take_sound_events().is_empty());
+    }
+
+    #[test]
+    fn holding_forward_for_some_tics() {
+        let mut game = test_game();
+        let start = game.level.player_pos();
+        let angle = game.level.player_angle();
+        game.key_flags = KEY_MOVE_FWD;
+        game.run_tics(20);
+        assert_eq!(game.clock.tics(), 20);
+
+        // same as 20 updates of one tic each, straight ahead (nothing blocks the way)
+        let end = game.level.player_pos();
+        let distance = ((end.x - start.x) as f64).hypot((end.y - start.y) as f64);
+        assert!(
+            (distance - walked_distance(true, KEY_MOVE_FWD)).abs() <= 1.0,
+            "{distance}"
+        );
+        let ahead = start.polar_translate(distance, angle);
+        assert!((end.x - ahead.x).abs() <= 1 && (end.y - ahead.y).abs() <= 1);
+        // the camera shows the latest position
+        assert_eq!(game.camera().pos, end);
     }
 }
@@ -4,19 +4,60 @@
 //! * Font
 //! * Graphics (patches, flats, textures)
 //! * Palette (and colormap)
+//!
+//! By default, the data is shared via `Rc`. With the `threaded` feature, it is shared via `Arc`,
+//! so that it can also be used from other threads (e.g. for multi-threaded rendering).
 
-use crate::{angle::Angle, font::Font, graphics::Graphics, palette::Palette, WadData};
-use std::rc::Rc;
+use crate::{angle::Angle, font::Font, graphics::Graphics, palette::Palette, WadData, DEFAULT_FOV};
 
-pub struct GameConfig(Rc<InternalGameData>);
+#[cfg(not(feature = "threaded"))]
+type Shared<T> = std::rc::Rc<T>;
+#[cfg(feature = "threaded")]
+type Shared<T> = std::sync::Arc<T>;
+
+// make sure the game data can really be shared between threads
+#[cfg(feature = "threaded")]
+const _: fn() = assert_send_sync::<GameConfig>;
+#[cfg(feature = "threaded")]
+fn assert_send_sync<T: Send + Sync>() {}
+
+pub struct GameConfig(Shared<InternalGameData>);
 
 impl GameConfig {
     pub fn new(wad_data: WadData, scr_width: i32, scr_height: i32) -> Self {
+        Self::with_fov(wad_data, scr_width, scr_height, DEFAULT_FOV)
+    }
+
+    /// Same as `new`, but with a custom horizontal FOV (in degrees, for a 4:3 screen).
+    pub fn with_fov(wad_data: WadData, scr_width: i32, scr_height: i32, fov: i32) -> Self {
         assert!(scr_width > 0);
         assert!(scr_height > 0);
+        assert!(fov > 0 && fov < 180);
         assert!(wad_data.map_count() > 0);
+        Self::build(Shared::new(wad_data), scr_width, scr_height, fov)
+    }
 
-        let dist_from_screen = compute_dist_from_screen(scr_height);
+    /// A copy of this config (sharing the same WAD data), for a different screen resolution.
+    /// All the projection values (distance from screen, FOV angles) are recomputed.
+    pub fn with_resolution(&self, scr_width: i32, scr_height: i32) -> Self {
+        assert!(scr_width > 0);
+        assert!(scr_height > 0);
+        Self::build(Shared::clone(&self.0.wad_data), scr_width, scr_height, self.0.fov)
+    }
+
+    /// A copy of this config (sharing the same WAD data), for a different FOV.
+    pub fn with_changed_fov(&self, fov: i32) -> Self {
+        assert!(fov > 0 && fov < 180);
+        Self::build(
+            Shared::clone(&self.0.wad_data),
+            self.0.scr_width,
+            self.0.scr_height,
+            fov,
+        )
+    }
+
+    fn build(wad_data: Shared<WadData>, scr_width: i32, scr_height: i32, fov: i32) -> Self {
+        let dist_from_screen = compute_dist_from_screen(scr_height, fov);
         let dx = (scr_width as f64) / 2.0;
         let rad = dx.atan2(dist_from_screen);
         let hfov = Angle::from_radians(rad);
@@ -24,10 +65,11 @@ impl GameConfig {
             wad_data,
             scr_width,
             scr_height,
+            fov,
             dist_from_screen,
             hfov,
         };
-        GameConfig(Rc::new(igd))
+        GameConfig(Shared::new(igd))
     }
 
     #[inline]
@@ -40,6 +82,11 @@ impl GameConfig {
         self.0.scr_height
     }
 
+    #[inline]
+    pub fn fov(&self) -> i32 {
+        self.0.fov
+    }
+
     #[inline]
     pub fn wad(&self) -> &WadData {
         &self.0.wad_data
@@ -87,26 +134,69 @@ impl GameConfig {
 
 impl Clone for GameConfig {
     fn clone(&self) -> Self {
-        let rc_clone = Rc::clone(&self.0);
-        Self(rc_clone)
+        Self(Shared::clone(&self.0))
     }
 }
 
 //-------------------
 
 struct InternalGameData {
-    wad_data: WadData,
+    wad_data: Shared<WadData>,
     scr_width: i32,
     scr_height: i32,
+    fov: i32,
     dist_from_screen: f64,
     hfov: Angle,
 }
 
-/// Compute distance from screen, assuming a 4/3 aspect ratio and a 90 degrees FOV,
+/// Compute distance from screen, assuming a 4/3 aspect ratio and the given FOV (in degrees),
 // based on screen height (as if width would be 4/3 of height)
 #[inline]
-fn compute_dist_from_screen(height: i32) -> f64 {
-    let dist_from_screen = (height as f64) * 2.0 / 3.0;
+fn compute_dist_from_screen(height: i32, fov: i32) -> f64 {
+    let half_fov_rad = (fov as f64).to_radians() / 2.0;
+    let dist_from_screen = (height as f64) * 2.0 / 3.0 / half_fov_rad.tan();
     assert!(dist_from_screen > 1.0);
     dist_from_screen
 }
+
+#[cfg(all(test, feature = "threaded"))]
+mod tests {
+    use super::*;
+    use crate::layer::Layer;
+    use crate::Painter;
+
+    /// Paint every texture, and sum up all the painted colors.
+    fn textures_checksum(cfg: &GameConfig) -> u64 {
+        let gfx = cfg.graphics();
+        let mut checksum = 0;
+        for (key, _) in gfx.iter_textures_sorted() {
+            let texture = gfx.get_texture(key).unwrap();
+            let mut layer = Layer::new(texture.width() as i32, texture.height() as i32);
+            texture.paint(0, 0, &mut layer, cfg.palette());
+            for y in 0..layer.get_screen_height() {
+                for x in 0..layer.get_screen_width() {
+                    if let Some(c) = layer.read_pixel(x, y) {
+                        checksum += (c.r as u64) + (c.g as u64) * 3 + (c.b as u64) * 7;
+                    }
+                }
+            }
+        }
+        checksum
+    }
+
+    #[test]
+    fn textures_can_be_read_from_several_threads() {
+        let cfg = GameConfig::new(WadData::load("DOOM1.WAD", true).unwrap(), 320, 200);
+        let expected = textures_checksum(&cfg);
+        assert!(expected > 0);
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let cfg = cfg.clone();
+                std::thread::spawn(move || textures_checksum(&cfg))
+            })
+            .collect();
+        for thread in threads {
+            assert_eq!(thread.join().unwrap(), expected);
+        }
+    }
+}
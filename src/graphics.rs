@@ -3,6 +3,7 @@
 use crate::{
     pixmap::{PixMap, Texture},
     utils::*,
+    WadError,
 };
 use bytes::Bytes;
 use std::collections::HashMap;
@@ -34,40 +35,46 @@ impl Graphics {
         self.flats.insert(key, lump.clone());
     }
 
-    pub fn set_patch_names(&mut self, patches: &Bytes) -> Result<(), String> {
+    pub fn set_patch_names(&mut self, patches: &Bytes) -> Result<(), WadError> {
         if patches.len() <= 4 {
-            return Err(String::from("PNAMES lump size too small"));
+            return Err(bad_lump("PNAMES", format!("size too small: {}", patches.len())));
         }
         let cnt = buf_to_u32(&patches[0..4]) as usize;
         if patches.len() < (4 + cnt * 8) {
-            return Err(String::from("PNAMES lump size too small"));
+            return Err(bad_lump("PNAMES", format!("size too small for {cnt} patches")));
         }
         // OK
         self.pnames = patches.clone();
         Ok(())
     }
 
-    pub fn add_textures(&mut self, bytes: &Bytes) -> Result<(), String> {
+    pub fn add_textures(&mut self, bytes: &Bytes) -> Result<(), WadError> {
         let len = bytes.len();
         if len <= 8 {
-            return Err(format!("TEXTUREx lump size too small: {len}"));
+            return Err(bad_lump("TEXTUREx", format!("size too small: {len}")));
         }
         // number of textures
         let cnt = buf_to_u32(bytes) as usize;
         if len <= 4 + 4 * cnt {
-            return Err(format!("TEXTUREx lump size too small: {len}"));
+            return Err(bad_lump("TEXTUREx", format!("size too small: {len}")));
         }
         // extract bytes for each texture
         for t in 0..cnt {
             let offs = buf_to_u32(&bytes[4 + 4 * t..]) as usize;
             if len <= (offs + 28) {
-                return Err(format!("TEXTUREx entry #{t} out of bounds: len={len} < ofs={offs}"));
+                return Err(bad_lump(
+                    "TEXTUREx",
+                    format!("entry #{t} out of bounds: len={len} < ofs={offs}"),
+                ));
             }
             let key = hash_lump_name(&bytes[offs..offs + 8]);
             let patch_count = buf_to_u16(&bytes[offs + 20..]) as usize;
             let tex_len = 22 + 10 * patch_count;
             if len < (offs + tex_len) {
-                return Err(format!("TEXTUREx entry #{t} out of bounds: len={len} < ofs={offs}"));
+                return Err(bad_lump(
+                    "TEXTUREx",
+                    format!("entry #{t} out of bounds: len={len} < ofs={offs}"),
+                ));
             }
             let tex_bytes = bytes.slice(offs..offs + tex_len);
             self.textures.insert(key, tex_bytes);
@@ -76,6 +83,21 @@ impl Graphics {
         Ok(())
     }
 
+    /// Iterate over all the patches, in a stable order (sorted by key).
+    pub fn iter_patches_sorted(&self) -> impl Iterator<Item = (u64, &Bytes)> {
+        iter_sorted(&self.patches)
+    }
+
+    /// Iterate over all the flats, in a stable order (sorted by key).
+    pub fn iter_flats_sorted(&self) -> impl Iterator<Item = (u64, &Bytes)> {
+        iter_sorted(&self.flats)
+    }
+
+    /// Iterate over all the textures, in a stable order (sorted by key).
+    pub fn iter_textures_sorted(&self) -> impl Iterator<Item = (u64, &Bytes)> {
+        iter_sorted(&self.textures)
+    }
+
     pub fn get_patch(&self, key: u64) -> Option<PixMap> {
         self.patches.get(&key).map(|bytes| PixMap::from_patch(&bytes))
     }
@@ -107,4 +129,72 @@ impl Graphics {
         }
         Some(texture)
     }
+
+    /// Get a texture, or a pink placeholder of the given size, if the texture is missing
+    /// (so that missing textures are obvious, instead of crashing or not painting anything).
+    pub fn get_texture_or_placeholder(&self, key: u64, width: u16, height: u16) -> Texture {
+        self.get_texture(key)
+            .unwrap_or_else(|| Texture::new_placeholder(width, height))
+    }
+
+    /// Get a flat, or a pink 64x64 placeholder, if the flat is missing.
+    pub fn get_flat_or_placeholder(&self, key: u64) -> PixMap {
+        self.get_flat(key).unwrap_or_else(|| PixMap::new_placeholder(64, 64))
+    }
+}
+
+/// HashMap iteration order is not deterministic => sort the entries by key.
+fn iter_sorted(map: &HashMap<u64, Bytes>) -> impl Iterator<Item = (u64, &Bytes)> {
+    let mut entries: Vec<_> = map.iter().map(|(key, bytes)| (*key, bytes)).collect();
+    entries.sort_unstable_by_key(|(key, _)| *key);
+    entries.into_iter()
+}
+
+fn bad_lump(name: &str, reason: String) -> WadError {
+    WadError::BadLump {
+        name: name.to_string(),
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::hash_lump_name;
+    use crate::WadData;
+
+    #[test]
+    fn sorted_iteration_order_is_stable() {
+        // two separate loads have differently seeded hash maps
+        let wad1 = WadData::load("DOOM1.WAD", true).unwrap();
+        let wad2 = WadData::load("DOOM1.WAD", true).unwrap();
+        let (gfx1, gfx2) = (wad1.graphics(), wad2.graphics());
+        let keys = |iter: &mut dyn Iterator<Item = (u64, &bytes::Bytes)>| iter.map(|(key, _)| key).collect::<Vec<_>>();
+
+        let patches = keys(&mut gfx1.iter_patches_sorted());
+        assert!(!patches.is_empty() && patches.is_sorted());
+        assert_eq!(patches, keys(&mut gfx1.iter_patches_sorted()));
+        assert_eq!(patches, keys(&mut gfx2.iter_patches_sorted()));
+        assert_eq!(keys(&mut gfx1.iter_flats_sorted()), keys(&mut gfx2.iter_flats_sorted()));
+        assert_eq!(
+            keys(&mut gfx1.iter_textures_sorted()),
+            keys(&mut gfx2.iter_textures_sorted())
+        );
+    }
+
+    #[test]
+    fn missing_textures_and_flats_get_placeholders() {
+        let wad_data = WadData::load("DOOM1.WAD", true).unwrap();
+        let gfx = wad_data.graphics();
+        let missing_key = hash_lump_name(b"NOSUCHTX");
+        let texture = gfx.get_texture_or_placeholder(missing_key, 96, 40);
+        assert!(texture.is_placeholder());
+        assert_eq!((texture.width(), texture.height()), (96, 40));
+        let flat = gfx.get_flat_or_placeholder(missing_key);
+        assert_eq!((flat.width(), flat.height()), (64, 64));
+
+        // existing textures keep their own size
+        let texture = gfx.get_texture_or_placeholder(hash_lump_name(b"STARTAN3"), 96, 40);
+        assert!(!texture.is_placeholder());
+        assert_eq!((texture.width(), texture.height()), (128, 128));
+    }
 }
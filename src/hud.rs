@@ -0,0 +1,68 @@
+//! Heads-up display: short text messages shown on top of the game view.
+
+use crate::font::{Font, LINE_HEIGHT};
+use crate::layer::Layer;
+use crate::*;
+use std::cell::{Cell, RefCell};
+
+/// How long a message stays on screen, in seconds.
+const MESSAGE_DURATION: f64 = 4.0;
+
+/// Shows one message at a time (like the original game does), for a few seconds.
+/// The message is painted into an offscreen layer only when it changes.
+// TODO also paint the status bar into a layer (invalidated when the player state changes)
+pub struct HudMessages {
+    text: String,
+    time_left: f64,
+    layer: RefCell<Layer>,
+    is_layer_valid: Cell<bool>,
+}
+
+impl HudMessages {
+    pub fn new() -> Self {
+        Self {
+            text: String::new(),
+            time_left: 0.0,
+            layer: RefCell::new(Layer::new(0, 0)),
+            is_layer_valid: Cell::new(false),
+        }
+    }
+
+    /// Show a new message, replacing the current one (if any).
+    pub fn push(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.time_left = MESSAGE_DURATION;
+        self.is_layer_valid.set(false);
+    }
+
+    #[inline]
+    pub fn current(&self) -> Option<&str> {
+        if self.time_left > 0.0 {
+            Some(&self.text)
+        } else {
+            None
+        }
+    }
+
+    pub fn update(&mut self, elapsed_time: f64) {
+        let was_shown = self.time_left > 0.0;
+        self.time_left = (self.time_left - elapsed_time).max(0.0);
+        if was_shown && self.time_left == 0.0 {
+            self.is_layer_valid.set(false);
+        }
+    }
+
+    pub fn paint(&self, font: &Font, painter: &mut dyn Painter) {
+        let mut layer = self.layer.borrow_mut();
+        let was_resized = layer.resize(painter.get_screen_width(), painter.get_screen_height());
+        if was_resized || !self.is_layer_valid.get() {
+            layer.clear_transparent();
+            if let Some(text) = self.current() {
+                let y = layer.get_screen_height() - LINE_HEIGHT;
+                font.draw_text(3, y, text, WHITE, &mut *layer);
+            }
+            self.is_layer_valid.set(true);
+        }
+        layer.composite(painter);
+    }
+}
@@ -0,0 +1,147 @@
+//! Offscreen layers: painted only when their content changes, then composited over the screen
+//! on every frame (e.g. for the HUD, which rarely changes).
+
+use crate::*;
+
+/// An offscreen RGB + alpha surface. Painting into it is opaque (unless blended),
+/// and the pixels which were never painted stay transparent.
+pub struct Layer {
+    width: i32,
+    height: i32,
+    pixels: Vec<(RGB, u8)>,
+    /// The bounds (x1, y1, x2, y2) of the painted pixels - only this area is composited.
+    bounds: Option<(i32, i32, i32, i32)>,
+}
+
+impl Layer {
+    pub fn new(width: i32, height: i32) -> Self {
+        let (width, height) = (width.max(0), height.max(0));
+        Layer {
+            width,
+            height,
+            pixels: vec![(BLACK, 0); (width * height) as usize],
+            bounds: None,
+        }
+    }
+
+    /// Match the screen size. Returns true if the layer was resized (and cleared).
+    pub fn resize(&mut self, width: i32, height: i32) -> bool {
+        let changed = width != self.width || height != self.height;
+        if changed {
+            *self = Self::new(width, height);
+        }
+        changed
+    }
+
+    /// Make the whole layer transparent again.
+    pub fn clear_transparent(&mut self) {
+        if self.bounds.take().is_some() {
+            self.pixels.fill((BLACK, 0));
+        }
+    }
+
+    /// Paint the layer over another painter (the transparent pixels are skipped).
+    pub fn composite(&self, painter: &mut dyn Painter) {
+        let Some((x1, y1, x2, y2)) = self.bounds else {
+            return;
+        };
+        for y in y1..=y2 {
+            let row = (y * self.width) as usize;
+            for x in x1..=x2 {
+                match self.pixels[row + x as usize] {
+                    (_, 0) => {}
+                    (color, 255) => painter.draw_pixel(x, y, color),
+                    (color, alpha) => painter.draw_pixel_blended(x, y, color, alpha),
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn pixel_index(&self, x: i32, y: i32) -> Option<usize> {
+        (x >= 0 && y >= 0 && x < self.width && y < self.height).then_some((y * self.width + x) as usize)
+    }
+
+    fn store_pixel(&mut self, idx: usize, x: i32, y: i32, pixel: (RGB, u8)) {
+        self.pixels[idx] = pixel;
+        self.bounds = Some(match self.bounds {
+            None => (x, y, x, y),
+            Some((x1, y1, x2, y2)) => (x1.min(x), y1.min(y), x2.max(x), y2.max(y)),
+        });
+    }
+}
+
+impl Painter for Layer {
+    fn get_screen_width(&self) -> i32 {
+        self.width
+    }
+
+    fn get_screen_height(&self) -> i32 {
+        self.height
+    }
+
+    fn draw_pixel(&mut self, x: i32, y: i32, color: RGB) {
+        if let Some(idx) = self.pixel_index(x, y) {
+            self.store_pixel(idx, x, y, (color, 255));
+        }
+    }
+
+    fn read_pixel(&self, x: i32, y: i32) -> Option<RGB> {
+        let (color, alpha) = self.pixels[self.pixel_index(x, y)?];
+        (alpha > 0).then_some(color)
+    }
+
+    /// Over a transparent pixel, the translucent color is kept as it is (to be blended when compositing).
+    fn draw_pixel_blended(&mut self, x: i32, y: i32, color: RGB, alpha: u8) {
+        let Some(idx) = self.pixel_index(x, y) else {
+            return;
+        };
+        let (old_color, old_alpha) = self.pixels[idx];
+        let pixel = if old_alpha == 0 {
+            (color, alpha)
+        } else {
+            (color.blend_over(old_color, alpha), old_alpha.max(alpha))
+        };
+        if pixel.1 > 0 {
+            self.store_pixel(idx, x, y, pixel);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_painted_pixels_are_composited() {
+        let mut background = Layer::new(40, 16);
+        background.fill_rect(0, 0, 40, 16, RED);
+
+        // an opaque square, a translucent square, and nothing else
+        let mut layer = Layer::new(40, 16);
+        layer.fill_rect(4, 4, 8, 8, BLUE);
+        for y in 4..12 {
+            for x in 20..28 {
+                layer.draw_pixel_blended(x, y, GREEN, 128);
+            }
+        }
+        layer.composite(&mut background);
+        for y in 0..16 {
+            for x in 0..40 {
+                let expected = match (x, y) {
+                    (4..12, 4..12) => BLUE,
+                    (20..28, 4..12) => GREEN.blend_over(RED, 128),
+                    _ => RED,
+                };
+                assert!(background.read_pixel(x, y) == Some(expected), "pixel {x},{y}");
+            }
+        }
+
+        // once cleared, the layer does not paint anything
+        let mut background = Layer::new(40, 16);
+        background.fill_rect(0, 0, 40, 16, RED);
+        layer.clear_transparent();
+        layer.composite(&mut background);
+        assert!((0..16).all(|y| (0..40).all(|x| background.read_pixel(x, y) == Some(RED))));
+    }
+}
@@ -2,24 +2,22 @@
 //! Built from an existing MapData.
 
 use crate::angle::Angle;
+use crate::clock::TICS_PER_SECOND;
+#[cfg(feature = "fixed_point")]
+use crate::fixed::{Fixed, FRACBITS};
 use crate::map::*;
 use crate::map_items::*;
-use crate::pixmap::Texture;
-use crate::things::Thing;
+use crate::pixmap::{ColumnSampling, Texture};
+use crate::player::PlayerState;
+use crate::specials::{self, LevelExit};
+use crate::things::{Thing, DEATHMATCH_START_TYPE, TELEPORT_DEST_TYPE};
+use crate::trig;
 use crate::utils::*;
+use crate::walls::{self, DistanceFog, SegRenderData, MAX_LIGHT_LEVEL};
 use crate::*;
-use std::cell::RefCell;
-
-// LineDef flags
-const LINE_BLOCKS: u16 = 0x0001;
-//const LINE_BLOCKS_MONSTERS: u16 = 0x0002;
-const LINE_TWO_SIDED: u16 = 0x0004;
-//const LINE_UPPER_UNPEGGED: u16 = 0x0008;
-//const LINE_LOWER_UNPEGGED: u16 = 0x0010;
-const LINE_SECRET: u16 = 0x0020;
-//const LINE_BLOCKS_SND: u16 = 0x0040;
-const LINE_NEVER_ON_AMAP: u16 = 0x0080;
-const LINE_ALWAYS_ON_AMAP: u16 = 0x0100;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::time::Instant;
 
 // Automap zoom limits
 const DEFAULT_AUTOMAP_ZOOM: f64 = 0.1875;
@@ -31,61 +29,308 @@ const SSECTOR_FLAG: u16 = 0x8000;
 
 const AMAP_MOVE_SPEED: f64 = 800.0;
 const AMAP_ZOOM_SPEED: f64 = 0.0625;
+/// The player's top speed (in map units per second).
 const PLAYER_MOVE_SPEED: f64 = 200.0;
+/// Moving backward is slower than moving forward, by this factor.
+/// (vanilla DOOM uses the same speed for both - but backpedaling slower feels more natural)
+const PLAYER_BACKPEDAL_FACTOR: f64 = 0.75;
+/// Like vanilla DOOM, the player's momentum is multiplied by this, on every tic
+/// (see `TICS_PER_SECOND` - the friction is scaled to the elapsed time).
+const PLAYER_FRICTION: f64 = 0.90625;
+/// Below this speed (in map units per second), the player stops.
+const PLAYER_STOP_SPEED: f64 = 2.0;
+/// The maximum view bobbing, like vanilla DOOM's `MAXBOB` (the eye moves up/down half of this).
+const VIEW_BOB_MAX: f64 = 16.0;
+/// One up-and-down bobbing cycle lasts this many tics.
+const VIEW_BOB_PERIOD_TICS: f64 = 20.0;
 const PLAYER_ROT_SPEED: f64 = 1.5;
+const PLAYER_USE_RANGE: f64 = 64.0;
+const PLAYER_HEIGHT: i32 = 56;
+const PLAYER_MAX_STEP: i32 = 24;
+/// The player's eye height, above the floor (like vanilla DOOM's `VIEWHEIGHT`).
+const PLAYER_VIEW_HEIGHT: f64 = 41.0;
+/// The size of the (vanilla) sky textures - used for the placeholder, if the sky texture is missing.
+const SKY_WIDTH: u16 = 256;
+const SKY_HEIGHT: u16 = 128;
+/// Like in vanilla DOOM, the sky repeats every 1024 columns for a full turn (4 times, for 256 wide skies)
+/// and its texture row 100 is at the horizon (at the original 200 pixels screen height).
+const SKY_COLUMNS_PER_TURN: f64 = 1024.0;
+const SKY_HORIZON_ROW: f64 = 100.0;
 
-const AUTOMAP_OLD_STYLE_ARROW: bool = false;
+// Automap colors
+const AMAP_WALL_COLOR: RGB = RED;
+const AMAP_TELEPORTER_COLOR: RGB = RGB { r: 112, g: 0, b: 0 };
+const AMAP_FLOOR_DELTA_COLOR: RGB = CHOCO;
+const AMAP_CEILING_DELTA_COLOR: RGB = YELLOW;
+const AMAP_TWO_SIDED_COLOR: RGB = DARK_GREY;
+const AMAP_COMPUTER_MAP_COLOR: RGB = GREY;
+const AMAP_SECRET_COLOR: RGB = GREEN;
+const AMAP_SPECIAL_COLOR: RGB = BLUE;
+const AMAP_TELEPORT_DEST_COLOR: RGB = MAGENTA;
+const AMAP_PLAYER_START_COLOR: RGB = GREEN;
+const AMAP_DEATHMATCH_START_COLOR: RGB = ORANGE;
+const AMAP_SUBSECTOR_ALPHA: u8 = 96;
+const AMAP_BORDER_COLOR: RGB = GREY;
+/// The opacity of the automap, when it is shown over the 3D view.
+const AMAP_OVERLAY_ALPHA: u8 = 160;
+/// The status bar height, in the original 320x200 resolution.
+const STATUS_BAR_HEIGHT: i32 = 32;
+const ORIGINAL_SCREEN_HEIGHT: i32 = 200;
+/// The size of the spawn markers, in pixels (they do not scale with the zoom).
+const AMAP_MARKER_SIZE: i32 = 3;
+
+// Linedef special for the (walk-over, once) teleporter
+const TELEPORT_LINE_SPECIAL: u16 = 39;
 
 // Level Flags
 const FLAG_AUTOMAP_ON: u32 = 1 << 0;
 const FLAG_HAS_COMPUTER_MAP: u32 = 1 << 1;
 const FLAG_AUTOMAP_EXTRA_COLORS: u32 = 1 << 2;
+const FLAG_FAKE_CONTRAST: u32 = 1 << 3;
+const FLAG_VIEW_BOB: u32 = 1 << 4;
+const FLAG_AUTOMAP_CULLING: u32 = 1 << 5;
+const FLAG_AUTOMAP_OVERLAY: u32 = 1 << 6;
+
+/// How the automap is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutomapMode {
+    Off,
+    /// The automap replaces the 3D view.
+    Full,
+    /// The automap is painted (translucent) on top of the 3D view.
+    Overlay,
+}
+
+/// A read-only snapshot of everything needed to render the player's view
+/// (e.g. for plugging in a different renderer).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub pos: Vertex,
+    /// The eye height (including the view bobbing).
+    pub eye_z: f64,
+    pub angle: Angle,
+    pub half_fov: Angle,
+    /// The distance from the eye to the projection plane (the screen), in pixels.
+    pub dist_from_screen: f64,
+}
+
+impl Camera {
+    /// Project a world point (at height `z`) on a screen of the given size.
+    /// Returns the screen position and the depth (the distance along the view direction),
+    /// or `None` if the point is behind the camera or outside the horizontal FOV.
+    /// (the screen y is not clipped, so points above or below the screen are still returned)
+    pub fn project(&self, world: Vertex, z: f64, scr_width: i32, scr_height: i32) -> Option<(i32, i32, f64)> {
+        let dx = (world.x - self.pos.x) as f64;
+        let dy = (world.y - self.pos.y) as f64;
+        let (sin, cos) = self.angle.rad().sin_cos();
+        // rotate into view space: depth is forward, side is to the left
+        let depth = dx * cos + dy * sin;
+        let side = dy * cos - dx * sin;
+        if depth < 1.0 {
+            return None;
+        }
+        let scale = self.dist_from_screen / depth;
+        let x = ((scr_width as f64) / 2.0 - side * scale).round() as i32;
+        if x < 0 || x >= scr_width {
+            return None;
+        }
+        let y = ((scr_height as f64) / 2.0 - (z - self.eye_z) * scale).round() as i32;
+        Some((x, y, depth))
+    }
+}
+
+/// Statistics about the last painted frame, for profiling and debugging.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RenderStats {
+    /// The segs collected by the BSP traversal (which faced the player, within the FOV).
+    pub segs_collected: u32,
+    /// The collected segs which painted at least one column (the others were hidden).
+    pub segs_drawn: u32,
+    pub columns_drawn: u32,
+    pub subsectors_visited: u32,
+    /// How many painter calls the 3D view made.
+    pub draw_calls: u32,
+    /// The time spent in each painting phase, in microseconds (only measured while profiling).
+    pub bsp_time_us: u32,
+    pub sky_time_us: u32,
+    pub walls_time_us: u32,
+    pub automap_time_us: u32,
+    pub hud_time_us: u32,
+    pub total_time_us: u32,
+}
+
+/// Measures how long a painting phase takes - but only if profiling is on,
+/// so it costs (almost) nothing otherwise.
+pub(crate) struct PhaseTimer(Option<Instant>);
+
+impl PhaseTimer {
+    #[inline]
+    pub fn start(is_profiling: bool) -> Self {
+        Self(is_profiling.then(Instant::now))
+    }
+
+    /// The elapsed time since the start, in microseconds (0 if not profiling).
+    #[inline]
+    pub fn elapsed_us(&self) -> u32 {
+        self.0.map_or(0, |start| start.elapsed().as_micros() as u32)
+    }
+}
 
 pub struct ActiveLevel {
     cfg: GameConfig,
     map_data: MapData,
     player: Thing,
+    prev_player: Thing,
+    interpolation: f64,
     amap_center: Vertex,
     amap_zoom: f64,
     sky: Texture,
     player_x: f64,
     player_y: f64,
+    vel_x: f64,
+    vel_y: f64,
+    /// The fraction of a tic which the friction was not applied for yet (it works in whole tics).
+    #[cfg(feature = "fixed_point")]
+    friction_tics: f64,
+    /// The game time (from the game clock), which drives the time-based animations.
+    game_time: f64,
+    /// For how long (in seconds) the player has been turning in the same direction (signed).
+    turn_held_time: f64,
     amap_cx: f64,
     amap_cy: f64,
     flags: u32,
+    classic_arrow: bool,
+    show_subsectors: bool,
+    is_profiling: bool,
+    exit: Option<LevelExit>,
+    amap_thickness: i32,
+    teleport_keeps_momentum: bool,
+    fog: Option<DistanceFog>,
+    render_stats: Cell<RenderStats>,
+    /// The stats of the frame before, for the profiling overlay.
+    prev_render_stats: Cell<RenderStats>,
     seen_lines: RefCell<Vec<u8>>,
+    triggered_lines: Vec<u8>,
+    /// The lines which are not part of any seg (so the BSP traversal never finds them).
+    segless_lines: Vec<usize>,
+    player_state: PlayerState,
+    messages: Vec<String>,
+    sounds: Vec<SoundEvent>,
+    texture_overrides: HashMap<u16, [u64; 3]>,
+    noclip: bool,
 }
 
 impl ActiveLevel {
     pub fn new(cfg: GameConfig, map_idx: usize) -> Self {
+        // tints and colormaps from the previous level must not bleed into this one
+        cfg.palette().reset_selection();
         let map_data = cfg.wad().map(map_idx).clone();
         let player = find_player_thing(&map_data);
         let pc = player.pos;
         let amap_center = player.pos;
         let sky = load_sky(&cfg);
         let seen_lines_size = (map_data.linedef_count() + 7) >> 3;
+        let segless_lines = find_segless_lines(&map_data);
         let flags = 0xFFFF; // TODO should be 0 in normal gameplay
         Self {
             cfg,
             map_data,
+            prev_player: player.clone(),
+            interpolation: 1.0,
             player,
             amap_center,
             amap_zoom: DEFAULT_AUTOMAP_ZOOM,
             sky,
             player_x: pc.x as f64,
             player_y: pc.y as f64,
+            vel_x: 0.0,
+            vel_y: 0.0,
+            #[cfg(feature = "fixed_point")]
+            friction_tics: 0.0,
+            game_time: 0.0,
+            turn_held_time: 0.0,
             amap_cx: amap_center.x as f64,
             amap_cy: amap_center.y as f64,
             flags,
+            classic_arrow: false,
+            show_subsectors: false,
+            is_profiling: false,
+            exit: None,
+            amap_thickness: 1,
+            teleport_keeps_momentum: false,
+            fog: None,
+            render_stats: Cell::new(RenderStats::default()),
+            prev_render_stats: Cell::new(RenderStats::default()),
             seen_lines: RefCell::new(vec![0; seen_lines_size]),
+            triggered_lines: vec![0; seen_lines_size],
+            segless_lines,
+            player_state: PlayerState::new(),
+            messages: Vec::new(),
+            sounds: Vec::new(),
+            texture_overrides: HashMap::new(),
+            noclip: false,
         }
     }
 
+    /// Switch to another game config (e.g. after changing the screen resolution).
+    /// The config must be for the same WAD data.
+    pub fn set_game_config(&mut self, cfg: GameConfig) {
+        self.cfg = cfg;
+    }
+
     #[inline]
     pub fn name(&self) -> &str {
         &self.map_data.name()
     }
 
+    #[inline]
+    pub fn player_pos(&self) -> Vertex {
+        self.player.pos
+    }
+
+    #[inline]
+    pub fn player_angle(&self) -> Angle {
+        self.player.angle
+    }
+
+    #[inline]
+    pub fn player_state(&self) -> &PlayerState {
+        &self.player_state
+    }
+
+    #[inline]
+    pub fn player_state_mut(&mut self) -> &mut PlayerState {
+        &mut self.player_state
+    }
+
+    /// If the player has just exited the level, take how they exited (only once).
+    pub fn take_exit(&mut self) -> Option<LevelExit> {
+        self.exit.take()
+    }
+
+    /// Take the sound events which were generated since the last call.
+    pub fn take_sound_events(&mut self) -> Vec<SoundEvent> {
+        std::mem::take(&mut self.sounds)
+    }
+
+    /// Take the messages for the player (to be shown in the HUD) which were generated since the last call.
+    pub fn take_messages(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.messages)
+    }
+
+    /// Move the player to the given position, facing the given angle.
+    pub fn place_player(&mut self, pos: Vertex, angle: Angle) {
+        self.player.pos = pos;
+        self.player.angle = angle;
+        self.player_x = pos.x as f64;
+        self.player_y = pos.y as f64;
+        // teleporting also kills the player's momentum
+        self.vel_x = 0.0;
+        self.vel_y = 0.0;
+        // no interpolation when "jumping" to a new position
+        self.prev_player = self.player.clone();
+    }
+
     pub fn move_automap_x(&mut self, dx: f64) {
         self.amap_cx += dx * AMAP_MOVE_SPEED;
         let (cv, was_clamped) = clamp_value(self.amap_cx as i32, self.map_data.min_x(), self.map_data.max_x());
@@ -109,50 +354,657 @@ impl ActiveLevel {
         self.amap_zoom = f64::clamp(new_zoom, AUTOMAP_ZOOM_MIN, AUTOMAP_ZOOM_MAX);
     }
 
+    /// Move the player forward (or backward, for a negative time - which is slower).
     pub fn move_player(&mut self, ellapsed_time: f64) {
-        self.translate_player(ellapsed_time, self.player.angle);
+        let move_time = if ellapsed_time < 0.0 {
+            ellapsed_time * PLAYER_BACKPEDAL_FACTOR
+        } else {
+            ellapsed_time
+        };
+        self.accelerate_player(move_time, self.player.angle);
     }
 
     pub fn strafe_player(&mut self, ellapsed_time: f64) {
-        self.translate_player(ellapsed_time, self.player.angle - Angle::with_90_deg());
+        self.accelerate_player(ellapsed_time, self.player.angle - Angle::with_90_deg());
     }
 
+    /// Move the player based on their momentum, then slow them down (by friction).
+    /// Should be called once per update, after all the `move_player`/`strafe_player` calls.
+    pub fn apply_player_momentum(&mut self, ellapsed_time: f64) {
+        if self.vel_x == 0.0 && self.vel_y == 0.0 {
+            return;
+        }
+        self.translate_player(self.vel_x * ellapsed_time, self.vel_y * ellapsed_time);
+        self.apply_player_friction(ellapsed_time);
+        if self.vel_x.hypot(self.vel_y) < PLAYER_STOP_SPEED {
+            self.vel_x = 0.0;
+            self.vel_y = 0.0;
+        }
+    }
+
+    /// Sync with the game clock (see `GameClock`), once per update.
+    #[inline]
+    pub fn set_game_time(&mut self, game_time: f64) {
+        self.game_time = game_time;
+    }
+
+    /// Rotate the player directly (1:1, without any acceleration - e.g. for mouse turning).
     pub fn rotate_player(&mut self, ellapsed_time: f64) {
         self.player.angle = self.player.angle + ellapsed_time * PLAYER_ROT_SPEED;
     }
 
-    fn translate_player(&mut self, ellapsed_time: f64, angle: Angle) {
-        let (dx, dy) = float_polar_translate(ellapsed_time * PLAYER_MOVE_SPEED, angle);
-        self.player_x += dx;
-        self.player_y += dy;
+    /// Rotate the player, for a held turn key: the turn starts at `start_factor` of the full speed,
+    /// then speeds up to the full speed during `ramp_time` seconds (like vanilla DOOM's slow turn start,
+    /// which makes small turns easy to aim). Turning the other way starts the ramp over.
+    pub fn turn_player(&mut self, ellapsed_time: f64, ramp_time: f64, start_factor: f64) {
+        if self.turn_held_time * ellapsed_time < 0.0 {
+            self.turn_held_time = 0.0;
+        }
+        self.turn_held_time += ellapsed_time;
+        let factor = if ramp_time <= 0.0 {
+            1.0
+        } else {
+            let ramp = (self.turn_held_time.abs() / ramp_time).min(1.0);
+            start_factor + (1.0 - start_factor) * ramp
+        };
+        self.rotate_player(ellapsed_time * factor);
+    }
+
+    /// No turn key is held anymore => the next turn starts slowly again.
+    #[inline]
+    pub fn stop_turning(&mut self) {
+        self.turn_held_time = 0.0;
+    }
+
+    /// Toggle walking through walls. Returns the new state.
+    pub fn toggle_noclip(&mut self) -> bool {
+        self.noclip = !self.noclip;
+        self.noclip
+    }
+
+    /// Thrust the player in the given direction: the speed is gained gradually and, when
+    /// thrusting continuously, it converges to `PLAYER_MOVE_SPEED` (because of the friction).
+    fn accelerate_player(&mut self, ellapsed_time: f64, angle: Angle) {
+        let thrust = PLAYER_MOVE_SPEED * (1.0 - PLAYER_FRICTION) * TICS_PER_SECOND;
+        let (dvx, dvy) = float_polar_translate(ellapsed_time * thrust, angle);
+        self.vel_x += dvx;
+        self.vel_y += dvy;
+    }
+
+    #[cfg(not(feature = "fixed_point"))]
+    fn apply_player_friction(&mut self, ellapsed_time: f64) {
+        let friction = PLAYER_FRICTION.powf(ellapsed_time * TICS_PER_SECOND);
+        self.vel_x *= friction;
+        self.vel_y *= friction;
+    }
+
+    /// Vanilla friction: `FixedMul(momentum, FRICTION)`, once per (whole) tic.
+    /// The tic fractions of the frames add up, until they make a whole tic.
+    #[cfg(feature = "fixed_point")]
+    fn apply_player_friction(&mut self, ellapsed_time: f64) {
+        let friction = Fixed::from_f64(PLAYER_FRICTION);
+        // (with a tiny tolerance, for the rounding errors of the frame times)
+        self.friction_tics += ellapsed_time * TICS_PER_SECOND;
+        let tics = (self.friction_tics + 1e-6).floor().max(0.0);
+        self.friction_tics -= tics;
+        let tics = tics as u32;
+        let (mut vx, mut vy) = (Fixed::from_f64(self.vel_x), Fixed::from_f64(self.vel_y));
+        for _ in 0..tics {
+            vx = vx.mul(friction);
+            vy = vy.mul(friction);
+        }
+        self.vel_x = vx.to_f64();
+        self.vel_y = vy.to_f64();
+    }
+
+    /// The new player position, after moving by (dx, dy).
+    /// With the `fixed_point` feature, the positions are kept exactly on the 16.16 grid,
+    /// so the collision checks (which use the same values) see the vanilla coordinates.
+    #[cfg(not(feature = "fixed_point"))]
+    #[inline]
+    fn player_destination(&self, dx: f64, dy: f64) -> (f64, f64) {
+        (self.player_x + dx, self.player_y + dy)
+    }
+
+    #[cfg(feature = "fixed_point")]
+    #[inline]
+    fn player_destination(&self, dx: f64, dy: f64) -> (f64, f64) {
+        let x = Fixed::from_f64(self.player_x) + Fixed::from_f64(dx);
+        let y = Fixed::from_f64(self.player_y) + Fixed::from_f64(dy);
+        (x.to_f64(), y.to_f64())
+    }
+
+    fn translate_player(&mut self, dx: f64, dy: f64) {
+        let old_pos = self.player.pos;
+        let (x, y) = (self.player_x, self.player_y);
+        let (dest_x, dest_y) = self.player_destination(dx, dy);
+        // if the move is blocked, try to slide along the wall (move only on X or only on Y)
+        let new_pos = [(dest_x, dest_y), (dest_x, y), (x, dest_y)]
+            .into_iter()
+            .find(|(nx, ny)| self.noclip || self.can_move(x, y, *nx, *ny, false));
+        // the momentum is lost on the blocked direction(s)
+        let Some((new_x, new_y)) = new_pos else {
+            self.vel_x = 0.0;
+            self.vel_y = 0.0;
+            return;
+        };
+        if new_x == x {
+            self.vel_x = 0.0;
+        }
+        if new_y == y {
+            self.vel_y = 0.0;
+        }
+        self.player_x = new_x;
+        self.player_y = new_y;
         self.player.pos = Vertex {
-            x: self.player_x as i32,
-            y: self.player_y as i32,
+            x: map_units(self.player_x),
+            y: map_units(self.player_y),
+        };
+        self.check_crossed_lines(old_pos, self.player.pos);
+    }
+
+    /// Check if an actor (the player or a monster) can move from (x1, y1) to (x2, y2),
+    /// without crossing any blocking line.
+    fn can_move(&self, x1: f64, y1: f64, x2: f64, y2: f64, is_monster: bool) -> bool {
+        let (corner1, corner2) = move_bounding_box(x1, y1, x2, y2);
+        self.map_data.blockmap_lines(corner1, corner2).into_iter().all(|idx| {
+            let line = self.map_data.linedef(idx as usize);
+            match move_crosses_line(&line, x1, y1, x2, y2) {
+                Some(from_front) => !self.is_line_blocking(&line, from_front, is_monster),
+                None => true,
+            }
+        })
+    }
+
+    /// Check if a line blocks an actor, when trying to cross it from the given side.
+    /// Some lines only block the monsters, but not the player.
+    fn is_line_blocking(&self, line: &LineDef, from_front: bool, is_monster: bool) -> bool {
+        if !line.has_both_sides() || (line.flags & LINE_BLOCKS) != 0 {
+            return true;
+        }
+        if is_monster && (line.flags & LINE_BLOCKS_MONSTERS) != 0 {
+            return true;
+        }
+        // broken lines (with an invalid side or sector) block everything
+        let (Some((_, front)), Some((_, back))) = (
+            self.side_and_sector(line.right_side_idx),
+            self.side_and_sector(line.left_side_idx),
+        ) else {
+            return true;
+        };
+        let (from, to) = if from_front { (front, back) } else { (back, front) };
+        let floor = Ord::max(from.floor_height, to.floor_height) as i32;
+        let ceiling = Ord::min(from.ceiling_height, to.ceiling_height) as i32;
+        (ceiling - floor) < PLAYER_HEIGHT || (to.floor_height - from.floor_height) as i32 > PLAYER_MAX_STEP
+    }
+
+    /// Check which walk-over trigger lines were crossed by the player while moving, and trigger them.
+    /// A line is triggered only when the player goes from one of its sides to the other,
+    /// so standing on (or near) a line does not trigger it on every frame.
+    fn check_crossed_lines(&mut self, from: Vertex, to: Vertex) {
+        if from == to {
+            return;
+        }
+        for idx in self.map_data.blockmap_lines(from, to) {
+            let idx = idx as usize;
+            let line = self.map_data.linedef(idx);
+            if specials::is_walk_trigger(line.special_type) && !self.was_line_triggered(idx) {
+                let from_front = line.is_on_front_side(from);
+                if from_front != line.is_on_front_side(to) && line.intersection_fraction(from, to).is_some() {
+                    self.trigger_line_special(idx, &line, from_front);
+                }
+            }
+        }
+    }
+
+    /// Trigger a walk-over special, when the player crosses its line.
+    fn trigger_line_special(&mut self, line_idx: usize, line: &LineDef, from_front: bool) {
+        if let Some(exit) = specials::level_exit(line.special_type) {
+            self.exit = Some(exit);
+        } else if specials::is_player_teleport(line.special_type) {
+            // teleporters only work when crossed from their front side
+            if !from_front || !self.teleport_player(line.sector_tag) {
+                return;
+            }
+        } else {
+            // TODO implement the actual specials (doors, lifts etc)
+        }
+        if specials::is_walk_once(line.special_type) {
+            self.line_was_triggered(line_idx);
+        }
+    }
+
+    /// Debug helper: move the player to the center of the automap, keeping the player's angle.
+    /// Returns false (and does not move the player) if that point is outside the map.
+    pub fn jump_to_automap_center(&mut self) -> bool {
+        let target = self.amap_center;
+        if !self.is_inside_map(target) {
+            return false;
+        }
+        self.place_player(target, self.player.angle);
+        true
+    }
+
+    /// Teleport the player to the teleport destination thing in the sector(s) with the given tag.
+    /// Returns false if there is no such destination.
+    fn teleport_player(&mut self, sector_tag: u16) -> bool {
+        let destination = self.map_data.things().find(|thing| {
+            thing.type_code() == TELEPORT_DEST_TYPE
+                && self.sector_at(thing.pos).is_some_and(|sect| sect.tag_nr == sector_tag)
+        });
+        match destination {
+            Some(thing) => {
+                // the teleport sound is heard both where the player leaves and where they arrive
+                self.sounds.push(SoundEvent::at(SOUND_TELEPORT, self.player.pos));
+                self.sounds.push(SoundEvent::at(SOUND_TELEPORT, thing.pos));
+                let velocity = (self.vel_x, self.vel_y);
+                self.place_player(thing.pos, thing.angle);
+                if self.teleport_keeps_momentum {
+                    (self.vel_x, self.vel_y) = velocity;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Find the sub-sector which contains a point, by descending the BSP tree.
+    fn subsector_at(&self, point: Vertex) -> u16 {
+        let mut node_idx = self.map_data.root_bsp_node_idx();
+        while (node_idx & SSECTOR_FLAG) == 0 {
+            let node = self.map_data.bsp_node(node_idx as usize);
+            node_idx = node.child_indices_based_on_point_pos(point).0;
+        }
+        node_idx & !SSECTOR_FLAG
+    }
+
+    /// Check if a point is inside the map (i.e. inside some sector).
+    /// The BSP descent always ends in a sub-sector, but the point may still be outside of it,
+    /// beyond its segs - which all have their sector on their right side.
+    fn is_inside_map(&self, point: Vertex) -> bool {
+        let segs = self.map_data.sub_sector(self.subsector_at(point) as usize);
+        segs.iter().all(|seg| {
+            let dir = seg.end - seg.start;
+            let pvect = point - seg.start;
+            (pvect.x as i64) * (dir.y as i64) - (pvect.y as i64) * (dir.x as i64) >= 0
+        })
+    }
+
+    /// Find the sector which contains a point.
+    /// (`None` only for broken maps)
+    fn sector_at(&self, point: Vertex) -> Option<Sector> {
+        // all the segs of a sub-sector are in the same sector
+        let segs = self.map_data.sub_sector(self.subsector_at(point) as usize);
+        Some(self.seg_render_data(segs.first()?)?.front_sector)
+    }
+
+    /// Use (press) the closest line in front of the player, within "arm's reach".
+    /// Like in vanilla DOOM, the "use" check passes through two-sided lines and stops at
+    /// the first line with a special, or at the first one-sided line.
+    pub fn use_line(&mut self) {
+        let from = self.player.pos;
+        let to = from.polar_translate(PLAYER_USE_RANGE, self.player.angle);
+        let mut crossed_lines = Vec::new();
+        for (idx, line) in self.map_data.linedefs().enumerate() {
+            if let Some(frac) = line.intersection_fraction(from, to) {
+                crossed_lines.push((frac, idx));
+            }
+        }
+        crossed_lines.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        for (_, idx) in crossed_lines {
+            let line = self.map_data.linedef(idx);
+            if line.special_type != 0 {
+                // specials can only be used from their front side
+                if line.is_on_front_side(from) {
+                    self.activate_line_special(idx, &line);
+                }
+                return;
+            }
+            if !line.has_both_sides() {
+                self.sounds.push(SoundEvent::at(SOUND_NO_WAY, from));
+                return;
+            }
+        }
+    }
+
+    /// Resolve everything needed for rendering a seg: its linedef, plus the front and back
+    /// sidedefs and sectors, as seen from the side of the linedef that the seg is on.
+    /// (`None` for broken maps, with invalid side or sector indices)
+    pub fn seg_render_data(&self, seg: &Seg) -> Option<SegRenderData> {
+        let linedef = self.map_data.linedef(seg.linedef_idx as usize);
+        // segs going in the same direction as their linedef are on its right (front) side
+        let (mut front_idx, mut back_idx) = if seg.direction_same {
+            (linedef.right_side_idx, linedef.left_side_idx)
+        } else {
+            (linedef.left_side_idx, linedef.right_side_idx)
+        };
+        if front_idx == NO_SIDEDEF {
+            // broken map => at least render the side that exists
+            std::mem::swap(&mut front_idx, &mut back_idx);
         }
+        let (front_side, front_sector) = self.side_and_sector(front_idx)?;
+        let (back_side, back_sector) = self.side_and_sector(back_idx).unzip();
+        Some(SegRenderData {
+            linedef,
+            front_side,
+            front_sector,
+            back_side,
+            back_sector,
+            seg_offset: seg.offset,
+        })
     }
 
+    /// Developer mode, for stress testing the renderer: replace the textures of all the sidedefs
+    /// with random (but valid) ones. The same seed always picks the same textures.
+    /// The map data is not changed - the random textures are kept as overrides.
+    pub fn randomize_textures(&mut self, seed: u64) {
+        let texture_keys: Vec<u64> = self.cfg.graphics().iter_textures_sorted().map(|(key, _)| key).collect();
+        self.texture_overrides.clear();
+        if texture_keys.is_empty() {
+            return;
+        }
+        let rng = fastrand::Rng::with_seed(seed);
+        let pick = |key: u64| {
+            // keep the "no texture" markers, so see-through walls stay that way
+            if is_no_texture(key) {
+                key
+            } else {
+                texture_keys[rng.usize(..texture_keys.len())]
+            }
+        };
+        for idx in 0..self.map_data.sidedef_count() {
+            let side = self.map_data.sidedef(idx);
+            let keys = [
+                pick(side.upper_texture_key),
+                pick(side.lower_texture_key),
+                pick(side.middle_texture_key),
+            ];
+            self.texture_overrides.insert(idx as u16, keys);
+        }
+    }
+
+    /// Go back to the map's own textures.
     #[inline]
+    pub fn clear_texture_overrides(&mut self) {
+        self.texture_overrides.clear();
+    }
+
+    /// Cycle the automap: off -> full screen -> overlaid on the 3D view -> off.
     pub fn toggle_automap(&mut self) {
-        self.flags ^= FLAG_AUTOMAP_ON;
+        self.set_automap_mode(match self.automap_mode() {
+            AutomapMode::Off => AutomapMode::Full,
+            AutomapMode::Full => AutomapMode::Overlay,
+            AutomapMode::Overlay => AutomapMode::Off,
+        });
+    }
+
+    pub fn automap_mode(&self) -> AutomapMode {
+        match (self.is_automap_on(), self.is_automap_overlay()) {
+            (false, _) => AutomapMode::Off,
+            (true, false) => AutomapMode::Full,
+            (true, true) => AutomapMode::Overlay,
+        }
     }
 
+    pub fn set_automap_mode(&mut self, mode: AutomapMode) {
+        self.flags &= !(FLAG_AUTOMAP_ON | FLAG_AUTOMAP_OVERLAY);
+        self.flags |= match mode {
+            AutomapMode::Off => 0,
+            AutomapMode::Full => FLAG_AUTOMAP_ON,
+            AutomapMode::Overlay => FLAG_AUTOMAP_ON | FLAG_AUTOMAP_OVERLAY,
+        };
+    }
+
+    /// True if the automap is shown (either full screen or as an overlay).
     #[inline]
     pub fn is_automap_on(&self) -> bool {
         (self.flags & FLAG_AUTOMAP_ON) != 0
     }
 
+    /// True if the automap is shown on top of the 3D view (and it follows the player).
+    #[inline]
+    pub fn is_automap_overlay(&self) -> bool {
+        (self.flags & FLAG_AUTOMAP_OVERLAY) != 0
+    }
+
+    /// True if the automap replaces the 3D view (so the movement keys pan the map).
+    #[inline]
+    pub fn is_automap_full(&self) -> bool {
+        self.is_automap_on() && !self.is_automap_overlay()
+    }
+
+    /// Toggle between the classic automap arrow and the simple dot-and-line player marker.
+    #[inline]
+    pub fn toggle_automap_arrow_style(&mut self) -> bool {
+        self.classic_arrow = !self.classic_arrow;
+        self.classic_arrow
+    }
+
+    /// Toggle filling the sub-sectors on the automap (for debugging the BSP traversal).
+    #[inline]
+    pub fn toggle_automap_subsectors(&mut self) -> bool {
+        self.show_subsectors = !self.show_subsectors;
+        self.show_subsectors
+    }
+
+    /// Add the time it took to paint the HUD (which is painted outside the level), to the render stats.
+    pub fn add_hud_time(&self, hud_time_us: u32) {
+        let mut stats = self.render_stats.get();
+        stats.hud_time_us = hud_time_us;
+        stats.total_time_us += hud_time_us;
+        self.render_stats.set(stats);
+    }
+
+    /// The thickness of the automap lines, in pixels.
+    pub fn set_automap_thickness(&mut self, thickness: i32) {
+        self.amap_thickness = thickness.max(1);
+    }
+
+    /// By default (like in vanilla), teleporting stops the player.
+    #[inline]
+    pub fn set_teleport_keeps_momentum(&mut self, keep: bool) {
+        self.teleport_keeps_momentum = keep;
+    }
+
+    /// Set the distance fog for the 3D view (`None` = no fog, like in vanilla).
+    #[inline]
+    pub fn set_fog(&mut self, fog: Option<DistanceFog>) {
+        self.fog = fog;
+    }
+
+    /// Toggle painting only the automap lines which may be visible (vs. all of them, which is slower).
+    pub fn toggle_automap_culling(&mut self) -> bool {
+        self.flags ^= FLAG_AUTOMAP_CULLING;
+        (self.flags & FLAG_AUTOMAP_CULLING) != 0
+    }
+
+    /// Toggle measuring the time of each painting phase (see `RenderStats`).
+    pub fn toggle_profiling(&mut self) -> bool {
+        self.is_profiling = !self.is_profiling;
+        self.is_profiling
+    }
+
+    #[inline]
+    pub fn is_profiling(&self) -> bool {
+        self.is_profiling
+    }
+
+    /// Toggle the "fake contrast" (slightly darker/brighter axis-aligned walls), like in vanilla DOOM.
+    #[inline]
+    pub fn toggle_fake_contrast(&mut self) -> bool {
+        self.flags ^= FLAG_FAKE_CONTRAST;
+        (self.flags & FLAG_FAKE_CONTRAST) != 0
+    }
+
+    /// Cycle the automap reveal modes, like the IDDT cheat: normal, then all lines (like with
+    /// the computer map), then all lines with the extra colors. Returns the new mode's description.
+    pub fn cycle_automap_reveal(&mut self) -> &'static str {
+        let mask = FLAG_HAS_COMPUTER_MAP | FLAG_AUTOMAP_EXTRA_COLORS;
+        let (next, description) = match self.flags & mask {
+            0 => (FLAG_HAS_COMPUTER_MAP, "Automap: all lines"),
+            FLAG_HAS_COMPUTER_MAP => (mask, "Automap: all lines, extra colors"),
+            _ => (0, "Automap: normal"),
+        };
+        self.flags = (self.flags & !mask) | next;
+        description
+    }
+
+    /// Toggle the view bobbing, while moving.
+    #[inline]
+    pub fn toggle_view_bob(&mut self) -> bool {
+        self.flags ^= FLAG_VIEW_BOB;
+        (self.flags & FLAG_VIEW_BOB) != 0
+    }
+
+    /// The view bobbing offsets, as (horizontal, vertical) - in map units.
+    /// Like in vanilla DOOM, the bobbing grows with the (square of the) player's speed, up to a limit,
+    /// so it is zero when standing still. The view also sways a bit sideways, at half the rate.
+    pub fn view_bob(&self) -> (f64, f64) {
+        if (self.flags & FLAG_VIEW_BOB) == 0 {
+            return (0.0, 0.0);
+        }
+        let speed_per_tic = self.vel_x.hypot(self.vel_y) / TICS_PER_SECOND;
+        let bob = (speed_per_tic * speed_per_tic / 4.0).min(VIEW_BOB_MAX);
+        // like in vanilla, the phase comes from the level time (not from the distance walked)
+        let bob_step = std::f64::consts::TAU * TICS_PER_SECOND / VIEW_BOB_PERIOD_TICS;
+        let bob_phase = (self.game_time * bob_step) % std::f64::consts::TAU;
+        let horizontal = bob / 4.0 * (bob_phase / 2.0).cos();
+        let vertical = bob / 2.0 * bob_phase.sin();
+        (horizontal, vertical)
+    }
+
+    /// The effective light level of a seg's wall.
+    /// (broken segs, without a valid sector, are shown at full brightness)
+    pub fn wall_light_level(&self, seg: &Seg) -> u16 {
+        let sector_light = self
+            .seg_render_data(seg)
+            .map_or(MAX_LIGHT_LEVEL, |data| data.front_sector.light_level);
+        walls::wall_light_level(seg, sector_light, (self.flags & FLAG_FAKE_CONTRAST) != 0)
+    }
+
+    /// Set how far (0..1) the rendering is between the previous and the current game tick,
+    /// so that the player's movement looks smooth when painting faster than the fixed update rate.
+    #[inline]
+    pub fn set_interpolation(&mut self, alpha: f64) {
+        self.interpolation = alpha.clamp(0.0, 1.0);
+    }
+
+    /// Mark the start of a new game tick: the current player position becomes the previous one.
+    #[inline]
+    pub fn begin_tick(&mut self) {
+        self.prev_player = self.player.clone();
+    }
+
+    /// The player, as seen when painting: interpolated between the previous and the current tick.
+    pub fn view_player(&self) -> Thing {
+        let alpha = self.interpolation;
+        let prev = &self.prev_player;
+        let mut view = self.player.clone();
+        if alpha < 1.0 {
+            let lerp = |a: i32, b: i32| a + (((b - a) as f64) * alpha).round() as i32;
+            view.pos = Vertex {
+                x: lerp(prev.pos.x, self.player.pos.x),
+                y: lerp(prev.pos.y, self.player.pos.y),
+            };
+            // turn the shortest way around
+            let mut delta = (self.player.angle - prev.angle).rad();
+            if delta > std::f64::consts::PI {
+                delta -= 2.0 * std::f64::consts::PI;
+            }
+            view.angle = prev.angle + delta * alpha;
+        }
+        view
+    }
+
+    /// The camera, as seen when painting (see `view_player`).
+    pub fn camera(&self) -> Camera {
+        let view = self.view_player();
+        let floor = self.sector_at(view.pos).map_or(0, |sect| sect.floor_height);
+        let (_, bob_z) = self.view_bob();
+        Camera {
+            pos: view.pos,
+            eye_z: floor as f64 + PLAYER_VIEW_HEIGHT + bob_z,
+            angle: view.angle,
+            half_fov: self.cfg.half_fov(),
+            dist_from_screen: self.cfg.dist_from_screen(),
+        }
+    }
+
+    /// Project a world point (at height `z`) on the screen, as seen by the camera (see `Camera::project`).
+    pub fn project_point(&self, world: Vertex, z: f64) -> Option<(i32, i32, f64)> {
+        self.camera()
+            .project(world, z, self.cfg.scr_width(), self.cfg.scr_height())
+    }
+
+    /// The statistics of the last painted frame.
+    #[inline]
+    pub fn render_stats(&self) -> RenderStats {
+        self.render_stats.get()
+    }
+
     pub fn paint(&self, painter: &mut dyn Painter) {
-        if self.flags & FLAG_AUTOMAP_ON == 0 {
-            self.paint_3d_view(painter);
-        } else {
-            self.paint_automap(painter);
+        self.prev_render_stats.set(self.render_stats.get());
+        self.render_stats.set(RenderStats::default());
+        let total_timer = PhaseTimer::start(self.is_profiling);
+        let view = self.view_player();
+        if !self.is_automap_full() {
+            self.paint_3d_view(&view, painter);
         }
+        if self.is_automap_on() {
+            let timer = PhaseTimer::start(self.is_profiling);
+            if self.is_automap_overlay() {
+                self.paint_automap(&view, &mut BlendedPainter::new(painter, AMAP_OVERLAY_ALPHA));
+            } else {
+                self.paint_automap(&view, painter);
+            }
+            let mut stats = self.render_stats.get();
+            stats.automap_time_us = timer.elapsed_us();
+            self.render_stats.set(stats);
+        }
+        let mut stats = self.render_stats.get();
+        stats.total_time_us = total_timer.elapsed_us();
+        self.render_stats.set(stats);
     }
 
     //---------------
     // private methods
     //---------------
 
+    fn activate_line_special(&mut self, _line_idx: usize, line: &LineDef) {
+        let missing_key = specials::required_key(line.special_type).filter(|key| !self.player_state.has_key(*key));
+        if let Some(key) = missing_key {
+            let msg = specials::locked_message(line.special_type, key);
+            self.messages.push(msg);
+            self.sounds.push(SoundEvent::at(SOUND_OOF, self.player.pos));
+        } else if let Some(exit) = specials::level_exit(line.special_type) {
+            self.exit = Some(exit);
+        } else {
+            // TODO implement the actual specials (doors, lifts etc)
+        }
+    }
+
+    /// Get a sidedef, with its texture overrides (if any) applied.
+    /// (`None` for a missing side, or for an out of range index)
+    fn try_sidedef(&self, idx: u16) -> Option<SideDef> {
+        // (this also covers `NO_SIDEDEF`)
+        let mut side = self.map_data.try_sidedef(idx as usize)?;
+        if let Some([upper, lower, middle]) = self.texture_overrides.get(&idx) {
+            side.upper_texture_key = *upper;
+            side.lower_texture_key = *lower;
+            side.middle_texture_key = *middle;
+        }
+        Some(side)
+    }
+
+    /// A side of a line and its sector - or `None` for a missing side, or for broken maps
+    /// (with an out of range side or sector index).
+    fn side_and_sector(&self, side_idx: u16) -> Option<(SideDef, Sector)> {
+        let side = self.try_sidedef(side_idx)?;
+        let sector = self.map_data.try_sector(side.sector_idx as usize)?;
+        Some((side, sector))
+    }
+
     fn line_was_seen(&self, line_idx: u16) {
         let byte_idx = (line_idx as usize) >> 3;
         let bit_mask = 1 << (line_idx & 0x07);
@@ -167,26 +1019,79 @@ impl ActiveLevel {
         ((*vec)[byte_idx] & bit_mask) != 0
     }
 
-    fn paint_3d_view(&self, painter: &mut dyn Painter) {
+    fn line_was_triggered(&mut self, line_idx: usize) {
+        self.triggered_lines[line_idx >> 3] |= 1 << (line_idx & 0x07);
+    }
+
+    fn was_line_triggered(&self, line_idx: usize) -> bool {
+        (self.triggered_lines[line_idx >> 3] & (1 << (line_idx & 0x07))) != 0
+    }
+
+    /// Paint the sky across the whole screen width, from the top of the screen down to the horizon.
+    /// The sky turns with the player and repeats horizontally (see `SKY_COLUMNS_PER_TURN`).
+    // TODO only paint the sky where the sectors have a sky ceiling (plus the upper wall bands flagged
+    // with `is_sky`, instead of their texture), once the walls are projected properly
+    fn paint_sky(&self, view: &Thing, horizon_offset: i32, painter: &mut dyn Painter) {
+        let scr_height = self.cfg.scr_height();
+        let horizon = (scr_height / 2 + horizon_offset).clamp(0, scr_height);
+        // scale the sky like the rest of the view (which is scaled to the screen height)
+        let v_step = SKY_HORIZON_ROW * 2.0 / (scr_height as f64);
+        let v_start = (SKY_HORIZON_ROW - (horizon as f64) * v_step).max(0.0);
+        for x in 0..self.cfg.scr_width() {
+            let angle = view.angle + self.cfg.screen_x_to_angle(x);
+            // the angle grows to the left, but the texture columns grow to the right
+            let u = (-angle.rad() * SKY_COLUMNS_PER_TURN / std::f64::consts::TAU).floor() as i32;
+            let sampling = ColumnSampling {
+                u,
+                v_start,
+                v_step,
+                tiles_vertically: false,
+            };
+            self.sky
+                .paint_column(x, 0..horizon, &sampling, painter, self.cfg.palette());
+        }
+    }
+
+    fn paint_3d_view(&self, view: &Thing, painter: &mut dyn Painter) {
         // TODO implement this .............
-        let w = painter.get_screen_width();
-        let h = painter.get_screen_height();
-        painter.fill_rect(0, 0, w, h, CYAN);
-        // TODO properly align the sky with the player's rotation + fill the whole horizon width
-        self.sky.paint(0, 0, painter, self.cfg.palette());
+        painter.clear(CYAN);
+        // when the eye bobs up, the world moves down on screen
+        // TODO apply the bobbing to the eye height, once the walls are projected properly
+        let (bob_x, bob_z) = self.view_bob();
+        let (bob_x, bob_y) = (bob_x.round() as i32, bob_z.round() as i32);
+        let timer = PhaseTimer::start(self.is_profiling);
+        self.paint_sky(view, bob_y, painter);
+        let sky_time_us = timer.elapsed_us();
 
         // collect segments, for painting
-        let segs = self.player_visible_segments(&self.player);
-        let ppos = self.player.pos;
+        let timer = PhaseTimer::start(self.is_profiling);
+        let segs = self.player_visible_segments(view);
+        let bsp_time_us = timer.elapsed_us();
+        let timer = PhaseTimer::start(self.is_profiling);
+        let ppos = view.pos;
         let width = self.cfg.scr_width() as usize;
         let mut painted = vec![0_u8; width];
         let mut dbg_color = 240_u8;
+        let mut stats = self.render_stats.get();
+        stats.segs_collected = segs.len() as u32;
         for seg in segs.iter() {
             // TODO render the seg CORRECTLY !
-            let a1 = Angle::from_vector(ppos, seg.start) - self.player.angle;
-            let a2 = Angle::from_vector(ppos, seg.end) - self.player.angle;
+            let a1 = Angle::from_vector(ppos, seg.start) - view.angle;
+            let a2 = Angle::from_vector(ppos, seg.end) - view.angle;
             let (x1, _v1, clipped_1) = self.view_angle_to_x(a1, true);
             let (x2, _v2, clipped_2) = self.view_angle_to_x(a2, false);
+            // only solid (one-sided) walls hide what is behind them
+            let Some(render_data) = self.seg_render_data(seg) else {
+                // broken seg => skip it
+                continue;
+            };
+            let is_solid = !render_data.is_two_sided();
+            let light = self.wall_light_level(seg) as u32;
+            let shade = ((dbg_color as u32) * light / 255) as u8;
+            let columns_before = stats.columns_drawn;
+            // TODO use the real distance of each column, once the walls are projected correctly
+            let dist = |v: Vertex| ((v.x - ppos.x) as f64).hypot((v.y - ppos.y) as f64);
+            let (dist1, dist2) = (dist(seg.start), dist(seg.end));
             for x in x1..x2 {
                 if x < 0 || x >= (width as i32) {
                     continue;
@@ -195,12 +1100,27 @@ impl ActiveLevel {
                     continue;
                 }
                 // ok to paint
-                let color = RGB::from(0, dbg_color, dbg_color);
-                painter.draw_line(x, 20, x, 50, color);
+                let mut color = if is_solid {
+                    RGB::from(0, shade, shade)
+                } else {
+                    RGB::from(shade, shade, 0)
+                };
+                if let Some(fog) = &self.fog {
+                    let t = (x - x1) as f64 / (x2 - x1) as f64;
+                    color = fog.apply(color, dist1 + (dist2 - dist1) * t);
+                }
+                painter.draw_line(x + bob_x, 20 + bob_y, x + bob_x, 50 + bob_y, color);
+                stats.columns_drawn += 1;
+                stats.draw_calls += 1;
                 // also, mark seg as seen
-                painted[x as usize] = 1;
+                if is_solid {
+                    painted[x as usize] = 1;
+                }
                 self.line_was_seen(seg.linedef_idx);
             }
+            if stats.columns_drawn > columns_before {
+                stats.segs_drawn += 1;
+            }
             if dbg_color >= 10 {
                 dbg_color -= 10;
             } else {
@@ -209,11 +1129,32 @@ impl ActiveLevel {
             // TODO TEMP: also draw lines for each seg's edges
             painter.draw_line(x1, 50, x1, 60, if clipped_1 { PINK } else { RED });
             painter.draw_line(x2, 60, x2, 70, if clipped_2 { BLUE } else { GREEN });
+            stats.draw_calls += 2;
         }
+        stats.walls_time_us = timer.elapsed_us();
+        stats.sky_time_us = sky_time_us;
+        stats.bsp_time_us = bsp_time_us;
+        self.render_stats.set(stats);
 
         // TODO - TEMP message
-        let txt = format!("SEGs: {} / {}", segs.len(), self.map_data.seg_count());
+        let txt = format!(
+            "SEGs: {} / {} (drawn: {}), columns: {}, sub-sectors: {}",
+            segs.len(),
+            self.map_data.seg_count(),
+            stats.segs_drawn,
+            stats.columns_drawn,
+            stats.subsectors_visited
+        );
         self.cfg.font().draw_text(3, 15, &txt, WHITE, painter);
+        if self.is_profiling {
+            // (the times of the previous frame, since this one is not finished yet)
+            let prev = self.prev_render_stats.get();
+            let txt = format!(
+                "Time (us): BSP {}, sky {}, walls {}, HUD {}, total {}",
+                prev.bsp_time_us, prev.sky_time_us, prev.walls_time_us, prev.hud_time_us, prev.total_time_us
+            );
+            self.cfg.font().draw_text(3, 27, &txt, WHITE, painter);
+        }
     }
 
     // TODO this is kinda hacky + not very efficient, but if it works, it's OK :))
@@ -235,11 +1176,27 @@ impl ActiveLevel {
         }
     }
 
-    fn paint_automap(&self, painter: &mut dyn Painter) {
-        // clear the screen first
-        painter.fill_rect(0, 0, painter.get_screen_width(), painter.get_screen_height(), BLACK);
-        // paint the map itself
-        for idx in 0..self.map_data.linedef_count() {
+    fn paint_automap(&self, view: &Thing, painter: &mut dyn Painter) {
+        // clear the screen first (unless painting over the 3D view), then paint only inside the border
+        let (vx, vy, vw, vh) = self.automap_viewport();
+        if !self.is_automap_overlay() {
+            painter.clear(BLACK);
+            painter.draw_rect(vx, vy, vw, vh, AMAP_BORDER_COLOR);
+        }
+        let mut clipped_painter = ClippedPainter::new(painter, vx + 1, vy + 1, vw - 2, vh - 2);
+        let painter: &mut dyn Painter = &mut clipped_painter;
+        let filled_subsectors = if self.show_subsectors {
+            self.paint_automap_subsectors(view, painter)
+        } else {
+            0
+        };
+        // paint the map itself (only the lines which may be visible, or all of them, for comparison)
+        let line_indices: Vec<usize> = if (self.flags & FLAG_AUTOMAP_CULLING) != 0 {
+            self.automap_visible_lines()
+        } else {
+            (0..self.map_data.linedef_count()).collect()
+        };
+        for idx in line_indices {
             let line = self.map_data.linedef(idx);
             let color = self.pick_automap_line_color(idx as u16, &line);
             if color != BLACK {
@@ -247,52 +1204,129 @@ impl ActiveLevel {
             }
         }
 
+        if (self.flags & FLAG_AUTOMAP_EXTRA_COLORS) != 0 {
+            self.paint_automap_spawn_markers(painter);
+        }
+
         // paint the player, as a white arrow
-        let pos = self.player.pos;
-        let ang = self.player.angle;
-        if AUTOMAP_OLD_STYLE_ARROW {
-            let v1 = pos.polar_translate(25.0, ang);
-            let v2 = pos.polar_translate(18.0, -ang);
-            let v3 = pos.polar_translate(25.0, -ang);
-            self.draw_automap_line(v1, v3, WHITE, painter);
-            // draw the arrow head + fins
-            let a2 = ang + 2.7;
-            let a3 = ang - 2.7;
-            let va = v1.polar_translate(18.0, a2);
-            let vb = v1.polar_translate(18.0, a3);
-            self.draw_automap_line(v1, va, WHITE, painter);
-            self.draw_automap_line(v1, vb, WHITE, painter);
-            let a2 = ang + 2.5;
-            let a3 = ang - 2.5;
-            let va = v2.polar_translate(13.0, a2);
-            let vb = v2.polar_translate(13.0, a3);
-            self.draw_automap_line(v2, va, WHITE, painter);
-            self.draw_automap_line(v2, vb, WHITE, painter);
-            let va = v3.polar_translate(13.0, a2);
-            let vb = v3.polar_translate(13.0, a3);
-            self.draw_automap_line(v3, va, WHITE, painter);
-            self.draw_automap_line(v3, vb, WHITE, painter);
+        if self.classic_arrow {
+            self.paint_classic_player_arrow(view, painter);
         } else {
             // a dot at the player's actual position
+            let pos = view.pos;
             let p = self.translate_automap_vertex(pos);
-            painter.fill_rect(p.x - 1, p.y - 1, 3, 3, GREEN);
+            let t = self.amap_thickness;
+            painter.fill_rect(p.x - t, p.y - t, 2 * t + 1, 2 * t + 1, GREEN);
             // a line towards the player direction
-            let v = pos.polar_translate(40.0, ang);
+            let v = pos.polar_translate(40.0, view.angle);
             self.draw_automap_line(pos, v, WHITE, painter);
         }
 
         // text with the map name
-        let txt = format!("Map: {}", self.name());
-        self.cfg.font().draw_text(3, 3, &txt, RED, painter);
+        let mut txt = format!("Map: {}", self.cfg.wad().map_title(self.name()));
+        if self.show_subsectors {
+            txt += &format!(
+                "\nSub-sectors: {filled_subsectors} of {}",
+                self.map_data.ssector_count()
+            );
+        }
+        self.cfg.font().draw_text(vx + 3, vy + 3, &txt, RED, painter);
+
+        self.temp_paint_segs(view, painter);
+    }
+
+    /// The classic automap arrow: a shaft along the player's direction, with the head in front
+    /// of the player and two pairs of fins behind.
+    fn paint_classic_player_arrow(&self, view: &Thing, painter: &mut dyn Painter) {
+        for (v1, v2) in classic_arrow_lines(view.pos, view.angle) {
+            self.draw_automap_line(v1, v2, WHITE, painter);
+        }
+    }
+
+    /// Fill the sub-sectors, in the order they are visited by the BSP traversal, each with its own
+    /// translucent color. The sub-sectors are convex, so they are filled as triangle fans.
+    /// With automap culling on, only the sub-sectors which may be visible are filled.
+    /// Returns how many sub-sectors were filled.
+    fn paint_automap_subsectors(&self, view: &Thing, painter: &mut dyn Painter) -> usize {
+        let ssect_indices = if (self.flags & FLAG_AUTOMAP_CULLING) != 0 {
+            self.automap_visible_subsectors()
+        } else {
+            self.bsp_leaves_front_to_back(view.pos)
+        };
+        for &ssect_idx in &ssect_indices {
+            let mut polygon: Vec<Vertex> = Vec::new();
+            for seg in self.map_data.sub_sector(ssect_idx as usize) {
+                for v in [seg.start, seg.end] {
+                    if polygon.last() != Some(&v) && polygon.first() != Some(&v) {
+                        polygon.push(v);
+                    }
+                }
+            }
+            let color = debug_color(ssect_idx);
+            for [v1, v2, v3] in triangle_fan(&polygon) {
+                let [p1, p2, p3] = [v1, v2, v3].map(|v| {
+                    let p = self.translate_automap_vertex(v);
+                    (p.x, p.y)
+                });
+                painter.fill_triangle_blended(p1, p2, p3, color, AMAP_SUBSECTOR_ALPHA);
+            }
+        }
+        ssect_indices.len()
+    }
 
-        self.temp_paint_segs(painter);
+    /// The indices of all the sub-sectors, ordered from the closest to the farthest from a point.
+    fn bsp_leaves_front_to_back(&self, point: Vertex) -> Vec<u16> {
+        let mut leaves = Vec::new();
+        let mut stack = vec![self.map_data.root_bsp_node_idx()];
+        while let Some(node_idx) = stack.pop() {
+            if (node_idx & SSECTOR_FLAG) != 0 {
+                leaves.push(node_idx & !SSECTOR_FLAG);
+            } else {
+                let node = self.map_data.bsp_node(node_idx as usize);
+                let (near, far) = node.child_indices_based_on_point_pos(point);
+                stack.push(far);
+                stack.push(near);
+            }
+        }
+        leaves
+    }
+
+    /// For mappers: mark the teleport destinations (diamonds), the player starts (squares, with
+    /// the player's number) and the deathmatch starts (crosses).
+    fn paint_automap_spawn_markers(&self, painter: &mut dyn Painter) {
+        let m = AMAP_MARKER_SIZE;
+        for thing in self.map_data.things() {
+            let p = self.translate_automap_vertex(thing.pos);
+            match thing.type_code() {
+                TELEPORT_DEST_TYPE => {
+                    let color = AMAP_TELEPORT_DEST_COLOR;
+                    painter.draw_line(p.x - m, p.y, p.x, p.y - m, color);
+                    painter.draw_line(p.x, p.y - m, p.x + m, p.y, color);
+                    painter.draw_line(p.x + m, p.y, p.x, p.y + m, color);
+                    painter.draw_line(p.x, p.y + m, p.x - m, p.y, color);
+                }
+                player_nr @ 1..=4 => {
+                    let color = AMAP_PLAYER_START_COLOR;
+                    painter.draw_rect(p.x - m, p.y - m, 2 * m + 1, 2 * m + 1, color);
+                    let txt = player_nr.to_string();
+                    self.cfg.font().draw_text(p.x + m + 2, p.y - m, &txt, color, painter);
+                }
+                DEATHMATCH_START_TYPE => {
+                    let color = AMAP_DEATHMATCH_START_COLOR;
+                    painter.draw_line(p.x - m, p.y - m, p.x + m, p.y + m, color);
+                    painter.draw_line(p.x - m, p.y + m, p.x + m, p.y - m, color);
+                }
+                _ => {}
+            }
+        }
     }
 
     // TODO TEMPORARY: collect and paint the visible SEG-s
-    fn temp_paint_segs(&self, painter: &mut dyn Painter) {
-        let segs = self.player_visible_segments(&self.player);
+    fn temp_paint_segs(&self, view: &Thing, painter: &mut dyn Painter) {
+        let segs = self.player_visible_segments(view);
         let txt = format!("Collected SEGs: {} / {}", segs.len(), self.map_data.seg_count());
-        self.cfg.font().draw_text(3, 15, &txt, GREY, painter);
+        let (vx, vy, _, _) = self.automap_viewport();
+        self.cfg.font().draw_text(vx + 3, vy + 15, &txt, GREY, painter);
         for seg in segs.iter() {
             self.line_was_seen(seg.linedef_idx); // TODO - this should be done in 3D VIEW paint
             self.draw_automap_line(seg.start, seg.end, GREY, painter);
@@ -306,35 +1340,35 @@ impl ActiveLevel {
             self.draw_automap_line(mid, m2, GREY, painter);
         }
     }
-    // select color based on line type
-    // TODO some colors may be wrong, or temporary => CHECK against Crispy Doom
+    // select color based on line type, following the vanilla DOOM logic (see `AM_drawWalls`)
+    // - see: https://doomwiki.org/wiki/Automap
     fn pick_automap_line_color(&self, line_idx: u16, line: &LineDef) -> RGB {
         let f = line.flags;
         let extras = (self.flags & FLAG_AUTOMAP_EXTRA_COLORS) != 0;
 
-        // quick return, for lines that should NOT appear on automap
+        // lines that should NOT appear on automap are only shown with the extra colors
         let dont_show = (f & LINE_NEVER_ON_AMAP) != 0;
         if dont_show && !extras {
             return BLACK;
         }
-        // quick return, for lines that were not yet seen
+        // lines that were not yet seen are only shown if the player has the computer map
         let was_seen = (f & LINE_ALWAYS_ON_AMAP) != 0 || self.was_line_seen(line_idx);
         if !was_seen {
             if (self.flags & FLAG_HAS_COMPUTER_MAP) == 0 {
                 return BLACK;
             } else {
-                return CYAN;
+                return AMAP_COMPUTER_MAP_COLOR;
             }
         }
 
         if extras {
             // highlight secrets
             if f & LINE_SECRET != 0 {
-                return GREEN;
+                return AMAP_SECRET_COLOR;
             }
             // highlight actionable lines
             if line.special_type != 0 {
-                return BLUE;
+                return AMAP_SPECIAL_COLOR;
             }
             // if non-special and invisible
             if dont_show {
@@ -342,47 +1376,127 @@ impl ActiveLevel {
             }
         }
 
-        if f & LINE_TWO_SIDED != 0 {
-            let details = self.get_line_details(&line);
-            let s1 = details.left_sector.unwrap();
-            let s2 = details.right_sector.unwrap();
-            return if s1.floor_height != s2.floor_height {
-                // stairs
-                CHOCO
-            } else if s1.ceiling_height != s2.ceiling_height {
-                // ceiling diff
-                YELLOW
-            } else {
-                // no height delta => simply don't draw
-                BLACK
-            };
-        }
+        // one-sided lines are solid walls
+        // (broken two-sided lines, with a missing side, are also treated as one-sided)
+        let details = self.get_line_details(line);
+        let (s1, s2) = match (details.left_sector, details.right_sector) {
+            (Some(s1), Some(s2)) => (s1, s2),
+            _ => return AMAP_WALL_COLOR,
+        };
 
-        if f & LINE_BLOCKS != 0 {
-            return RED;
+        if line.special_type == TELEPORT_LINE_SPECIAL {
+            // teleporters are shown in a darker wall color
+            AMAP_TELEPORTER_COLOR
+        } else if f & LINE_SECRET != 0 {
+            // secret doors are disguised as normal walls
+            AMAP_WALL_COLOR
+        } else if s1.floor_height != s2.floor_height {
+            // floor height delta (e.g. stairs)
+            AMAP_FLOOR_DELTA_COLOR
+        } else if s1.ceiling_height != s2.ceiling_height {
+            // ceiling height delta (e.g. doors)
+            AMAP_CEILING_DELTA_COLOR
+        } else if extras {
+            // no height delta => only shown with the extra colors (like the IDDT cheat does)
+            AMAP_TWO_SIDED_COLOR
+        } else {
+            // no height delta => simply don't draw
+            BLACK
         }
-
-        // TODO temporary - just highlight lines that don't match any of the above
-        // (later, the default returned here should be BLACK)
-        PINK
     }
 
     fn draw_automap_line(&self, v1: Vertex, v2: Vertex, color: RGB, painter: &mut dyn Painter) {
         let xv1 = self.translate_automap_vertex(v1);
         let xv2 = self.translate_automap_vertex(v2);
-        painter.draw_line(xv1.x, xv1.y, xv2.x, xv2.y, color);
+        painter.draw_thick_line(xv1.x, xv1.y, xv2.x, xv2.y, self.amap_thickness, color);
+    }
+
+    /// The overlay automap follows the player; the full screen one can be moved around.
+    #[inline]
+    fn automap_center(&self) -> Vertex {
+        if self.is_automap_overlay() {
+            self.player.pos
+        } else {
+            self.amap_center
+        }
     }
 
     fn translate_automap_vertex(&self, orig_vertex: Vertex) -> Vertex {
         // scale the original coordinates
-        let sv = (orig_vertex - self.amap_center).fscale(self.amap_zoom);
-        // translate the scaled coordinates + mirror y
+        let sv = (orig_vertex - self.automap_center()).fscale(self.amap_zoom);
+        // translate the scaled coordinates (to the center of the automap viewport) + mirror y
+        let (vx, vy, vw, vh) = self.automap_viewport();
         Vertex {
-            x: sv.x + (self.cfg.scr_width() / 2),
-            y: (self.cfg.scr_height() / 2) - sv.y,
+            x: sv.x + vx + (vw / 2),
+            y: vy + (vh / 2) - sv.y,
+        }
+    }
+
+    /// The indices of the lines which may be visible in the automap viewport: the lines of the segs
+    /// in the visible sub-sectors, plus the (few) lines which are not part of any seg.
+    fn automap_visible_lines(&self) -> Vec<usize> {
+        let mut is_added = vec![0_u8; (self.map_data.linedef_count() + 7) >> 3];
+        let mut lines = self.segless_lines.clone();
+        for ssect_idx in self.automap_visible_subsectors() {
+            for seg in self.map_data.sub_sector(ssect_idx as usize) {
+                let idx = seg.linedef_idx as usize;
+                let bit_mask = 1 << (idx & 0x07);
+                if (is_added[idx >> 3] & bit_mask) == 0 {
+                    is_added[idx >> 3] |= bit_mask;
+                    lines.push(idx);
+                }
+            }
+        }
+        lines
+    }
+
+    /// The indices of the sub-sectors which may be visible in the automap viewport, found by walking
+    /// the BSP tree and skipping the sub-trees whose bounding boxes are entirely off-screen.
+    fn automap_visible_subsectors(&self) -> Vec<u16> {
+        let view_box = self.automap_view_box();
+        let mut leaves = Vec::new();
+        let mut stack = vec![self.map_data.root_bsp_node_idx()];
+        while let Some(node_idx) = stack.pop() {
+            if (node_idx & SSECTOR_FLAG) != 0 {
+                leaves.push(node_idx & !SSECTOR_FLAG);
+            } else {
+                let node = self.map_data.bsp_node(node_idx as usize);
+                for (child, bbox) in node.children_with_bboxes() {
+                    if bbox.intersects(&view_box) {
+                        stack.push(child);
+                    }
+                }
+            }
+        }
+        leaves
+    }
+
+    /// The map area shown in the automap viewport (rounded outwards).
+    fn automap_view_box(&self) -> BoundingBox {
+        let (_, _, vw, vh) = self.automap_viewport();
+        let half_w = ((vw as f64) / 2.0 / self.amap_zoom).ceil() as i32;
+        let half_h = ((vh as f64) / 2.0 / self.amap_zoom).ceil() as i32;
+        let c = self.automap_center();
+        BoundingBox {
+            min: Vertex {
+                x: c.x - half_w,
+                y: c.y - half_h,
+            },
+            max: Vertex {
+                x: c.x + half_w,
+                y: c.y + half_h,
+            },
         }
     }
 
+    /// The screen area of the automap (x, y, width, height), including its border:
+    /// like in vanilla DOOM, it leaves out the status bar area, at the bottom of the screen.
+    // TODO use the whole screen for the largest view size (no status bar), once view sizes are supported
+    fn automap_viewport(&self) -> (i32, i32, i32, i32) {
+        let status_bar_height = self.cfg.scr_height() * STATUS_BAR_HEIGHT / ORIGINAL_SCREEN_HEIGHT;
+        (0, 0, self.cfg.scr_width(), self.cfg.scr_height() - status_bar_height)
+    }
+
     fn get_line_details(&self, linedef: &LineDef) -> LineDefDetails {
         let mut details = LineDefDetails {
             left_sidedef: None,
@@ -390,62 +1504,37 @@ impl ActiveLevel {
             right_sidedef: None,
             right_sector: None,
         };
-        if linedef.left_side_idx != 0xFFFF {
-            let side = self.map_data.sidedef(linedef.left_side_idx as usize);
-            let sect = self.map_data.sector(side.sector_idx as usize);
+        if let Some((side, sect)) = self.side_and_sector(linedef.left_side_idx) {
             details.left_sidedef = Some(side);
             details.left_sector = Some(sect);
         }
-        if linedef.right_side_idx != 0xFFFF {
-            let side = self.map_data.sidedef(linedef.right_side_idx as usize);
-            let sect = self.map_data.sector(side.sector_idx as usize);
+        if let Some((side, sect)) = self.side_and_sector(linedef.right_side_idx) {
             details.right_sidedef = Some(side);
             details.right_sector = Some(sect);
         }
-
-        // sanity checks
-        // TODO: these should be tested during WAD validation
-        assert!(details.left_sidedef.is_some());
-        assert!(details.left_sector.is_some());
-        if linedef.flags & LINE_TWO_SIDED == 0 {
-            // NOT 2-sided
-            assert!(details.right_sidedef.is_none());
-            assert!(details.right_sector.is_none());
-        } else {
-            // is 2-sided
-            assert!(details.right_sidedef.is_some());
-            assert!(details.right_sector.is_some());
-        }
-
+        // NOTE: broken PWADs may have two-sided lines with a missing (or invalid) side
+        // => these are treated as one-sided (they are reported as warnings, when loading the WAD)
         details
     }
 
     fn player_visible_segments(&self, player: &Thing) -> Vec<Seg> {
         let mut sect_collector = Vec::with_capacity(self.map_data.seg_count() >> 1);
-        let start_idx = self.map_data.root_bsp_node_idx();
-        self.render_node(player, start_idx, &mut sect_collector);
-        sect_collector
-    }
-
-    fn render_node(&self, player: &Thing, node_idx: u16, seg_collector: &mut Vec<Seg>) {
-        if (node_idx & SSECTOR_FLAG) == 0 {
-            // NOT a leaf
-            let node = self.map_data.bsp_node(node_idx as usize);
-            let (kid1, kid2) = node.child_indices_based_on_point_pos(player.pos);
-            self.render_node(player, kid1, seg_collector);
-            // TODO? if self.check_bounding_box(player, &node.2nd_kid_box_bl, &node.2nd_kid_box_bl)
-            self.render_node(player, kid2, seg_collector);
-        } else {
-            // it's a LEAF => render sector
-            self.render_sub_sector(node_idx, seg_collector);
+        // the same front-to-back traversal as the automap (and its tests) use
+        for ssect_idx in self.bsp_leaves_front_to_back(player.pos) {
+            // TODO? skip the far children whose bounding box is not in the FOV
+            self.render_sub_sector(player, ssect_idx, &mut sect_collector);
         }
+        sect_collector
     }
 
-    fn render_sub_sector(&self, sect_idx: u16, seg_collector: &mut Vec<Seg>) {
+    fn render_sub_sector(&self, player: &Thing, sect_idx: u16, seg_collector: &mut Vec<Seg>) {
         let idx = (sect_idx & !SSECTOR_FLAG) as usize;
+        let mut stats = self.render_stats.get();
+        stats.subsectors_visited += 1;
+        self.render_stats.set(stats);
         let sub_sector_segs = self.map_data.sub_sector(idx);
         for seg in sub_sector_segs {
-            if self.is_seg_in_player_fov(&seg) {
+            if self.is_seg_in_player_fov(player, &seg) {
                 seg_collector.push(seg);
             }
         }
@@ -454,10 +1543,10 @@ impl ActiveLevel {
     // TODO I have simplified things, compared to DIY DOOM
     // -> see: https://github.com/amroibrahim/DIYDoom/tree/master/DIYDOOM/Notes010/notes
     // I don't care about clipped angles
-    fn is_seg_in_player_fov(&self, seg: &Seg) -> bool {
+    fn is_seg_in_player_fov(&self, player: &Thing, seg: &Seg) -> bool {
         // compute the initial, real-world angles from the player to the 2 edges of the SEG
-        let a1 = Angle::from_vector(self.player.pos, seg.start);
-        let a2 = Angle::from_vector(self.player.pos, seg.end);
+        let a1 = Angle::from_vector(player.pos, seg.start);
+        let a2 = Angle::from_vector(player.pos, seg.end);
 
         // drop segments which are "orthogonal" to the player's view
         if a1 == a2 {
@@ -473,8 +1562,8 @@ impl ActiveLevel {
         let half_fov = self.cfg.half_fov();
         let full_fov = half_fov * 2.0;
         // rotate angles, so that they're relative to player's angle + move FOV to range 0..full_fov
-        let a1 = a1 - self.player.angle + half_fov;
-        let a2 = a2 - self.player.angle + half_fov;
+        let a1 = a1 - player.angle + half_fov;
+        let a2 = a2 - player.angle + half_fov;
 
         // segment MAY BE visible if:
         //  - at least one segment edge is within player's fov
@@ -494,11 +1583,9 @@ struct LineDefDetails {
 }
 
 fn find_player_thing(map_data: &MapData) -> Thing {
-    for idx in 0..map_data.thing_count() {
-        let th = map_data.thing(idx);
-        if th.type_code() == 1 {
-            return th;
-        }
+    let [player1_start, ..] = map_data.player_starts();
+    if let Some(th) = player1_start {
+        return th;
     }
     // TODO validate this upon WAD loading, so we can panic here
     panic!("No player thing found in map {}", map_data.name());
@@ -506,10 +1593,56 @@ fn find_player_thing(map_data: &MapData) -> Thing {
 
 // TODO (later) pick the texture name based on level: https://doomwiki.org/wiki/Sky
 // (DOOM1, DOOM, DOOMU) ExMy => SKYx
+/// The lines which are not part of any seg (e.g. some zero-length or overlapping lines, which the
+/// node builders leave out).
+fn find_segless_lines(map_data: &MapData) -> Vec<usize> {
+    let mut has_segs = vec![false; map_data.linedef_count()];
+    for ssect_idx in 0..map_data.ssector_count() {
+        for seg in map_data.sub_sector(ssect_idx) {
+            if let Some(flag) = has_segs.get_mut(seg.linedef_idx as usize) {
+                *flag = true;
+            }
+        }
+    }
+    (0..has_segs.len()).filter(|&idx| !has_segs[idx]).collect()
+}
+
 fn load_sky(cfg: &GameConfig) -> Texture {
     let name = "SKY1";
     let key = hash_lump_name(name.as_bytes());
-    cfg.graphics().get_texture(key).unwrap()
+    cfg.graphics().get_texture_or_placeholder(key, SKY_WIDTH, SKY_HEIGHT)
+}
+
+/// The lines of the classic automap arrow, at a position and pointing in a direction.
+/// The first line is the shaft, from the tip of the arrow to its tail.
+fn classic_arrow_lines(pos: Vertex, ang: Angle) -> Vec<(Vertex, Vertex)> {
+    let back = ang.reversed();
+    let tip = pos.polar_translate(25.0, ang);
+    let fins1 = pos.polar_translate(18.0, back);
+    let tail = pos.polar_translate(25.0, back);
+    let mut lines = vec![(tip, tail)];
+    // the arrow head (pointing forward), then the fins (pointing backward)
+    for (v, len, spread) in [(tip, 18.0, 2.7), (fins1, 13.0, 2.5), (tail, 13.0, 2.5)] {
+        lines.push((v, v.polar_translate(len, ang + spread)));
+        lines.push((v, v.polar_translate(len, ang - spread)));
+    }
+    lines
+}
+
+/// Split a convex polygon into triangles, all starting from its first vertex.
+fn triangle_fan(polygon: &[Vertex]) -> Vec<[Vertex; 3]> {
+    if polygon.len() < 3 {
+        return Vec::new();
+    }
+    polygon.windows(2).skip(1).map(|w| [polygon[0], w[0], w[1]]).collect()
+}
+
+/// A (pseudo-random looking) color for an index, for telling apart the neighbouring items.
+/// (hashed with an odd multiplier, so that even consecutive indices get very different colors)
+fn debug_color(idx: u16) -> RGB {
+    let hash = (idx as u32).wrapping_mul(0x9E37_79B1);
+    let channel = |shift: u32| (64 + ((hash >> shift) & 0xFF) * 3 / 4) as u8;
+    RGB::from(channel(24), channel(16), channel(8))
 }
 
 /// Clamp a value, but also signal if it was clamped or not
@@ -524,8 +1657,975 @@ fn clamp_value<T: PartialOrd>(val: T, min: T, max: T) -> (T, bool) {
     }
 }
 
+/// The position in (whole) map units.
+#[cfg(not(feature = "fixed_point"))]
 #[inline]
+fn map_units(pos: f64) -> i32 {
+    pos as i32
+}
+
+/// The position in (whole) map units - rounded down, like `x >> FRACBITS` in vanilla.
+#[cfg(feature = "fixed_point")]
+#[inline]
+fn map_units(pos: f64) -> i32 {
+    Fixed::from_f64(pos).to_int()
+}
+
+/// The corners (bottom-left and top-right) of the blockmap area to check for a move, with a margin.
+#[cfg(not(feature = "fixed_point"))]
+fn move_bounding_box(x1: f64, y1: f64, x2: f64, y2: f64) -> (Vertex, Vertex) {
+    let corner1 = Vertex {
+        x: x1.min(x2).floor() as i32 - 1,
+        y: y1.min(y2).floor() as i32 - 1,
+    };
+    let corner2 = Vertex {
+        x: x1.max(x2).ceil() as i32 + 1,
+        y: y1.max(y2).ceil() as i32 + 1,
+    };
+    (corner1, corner2)
+}
+
+#[cfg(feature = "fixed_point")]
+fn move_bounding_box(x1: f64, y1: f64, x2: f64, y2: f64) -> (Vertex, Vertex) {
+    let [x1, y1, x2, y2] = [x1, y1, x2, y2].map(Fixed::from_f64);
+    let corner1 = Vertex {
+        x: x1.min(x2).to_int() - 1,
+        y: y1.min(y2).to_int() - 1,
+    };
+    // (one more unit, since `to_int` rounds down)
+    let corner2 = Vertex {
+        x: x1.max(x2).to_int() + 2,
+        y: y1.max(y2).to_int() + 2,
+    };
+    (corner1, corner2)
+}
+
+#[cfg(not(feature = "fixed_point"))]
+#[inline]
+/// Check if moving from (x1, y1) to (x2, y2) crosses a line (or ends up exactly on it).
+/// If so, return true if the move starts from the line's front side.
+fn move_crosses_line(line: &LineDef, x1: f64, y1: f64, x2: f64, y2: f64) -> Option<bool> {
+    let (lx, ly) = (line.v1.x as f64, line.v1.y as f64);
+    let (ldx, ldy) = ((line.v2.x - line.v1.x) as f64, (line.v2.y - line.v1.y) as f64);
+    // on which side of the line are the 2 points (negative = front side)
+    let side1 = ldx * (y1 - ly) - ldy * (x1 - lx);
+    let side2 = ldx * (y2 - ly) - ldy * (x2 - lx);
+    // moving away from the line (when standing exactly on it) is OK
+    let crosses = (side1 * side2 < 0.0) || (side2 == 0.0 && side1 != 0.0);
+    if !crosses {
+        return None;
+    }
+    // the line's ends must also be on different sides of the move
+    let (mdx, mdy) = (x2 - x1, y2 - y1);
+    let end1 = mdx * (ly - y1) - mdy * (lx - x1);
+    let end2 = mdx * (ly + ldy - y1) - mdy * (lx + ldx - x1);
+    (end1 * end2 <= 0.0).then_some(side1 < 0.0)
+}
+
+/// Same as above, but with vanilla's fixed-point side checks, so the same moves are blocked:
+/// * `P_PointOnLineSide` for the sides of the line (the line deltas are used as whole units)
+/// * `P_PointOnDivlineSide` for the sides of the move (all the deltas lose 8 bits of precision)
+#[cfg(feature = "fixed_point")]
+fn move_crosses_line(line: &LineDef, x1: f64, y1: f64, x2: f64, y2: f64) -> Option<bool> {
+    let [x1, y1, x2, y2] = [x1, y1, x2, y2].map(Fixed::from_f64);
+    let (lx, ly) = (Fixed::from_int(line.v1.x), Fixed::from_int(line.v1.y));
+    let (ldx, ldy) = (
+        Fixed::from_int(line.v2.x - line.v1.x),
+        Fixed::from_int(line.v2.y - line.v1.y),
+    );
+    // on which side of the line are the 2 points (negative = front side)
+    let side = |x: Fixed, y: Fixed| {
+        let right = (y - ly).mul(ldx >> FRACBITS);
+        let left = (ldy >> FRACBITS).mul(x - lx);
+        right.0 as i64 - left.0 as i64
+    };
+    let (side1, side2) = (side(x1, y1), side(x2, y2));
+    // moving away from the line (when standing exactly on it) is OK
+    let crosses = (side1 * side2 < 0) || (side2 == 0 && side1 != 0);
+    if !crosses {
+        return None;
+    }
+    // the line's ends must also be on different sides of the move
+    let (mdx, mdy) = (x2 - x1, y2 - y1);
+    let end = |x: Fixed, y: Fixed| {
+        let right = (mdx >> 8).mul((y - y1) >> 8);
+        let left = (mdy >> 8).mul((x - x1) >> 8);
+        right.0 as i64 - left.0 as i64
+    };
+    let (end1, end2) = (end(lx, ly), end(lx + ldx, ly + ldy));
+    (end1 * end2 <= 0).then_some(side1 < 0)
+}
+
 fn float_polar_translate(dist: f64, angle: Angle) -> (f64, f64) {
-    let (s, c) = angle.rad().sin_cos();
+    let (s, c) = trig::fine_sin_cos(angle);
     (dist * c, dist * s)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::KeyColor;
+
+    fn test_level(map_name: &str) -> ActiveLevel {
+        let cfg = GameConfig::new(WadData::load("DOOM1.WAD", true).unwrap(), 320, 200);
+        let map_idx = cfg.wad().map_index(map_name).unwrap();
+        ActiveLevel::new(cfg, map_idx)
+    }
+
+    /// Place the player on the front side of a line, `dist` units away from its middle, facing it.
+    fn face_line_front(level: &mut ActiveLevel, line_idx: usize, dist: f64) {
+        let line = level.map_data.linedef(line_idx);
+        let mid = Vertex {
+            x: (line.v1.x + line.v2.x) / 2,
+            y: (line.v1.y + line.v2.y) / 2,
+        };
+        // the front (right) side: the line direction, rotated 90 degrees clockwise
+        let front = Angle::from_vector(line.v1, line.v2) - Angle::from_degrees(90);
+        let pos = mid.polar_translate(dist, front);
+        level.place_player(pos, Angle::from_vector(pos, mid));
+    }
+
+    /// Hold "forward" for some tics. Returns the sound events.
+    fn walk_forward(level: &mut ActiveLevel, tics: usize) -> Vec<SoundEvent> {
+        let dt = 1.0 / TICS_PER_SECOND;
+        for _ in 0..tics {
+            level.move_player(dt);
+            level.apply_player_momentum(dt);
+        }
+        level.take_sound_events()
+    }
+
+    /// The first map (and linedef) with one of the given specials.
+    fn find_line_special(specials: &[u16]) -> (ActiveLevel, usize) {
+        let wad_data = WadData::load("DOOM1.WAD", true).unwrap();
+        let (map_idx, line_idx) = (0..wad_data.map_count())
+            .find_map(|map_idx| {
+                let line_idx = wad_data
+                    .map(map_idx)
+                    .linedefs()
+                    .position(|l| specials.contains(&l.special_type));
+                line_idx.map(|line_idx| (map_idx, line_idx))
+            })
+            .unwrap();
+        let cfg = GameConfig::new(wad_data, 320, 200);
+        (ActiveLevel::new(cfg, map_idx), line_idx)
+    }
+
+    #[test]
+    fn two_sided_segs_face_their_own_sub_sector() {
+        let level = test_level("E1M1");
+        let map = &level.map_data;
+        // (linedef, direction) -> (front sector, back sector)
+        let mut sides = HashMap::new();
+        for ss_idx in 0..map.ssector_count() {
+            let segs = map.sub_sector(ss_idx);
+            // all the segs of a sub-sector must be seen from inside it => same front sector
+            let ss_sector = level.seg_render_data(&segs[0]).unwrap().front_side.sector_idx;
+            for seg in &segs {
+                let data = level.seg_render_data(seg).unwrap();
+                assert_eq!(data.front_side.sector_idx, ss_sector, "sub-sector {ss_idx}");
+                let expected_front = if seg.direction_same {
+                    data.linedef.right_side_idx
+                } else {
+                    data.linedef.left_side_idx
+                };
+                assert_eq!(map.sidedef(expected_front as usize).sector_idx, ss_sector);
+                if let Some(back_side) = data.back_side {
+                    sides.insert((seg.linedef_idx, seg.direction_same), (ss_sector, back_side.sector_idx));
+                }
+            }
+        }
+        // the two segs of a two-sided line see each other's sector as their back sector
+        let mut pairs = 0;
+        for (&(line_idx, dir), &(front, back)) in &sides {
+            if let Some(&(other_front, other_back)) = sides.get(&(line_idx, !dir)) {
+                assert_eq!((front, back), (other_back, other_front), "linedef {line_idx}");
+                pairs += 1;
+            }
+        }
+        assert!(pairs > 100);
+    }
+
+    #[test]
+    fn automap_line_colors_follow_the_line_kind() {
+        let mut level = test_level("E1M1");
+        // only the vanilla colors, for lines which were seen
+        level.flags &= !(FLAG_AUTOMAP_EXTRA_COLORS | FLAG_HAS_COMPUTER_MAP);
+        let map = &level.map_data;
+        let sector_of = |side_idx: usize| map.sector(map.sidedef(side_idx).sector_idx as usize);
+        let base = sector_of(0);
+        let find_side =
+            |pred: &dyn Fn(&Sector) -> bool| (0..map.sidedef_count()).find(|&i| pred(&sector_of(i))).unwrap() as u16;
+        let floor_delta = find_side(&|s| s.floor_height != base.floor_height);
+        let ceiling_delta =
+            find_side(&|s| s.floor_height == base.floor_height && s.ceiling_height != base.ceiling_height);
+        let line = |flags: u16, special_type: u16, left_side_idx: u16| LineDef {
+            v1: Vertex { x: 0, y: 0 },
+            v2: Vertex { x: 64, y: 0 },
+            flags: flags | LINE_ALWAYS_ON_AMAP,
+            special_type,
+            sector_tag: 0,
+            right_side_idx: 0,
+            left_side_idx,
+        };
+
+        let color = |l: LineDef| level.pick_automap_line_color(0, &l);
+        assert!(color(line(LINE_BLOCKS, 0, NO_SIDEDEF)) == AMAP_WALL_COLOR);
+        assert!(color(line(LINE_TWO_SIDED, 0, floor_delta)) == AMAP_FLOOR_DELTA_COLOR);
+        assert!(color(line(LINE_TWO_SIDED, 0, ceiling_delta)) == AMAP_CEILING_DELTA_COLOR);
+        assert!(color(line(LINE_TWO_SIDED, TELEPORT_LINE_SPECIAL, 0)) == AMAP_TELEPORTER_COLOR);
+        assert!(color(line(LINE_TWO_SIDED | LINE_SECRET, 0, floor_delta)) == AMAP_WALL_COLOR);
+        // no height delta => not drawn
+        assert!(color(line(LINE_TWO_SIDED, 0, 0)) == BLACK);
+        assert!(color(line(LINE_BLOCKS | LINE_NEVER_ON_AMAP, 0, NO_SIDEDEF)) == BLACK);
+
+        // the extra colors
+        level.flags |= FLAG_AUTOMAP_EXTRA_COLORS;
+        let color = |l: LineDef| level.pick_automap_line_color(0, &l);
+        assert!(color(line(LINE_TWO_SIDED, 0, 0)) == AMAP_TWO_SIDED_COLOR);
+        assert!(color(line(LINE_TWO_SIDED | LINE_SECRET, 0, floor_delta)) == AMAP_SECRET_COLOR);
+        assert!(color(line(LINE_TWO_SIDED, 1, ceiling_delta)) == AMAP_SPECIAL_COLOR);
+    }
+
+    #[test]
+    fn classic_arrow_points_along_the_player_angle() {
+        let pos = Vertex { x: 100, y: -50 };
+        for deg in (0..360).step_by(30) {
+            let angle = Angle::from_degrees(deg);
+            let lines = classic_arrow_lines(pos, angle);
+            let (tip, tail) = lines[0];
+            // the tip is ahead of the player (and the tail behind), along the facing direction
+            let ahead = |v: Vertex| {
+                let (dx, dy) = ((v.x - pos.x) as f64, (v.y - pos.y) as f64);
+                dx * angle.rad().cos() + dy * angle.rad().sin()
+            };
+            assert!((ahead(tip) - 25.0).abs() < 1.5, "{deg} degrees");
+            assert!((ahead(tail) + 25.0).abs() < 1.5, "{deg} degrees");
+            // the arrow head lines start at the tip and go back
+            for &(v1, v2) in &lines[1..3] {
+                assert_eq!(v1, tip);
+                assert!(ahead(v2) < ahead(tip), "{deg} degrees");
+            }
+        }
+    }
+
+    #[test]
+    fn half_way_interpolation_shows_the_player_in_the_middle() {
+        let mut level = test_level("E1M1");
+        let start = level.player_pos();
+        level.place_player(start, Angle::from_degrees(350));
+        level.begin_tick();
+        level.player.pos = Vertex {
+            x: start.x + 20,
+            y: start.y - 10,
+        };
+        level.player.angle = Angle::from_degrees(30);
+
+        level.set_interpolation(0.5);
+        let view = level.view_player();
+        assert_eq!((view.pos.x, view.pos.y), (start.x + 10, start.y - 5));
+        // (turning the shortest way, through 0 degrees)
+        assert_eq!(view.angle.deg(), 10);
+        // at the end of the tick, the view is the current position
+        level.set_interpolation(1.0);
+        assert_eq!(level.view_player().pos, level.player_pos());
+    }
+
+    #[test]
+    fn random_textures_are_the_same_for_the_same_seed() {
+        let mut level1 = test_level("E1M1");
+        let mut level2 = test_level("E1M1");
+        level1.randomize_textures(42);
+        level2.randomize_textures(42);
+        assert_eq!(level1.texture_overrides.len(), level1.map_data.sidedef_count());
+        assert_eq!(level1.texture_overrides, level2.texture_overrides);
+        // the see-through walls stay see-through
+        for (&idx, keys) in &level1.texture_overrides {
+            let side = level1.map_data.sidedef(idx as usize);
+            assert_eq!(is_no_texture(keys[2]), is_no_texture(side.middle_texture_key));
+        }
+        level2.randomize_textures(43);
+        assert_ne!(level1.texture_overrides, level2.texture_overrides);
+    }
+
+    #[test]
+    fn player_slows_down_gradually_after_releasing_forward() {
+        let mut level = test_level("E1M1");
+        walk_forward(&mut level, 8);
+        let dt = 1.0 / TICS_PER_SECOND;
+        let mut speed = level.vel_x.hypot(level.vel_y);
+        assert!(speed > 0.0);
+        for _ in 0..5 {
+            let pos = level.player_pos();
+            level.apply_player_momentum(dt);
+            // still moving, but slower
+            let new_speed = level.vel_x.hypot(level.vel_y);
+            assert!(new_speed > 0.0 && new_speed < speed);
+            assert_ne!(level.player_pos(), pos);
+            speed = new_speed;
+        }
+    }
+
+    #[test]
+    fn view_bobs_only_while_moving() {
+        let mut level = test_level("E1M1");
+        let bobs = |level: &mut ActiveLevel| -> Vec<(f64, f64)> {
+            (0..40)
+                .map(|tic| {
+                    level.set_game_time((tic as f64) / TICS_PER_SECOND);
+                    level.view_bob()
+                })
+                .collect()
+        };
+        assert!(bobs(&mut level).iter().all(|&bob| bob == (0.0, 0.0)));
+
+        level.vel_x = PLAYER_MOVE_SPEED;
+        let moving = bobs(&mut level);
+        assert!(moving
+            .iter()
+            .all(|(h, v)| h.abs() <= VIEW_BOB_MAX / 4.0 && v.abs() <= VIEW_BOB_MAX / 2.0));
+        // up and down
+        assert!(moving.iter().any(|(_, v)| *v > 1.0));
+        assert!(moving.iter().any(|(_, v)| *v < -1.0));
+
+        assert!(!level.toggle_view_bob());
+        assert!(bobs(&mut level).iter().all(|&bob| bob == (0.0, 0.0)));
+    }
+
+    #[test]
+    fn teleport_destination_marker_is_painted_at_its_position() {
+        use crate::layer::Layer;
+
+        let (mut level, _) = find_line_special(&[39, 97]);
+        let dest = level
+            .map_data
+            .things()
+            .find(|t| t.type_code() == TELEPORT_DEST_TYPE)
+            .unwrap();
+        level.set_automap_mode(AutomapMode::Full);
+        level.amap_center = dest.pos;
+        let p = level.translate_automap_vertex(dest.pos);
+        let mut layer = Layer::new(320, 200);
+        level.paint_automap_spawn_markers(&mut layer);
+        // a diamond around the thing's position
+        let m = AMAP_MARKER_SIZE;
+        for (x, y) in [(p.x - m, p.y), (p.x + m, p.y), (p.x, p.y - m), (p.x, p.y + m)] {
+            assert!(layer.read_pixel(x, y) == Some(AMAP_TELEPORT_DEST_COLOR));
+        }
+        assert!(layer.read_pixel(p.x, p.y).is_none());
+    }
+
+    #[test]
+    fn two_sided_line_with_a_missing_side_does_not_panic() {
+        use crate::layer::Layer;
+        use crate::wad::test_wads::*;
+
+        // break the first two-sided line of E1M1: remove its left side
+        let mut lumps = doom1_lumps();
+        let lump_idx = map_lump_idx(&lumps, "E1M1", "LINEDEFS");
+        let linedefs = &mut lumps[lump_idx].1;
+        let line_idx = (0..linedefs.len() / LINEDEF_SIZE)
+            .find(|i| buf_to_u16(&linedefs[i * LINEDEF_SIZE + 4..]) & LINE_TWO_SIDED != 0)
+            .unwrap();
+        let ofs = line_idx * LINEDEF_SIZE;
+        linedefs[ofs + 12..ofs + 14].copy_from_slice(&NO_SIDEDEF.to_le_bytes());
+        let wad_data = build_wad(&lumps).unwrap();
+        assert!(wad_data
+            .warnings()
+            .iter()
+            .any(|w| w.contains(&format!("linedef #{line_idx} is two-sided"))));
+
+        // look at the broken line, walk through it and paint everything
+        let cfg = GameConfig::new(wad_data, 320, 200);
+        let mut level = ActiveLevel::new(cfg, 0);
+        let line = level.map_data.linedef(line_idx);
+        let mid = Vertex {
+            x: (line.v1.x + line.v2.x) / 2,
+            y: (line.v1.y + line.v2.y) / 2,
+        };
+        let mut layer = Layer::new(320, 200);
+        for angle in [0, 90, 180, 270] {
+            level.place_player(
+                mid.polar_translate(32.0, Angle::from_degrees(angle)),
+                Angle::from_degrees(angle + 180),
+            );
+            level.paint(&mut layer);
+            level.move_player(0.5);
+            level.apply_player_momentum(0.5);
+            level.paint(&mut layer);
+        }
+        level.toggle_automap();
+        level.paint(&mut layer);
+    }
+
+    #[test]
+    fn blue_door_needs_the_blue_key() {
+        let (mut level, line_idx) = find_line_special(&[26, 32]);
+        face_line_front(&mut level, line_idx, 16.0);
+        level.use_line();
+        assert_eq!(level.take_messages(), vec!["You need a blue key to open this door"]);
+        assert_eq!(level.take_sound_events()[0].name, SOUND_OOF);
+
+        level.player_state_mut().keys = KeyColor::Blue.key_flags();
+        level.use_line();
+        assert!(level.take_messages().is_empty());
+        assert!(level.take_sound_events().is_empty());
+    }
+
+    #[test]
+    fn crossing_a_teleport_line_moves_the_player_to_the_destination() {
+        let (mut level, line_idx) = find_line_special(&[39, 97]);
+        let tag = level.map_data.linedef(line_idx).sector_tag;
+        let dest = level
+            .map_data
+            .things()
+            .find(|t| t.type_code() == TELEPORT_DEST_TYPE && level.sector_at(t.pos).unwrap().tag_nr == tag)
+            .unwrap();
+
+        face_line_front(&mut level, line_idx, 8.0);
+        let teleported = (0..10).any(|_| walk_forward(&mut level, 1).iter().any(|s| s.name == SOUND_TELEPORT));
+        assert!(teleported);
+        // (the player stops when teleported)
+        assert_eq!(level.player_pos(), dest.pos);
+        assert_eq!(level.player_angle(), dest.angle);
+    }
+
+    #[test]
+    fn walk_over_line_triggers_only_once() {
+        use crate::wad::test_wads::*;
+
+        // turn the W1 "lower floor" line of E1M1 into a W1 exit line, to see when it triggers
+        let mut lumps = doom1_lumps();
+        let lump_idx = map_lump_idx(&lumps, "E1M1", "LINEDEFS");
+        let linedefs = &mut lumps[lump_idx].1;
+        let line_idx = (0..linedefs.len() / LINEDEF_SIZE)
+            .find(|i| buf_to_u16(&linedefs[i * LINEDEF_SIZE + 6..]) == 36)
+            .unwrap();
+        let ofs = line_idx * LINEDEF_SIZE + 6;
+        linedefs[ofs..ofs + 2].copy_from_slice(&52_u16.to_le_bytes());
+        let cfg = GameConfig::new(build_wad(&lumps).unwrap(), 320, 200);
+        let mut level = ActiveLevel::new(cfg, 0);
+
+        face_line_front(&mut level, line_idx, 8.0);
+        walk_forward(&mut level, 8);
+        assert_eq!(level.take_exit(), Some(LevelExit::Normal));
+        // walk back and forth over the line: it does not trigger again
+        for _ in 0..2 {
+            level.place_player(level.player_pos(), level.player_angle() + Angle::with_180_deg());
+            walk_forward(&mut level, 16);
+            assert_eq!(level.take_exit(), None);
+        }
+    }
+
+    #[test]
+    fn camera_follows_the_player() {
+        let mut level = test_level("E1M1");
+        let before = level.camera();
+        assert_eq!(before.pos, level.player_pos());
+        assert_eq!(before.angle, level.player_angle());
+
+        level.begin_tick();
+        walk_forward(&mut level, 10);
+        level.rotate_player(0.25);
+        let after = level.camera();
+        assert_ne!(after.pos, before.pos);
+        assert_eq!(after.pos, level.player_pos());
+        assert_eq!(after.angle, level.player_angle());
+        assert_ne!(after.angle, before.angle);
+        let floor = level.sector_at(after.pos).unwrap().floor_height;
+        assert_eq!(after.eye_z, floor as f64 + PLAYER_VIEW_HEIGHT + level.view_bob().1);
+        assert_eq!(after.half_fov, before.half_fov);
+    }
+
+    #[test]
+    fn square_is_split_into_two_triangles() {
+        let square = [(0, 0), (64, 0), (64, 64), (0, 64)].map(|(x, y)| Vertex { x, y });
+        let triangles = triangle_fan(&square);
+        assert_eq!(triangles.len(), 2);
+        // the triangles cover the whole square (without overlapping)
+        let area = |[a, b, c]: [Vertex; 3]| ((b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y)).abs() / 2;
+        assert_eq!(triangles.iter().map(|&t| area(t)).sum::<i32>(), 64 * 64);
+        for triangle in triangles {
+            assert!(triangle.iter().all(|v| square.contains(v)));
+        }
+        assert!(triangle_fan(&square[..2]).is_empty());
+    }
+
+    #[test]
+    fn render_stats_of_the_start_view_are_consistent() {
+        use crate::layer::Layer;
+        use AutomapMode::*;
+
+        let mut level = test_level("E1M1");
+        level.set_automap_mode(Off);
+        let mut layer = Layer::new(320, 200);
+        level.paint(&mut layer);
+        let stats = level.render_stats();
+        assert!(stats.subsectors_visited > 0);
+        assert!(stats.segs_drawn > 0);
+        assert!(stats.segs_drawn <= stats.segs_collected);
+        // every screen column shows (at least) one wall
+        assert!(stats.columns_drawn >= 320);
+        assert!(stats.draw_calls >= stats.columns_drawn);
+    }
+
+    #[test]
+    fn monster_blocking_lines_only_block_monsters() {
+        let level = test_level("E1M1");
+        let crossings: Vec<_> = level
+            .map_data
+            .linedefs()
+            .filter(|line| (line.flags & LINE_BLOCKS_MONSTERS) != 0)
+            .map(|line| {
+                // from 8 units in front of the line's middle, to 8 units behind it
+                let (dx, dy) = ((line.v2.x - line.v1.x) as f64, (line.v2.y - line.v1.y) as f64);
+                let len = dx.hypot(dy);
+                let (nx, ny) = (dy / len * 8.0, -dx / len * 8.0);
+                let (mx, my) = (
+                    (line.v1.x + line.v2.x) as f64 / 2.0,
+                    (line.v1.y + line.v2.y) as f64 / 2.0,
+                );
+                let player_passes = level.can_move(mx + nx, my + ny, mx - nx, my - ny, false);
+                let monster_passes = level.can_move(mx + nx, my + ny, mx - nx, my - ny, true);
+                (player_passes, monster_passes)
+            })
+            .collect();
+        assert!(crossings.contains(&(true, false)));
+        assert!(crossings.iter().all(|&(_, monster_passes)| !monster_passes));
+    }
+
+    #[test]
+    fn held_turn_reaches_full_speed_after_the_ramp() {
+        const DT: f64 = 0.02;
+        let mut level = test_level("E1M1");
+        let turn_step = |level: &mut ActiveLevel, dt: f64| {
+            let before = level.player_angle();
+            level.turn_player(dt, 0.2, 0.25);
+            (level.player_angle() - before).rad()
+        };
+        let full_step = DT * PLAYER_ROT_SPEED;
+        let first = turn_step(&mut level, DT);
+        assert!((first - full_step * (0.25 + 0.75 * 0.1)).abs() < 1e-9);
+        let steps: Vec<f64> = (0..12).map(|_| turn_step(&mut level, DT)).collect();
+        assert!(steps.windows(2).all(|w| w[1] >= w[0]));
+        // after 0.2 seconds, the turn is at full speed
+        assert!((steps[8] - full_step).abs() < 1e-9);
+        assert!((steps[11] - full_step).abs() < 1e-9);
+
+        // turning the other way starts slowly again
+        // (the angles are always positive => a small turn clockwise is almost a full turn)
+        let back = turn_step(&mut level, -DT);
+        assert!((back - (std::f64::consts::TAU - first)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn player_faces_the_angle_of_the_start() {
+        use crate::wad::test_wads::*;
+
+        // the E1M1 player start, turned to an angle which is not a multiple of 45 degrees
+        let mut lumps = doom1_lumps();
+        let things_idx = map_lump_idx(&lumps, "E1M1", "THINGS");
+        let player_start = lumps[things_idx]
+            .1
+            .chunks_exact_mut(10)
+            .find(|thing| thing[6..8] == [1, 0])
+            .unwrap();
+        player_start[4..6].copy_from_slice(&100_u16.to_le_bytes());
+        let cfg = GameConfig::new(build_wad(&lumps).unwrap(), 320, 200);
+        let level = ActiveLevel::new(cfg, 0);
+        let start = level.map_data.player_starts()[0].clone().unwrap();
+        assert_eq!(start.angle.deg(), 100);
+        assert_eq!(level.player_angle().deg(), 100);
+        assert_eq!(level.player_pos(), start.pos);
+    }
+
+    #[test]
+    fn bsp_leaves_are_ordered_front_to_back() {
+        let level = test_level("E1M1");
+        let view_point = level.player_pos();
+        let leaves = level.bsp_leaves_front_to_back(view_point);
+        assert_eq!(leaves[0], level.subsector_at(view_point));
+        let mut sorted = leaves.clone();
+        sorted.sort_unstable();
+        sorted.dedup();
+        assert_eq!(sorted.len(), level.map_data.ssector_count());
+        assert_eq!(leaves.len(), sorted.len());
+
+        // along any ray from the view point, the nearer sub-sectors come first
+        let order_of = |leaf: u16| leaves.iter().position(|&l| l == leaf).unwrap();
+        for deg in (0..360).step_by(15) {
+            let mut last_order = 0;
+            for dist in (0..2048).step_by(8) {
+                let point = view_point.polar_translate(dist as f64, Angle::from_degrees(deg));
+                let order = order_of(level.subsector_at(point));
+                assert!(order >= last_order, "angle {deg}, distance {dist}");
+                last_order = order;
+            }
+        }
+    }
+
+    #[test]
+    fn jumping_to_the_automap_center() {
+        let mut level = test_level("E1M1");
+        level.set_automap_mode(AutomapMode::Full);
+        let angle = level.player_angle();
+        // the player 2 start is surely inside the map
+        let target = level.map_data.player_starts()[1].clone().unwrap().pos;
+        level.amap_center = target;
+        assert!(level.jump_to_automap_center());
+        assert_eq!(level.player_pos(), target);
+        assert_eq!(level.player_angle(), angle);
+
+        let (map_width, _) = level.map_data.dimensions();
+        level.amap_center = Vertex {
+            x: level.map_data.center().x + map_width,
+            y: target.y,
+        };
+        assert!(!level.jump_to_automap_center());
+        assert_eq!(level.player_pos(), target);
+    }
+
+    #[test]
+    fn automap_is_painted_only_inside_its_viewport() {
+        use crate::layer::Layer;
+
+        let mut level = test_level("E1M1");
+        level.set_automap_mode(AutomapMode::Full);
+        level.zoom_automap(1.0);
+        let mut layer = Layer::new(320, 200);
+        level.paint(&mut layer);
+        let (vx, vy, vw, vh) = level.automap_viewport();
+        assert!(vh < 200);
+        for y in 0..200 {
+            for x in 0..320 {
+                let color = layer.read_pixel(x, y).unwrap();
+                let inside = x >= vx && x < vx + vw && y >= vy && y < vy + vh;
+                let on_border = inside && (x == vx || x == vx + vw - 1 || y == vy || y == vy + vh - 1);
+                if !inside {
+                    assert!(color == BLACK, "pixel {x},{y}");
+                } else if on_border {
+                    assert!(color == AMAP_BORDER_COLOR, "pixel {x},{y}");
+                }
+            }
+        }
+        // the map would go below the viewport, if it was not clipped
+        assert!(level
+            .map_data
+            .vertices()
+            .any(|v| level.translate_automap_vertex(v).y >= vy + vh));
+    }
+
+    #[test]
+    fn backpedaling_is_slower_than_moving_forward() {
+        let step_distance = |ellapsed_time: f64| {
+            let mut level = test_level("E1M1");
+            let (x, y) = (level.player_x, level.player_y);
+            level.move_player(ellapsed_time);
+            level.apply_player_momentum(ellapsed_time.abs());
+            (level.player_x - x).hypot(level.player_y - y)
+        };
+        let dt = 1.0 / TICS_PER_SECOND;
+        let forward = step_distance(dt);
+        let backward = step_distance(-dt);
+        assert!(forward > 0.0);
+        assert!((backward - forward * PLAYER_BACKPEDAL_FACTOR).abs() < 1e-9);
+    }
+
+    #[test]
+    fn phase_timers_add_up_to_the_total_time() {
+        use crate::layer::Layer;
+
+        let mut level = test_level("E1M1");
+        level.set_automap_mode(AutomapMode::Overlay);
+        let mut layer = Layer::new(320, 200);
+        level.paint(&mut layer);
+        // not measured, unless profiling
+        assert_eq!(level.render_stats().total_time_us, 0);
+
+        assert!(level.toggle_profiling());
+        level.paint(&mut layer);
+        let stats = level.render_stats();
+        let phases_time_us = stats.bsp_time_us + stats.sky_time_us + stats.walls_time_us + stats.automap_time_us;
+        assert!(stats.walls_time_us > 0 && stats.automap_time_us > 0);
+        assert!(phases_time_us <= stats.total_time_us);
+        // (almost all the time is spent in the measured phases)
+        assert!(phases_time_us * 2 >= stats.total_time_us, "{stats:?}");
+    }
+
+    #[test]
+    fn culled_automap_lines_include_every_on_screen_line() {
+        use crate::layer::Layer;
+
+        let mut level = test_level("E1M1");
+        level.set_automap_mode(AutomapMode::Full);
+        level.zoom_automap(2.0);
+        let mut culled = level.automap_visible_lines();
+        culled.sort_unstable();
+        let line_count = level.map_data.linedef_count();
+        assert!(culled.windows(2).all(|w| w[0] < w[1]) && culled.iter().all(|&idx| idx < line_count));
+        assert!(culled.len() < line_count);
+        assert!(level.segless_lines.iter().all(|idx| culled.contains(idx)));
+
+        // a line is on-screen if any of its points is inside the viewport
+        let (vx, vy, vw, vh) = level.automap_viewport();
+        for idx in 0..line_count {
+            let line = level.map_data.linedef(idx);
+            let is_on_screen = (0..=64).any(|i| {
+                let t = i as f64 / 64.0;
+                let v = Vertex {
+                    x: line.v1.x + ((line.v2.x - line.v1.x) as f64 * t) as i32,
+                    y: line.v1.y + ((line.v2.y - line.v1.y) as f64 * t) as i32,
+                };
+                let p = level.translate_automap_vertex(v);
+                p.x >= vx && p.x < vx + vw && p.y >= vy && p.y < vy + vh
+            });
+            assert!(!is_on_screen || culled.contains(&idx), "line {idx}");
+        }
+
+        // and the culled automap (the default) looks the same as the one with all the lines
+        let mut culled_layer = Layer::new(320, 200);
+        level.paint(&mut culled_layer);
+        assert!(!level.toggle_automap_culling());
+        let mut layer = Layer::new(320, 200);
+        level.paint(&mut layer);
+        for y in 0..200 {
+            for x in 0..320 {
+                assert!(layer.read_pixel(x, y) == culled_layer.read_pixel(x, y), "pixel {x},{y}");
+            }
+        }
+    }
+
+    #[test]
+    fn lines_without_segs_are_always_on_the_culled_automap() {
+        use crate::wad::test_wads::*;
+
+        // duplicate the first line of E1M1: the duplicate is not part of any seg
+        let mut lumps = doom1_lumps();
+        let lump_idx = map_lump_idx(&lumps, "E1M1", "LINEDEFS");
+        let linedefs = &mut lumps[lump_idx].1;
+        let first_line = linedefs[0..LINEDEF_SIZE].to_vec();
+        linedefs.extend_from_slice(&first_line);
+        let line_count = linedefs.len() / LINEDEF_SIZE;
+        let cfg = GameConfig::new(build_wad(&lumps).unwrap(), 320, 200);
+        let level = ActiveLevel::new(cfg, 0);
+        assert_eq!(level.segless_lines, [line_count - 1]);
+        assert!(level.automap_visible_lines().contains(&(line_count - 1)));
+        assert!(test_level("E1M1").segless_lines.is_empty());
+    }
+
+    #[test]
+    fn thick_automap_lines_paint_about_3_times_the_pixels() {
+        use crate::layer::Layer;
+
+        let mut level = test_level("E1M1");
+        let c = level.automap_center();
+        let painted_pixels = |level: &ActiveLevel| {
+            let mut layer = Layer::new(320, 200);
+            let v1 = Vertex {
+                x: c.x - 100,
+                y: c.y - 40,
+            };
+            let v2 = Vertex {
+                x: c.x + 100,
+                y: c.y + 60,
+            };
+            level.draw_automap_line(v1, v2, WHITE, &mut layer);
+            let pixels = (0..200).flat_map(|y| (0..320).map(move |x| (x, y)));
+            pixels.filter(|&(x, y)| layer.read_pixel(x, y).is_some()).count() as f64
+        };
+        let thin = painted_pixels(&level);
+        level.set_automap_thickness(3);
+        let thick = painted_pixels(&level);
+        assert!(thin > 20.0);
+        assert!((thick / thin - 3.0).abs() < 0.5, "{thick} vs {thin}");
+    }
+
+    #[cfg(feature = "fixed_point")]
+    #[test]
+    fn fixed_point_collisions_use_the_vanilla_side_checks() {
+        let line = LineDef {
+            v1: Vertex { x: 0, y: 0 },
+            v2: Vertex { x: 0, y: 64 },
+            flags: LINE_BLOCKS,
+            special_type: 0,
+            sector_tag: 0,
+            right_side_idx: 0,
+            left_side_idx: NO_SIDEDEF,
+        };
+        // a normal move through the line, from its front side
+        assert_eq!(move_crosses_line(&line, 8.0, 32.0, -8.0, 32.0), Some(true));
+        assert_eq!(move_crosses_line(&line, 8.0, 32.0, 4.0, 32.0), None);
+        assert_eq!(move_crosses_line(&line, 8.0, 80.0, -8.0, 80.0), None);
+        // vanilla's `FixedMul` rounds down, so the points closer than 1/64 units to this (64 units long)
+        // line count as being on its back side - moving between them does not cross the line
+        let unit = Fixed(1).to_f64();
+        assert_eq!(move_crosses_line(&line, unit * 16.0, 32.0, -unit * 16.0, 32.0), None);
+        assert_eq!(move_crosses_line(&line, 1.0, 32.0, -unit, 32.0), Some(true));
+        // the positions are also rounded down to map units, like `x >> FRACBITS`
+        assert_eq!(map_units(-unit), -1);
+        assert_eq!(
+            move_bounding_box(-unit, 0.0, 1.5, 0.5),
+            (Vertex { x: -2, y: -1 }, Vertex { x: 3, y: 2 })
+        );
+    }
+
+    #[cfg(feature = "fixed_point")]
+    #[test]
+    fn fixed_point_friction_matches_vanilla() {
+        use crate::fixed::FRACUNIT;
+
+        // vanilla: FixedMul(momentum, FRICTION), once per tic
+        const FRICTION: i64 = 0xe800;
+        // (negative momentums are rounded down too, so they slow down slightly less)
+        let start_vel = 700 * FRACUNIT + 12345;
+        let (mut vanilla_vel_x, mut vanilla_vel_y) = (start_vel, -start_vel);
+        for _ in 0..35 {
+            vanilla_vel_x = ((vanilla_vel_x as i64 * FRICTION) >> 16) as i32;
+            vanilla_vel_y = ((vanilla_vel_y as i64 * FRICTION) >> 16) as i32;
+        }
+
+        // the same 35 tics, in frames of 1 tic, half a tic or at 60 FPS
+        for (frame_cnt, frame_time) in [
+            (35, 1.0 / TICS_PER_SECOND),
+            (70, 0.5 / TICS_PER_SECOND),
+            (60, 1.0 / 60.0),
+        ] {
+            let mut level = test_level("E1M1");
+            level.vel_x = Fixed(start_vel).to_f64();
+            level.vel_y = Fixed(-start_vel).to_f64();
+            for _ in 0..frame_cnt {
+                level.apply_player_friction(frame_time);
+            }
+            assert_eq!(Fixed::from_f64(level.vel_x).0, vanilla_vel_x, "{frame_cnt} frames");
+            assert_eq!(Fixed::from_f64(level.vel_y).0, vanilla_vel_y, "{frame_cnt} frames");
+        }
+    }
+
+    #[test]
+    fn teleporting_resets_the_momentum_unless_kept() {
+        let (mut level, line_idx) = find_line_special(&[39, 97]);
+        let tag = level.map_data.linedef(line_idx).sector_tag;
+        for keep in [false, true] {
+            level.set_teleport_keeps_momentum(keep);
+            (level.vel_x, level.vel_y) = (150.0, -80.0);
+            assert!(level.teleport_player(tag));
+            let expected = if keep { (150.0, -80.0) } else { (0.0, 0.0) };
+            assert_eq!((level.vel_x, level.vel_y), expected);
+        }
+    }
+
+    #[test]
+    fn every_sub_sector_gets_its_own_color() {
+        let level = test_level("E1M1");
+        let colors: Vec<RGB> = (0..level.map_data.ssector_count() as u16).map(debug_color).collect();
+        assert!(colors.len() > 100);
+        for (i, c1) in colors.iter().enumerate() {
+            // (bright enough to be seen on the black automap)
+            assert!(c1.r >= 64 && c1.g >= 64 && c1.b >= 64);
+            assert!(colors[i + 1..].iter().all(|c2| c1 != c2), "sub-sector {i}");
+        }
+    }
+
+    #[test]
+    fn bad_sidedef_indices_do_not_crash_the_rendering() {
+        use crate::layer::Layer;
+        use crate::wad::test_wads::*;
+
+        // point both sides of the first 40 lines of E1M1 to missing sidedefs
+        let mut lumps = doom1_lumps();
+        let lump_idx = map_lump_idx(&lumps, "E1M1", "LINEDEFS");
+        let linedefs = &mut lumps[lump_idx].1;
+        for ofs in (0..linedefs.len()).step_by(LINEDEF_SIZE).take(40) {
+            linedefs[ofs + 10..ofs + 14].copy_from_slice(&[0xF0, 0x7F, 0xF1, 0x7F]);
+        }
+        let cfg = GameConfig::new(build_wad(&lumps).unwrap(), 320, 200);
+        let mut level = ActiveLevel::new(cfg, 0);
+        let mut layer = Layer::new(320, 200);
+        for mode in [AutomapMode::Off, AutomapMode::Overlay, AutomapMode::Full] {
+            level.set_automap_mode(mode);
+            level.paint(&mut layer);
+        }
+        level.use_line();
+        walk_forward(&mut level, 10);
+    }
+
+    #[test]
+    fn projecting_points_ahead_and_behind() {
+        let level = test_level("E1M1");
+        let camera = level.camera();
+        let ahead = camera.pos.polar_translate(200.0, camera.angle);
+        let (x, y, depth) = level.project_point(ahead, camera.eye_z).unwrap();
+        assert_eq!((x, y), (160, 100));
+        assert!((depth - 200.0).abs() < 1.0, "{depth}");
+        // higher points are higher on the screen, points on the left are on the left
+        let (_, y, _) = level.project_point(ahead, camera.eye_z + 32.0).unwrap();
+        assert!(y < 100);
+        let left = camera
+            .pos
+            .polar_translate(200.0, camera.angle + Angle::from_degrees(20));
+        let (x, _, _) = level.project_point(left, camera.eye_z).unwrap();
+        assert!(x < 160);
+
+        // behind the camera, or outside the FOV
+        let behind = camera.pos.polar_translate(200.0, camera.angle + Angle::with_180_deg());
+        assert_eq!(level.project_point(behind, camera.eye_z), None);
+        let aside = camera
+            .pos
+            .polar_translate(200.0, camera.angle + camera.half_fov + Angle::from_degrees(10));
+        assert_eq!(level.project_point(aside, camera.eye_z), None);
+    }
+
+    #[test]
+    fn sky_covers_every_column_down_to_the_horizon() {
+        use crate::layer::Layer;
+
+        let level = test_level("E1M1");
+        for (angle, horizon_offset) in [(0, 0), (135, 5), (290, -7)] {
+            let mut view = level.view_player();
+            view.angle = Angle::from_degrees(angle);
+            let mut layer = Layer::new(320, 200);
+            level.paint_sky(&view, horizon_offset, &mut layer);
+            let horizon = 100 + horizon_offset;
+            for x in 0..320 {
+                assert!((0..horizon).all(|y| layer.read_pixel(x, y).is_some()), "column {x}");
+                assert!((horizon..200).all(|y| layer.read_pixel(x, y).is_none()), "column {x}");
+            }
+        }
+    }
+
+    #[test]
+    fn overlay_automap_lets_the_3d_view_show_through() {
+        use crate::layer::Layer;
+        use AutomapMode::*;
+
+        let mut level = test_level("E1M1");
+        level.set_automap_mode(Off);
+        let mut modes = vec![level.automap_mode()];
+        for _ in 0..3 {
+            level.toggle_automap();
+            modes.push(level.automap_mode());
+        }
+        assert_eq!(modes, [Off, Full, Overlay, Off]);
+
+        let mut view_3d = Layer::new(320, 200);
+        level.paint(&mut view_3d);
+        level.set_automap_mode(Overlay);
+        // (zoomed in, for a longer player arrow)
+        level.zoom_automap(10.0);
+        let mut overlay = Layer::new(320, 200);
+        level.paint(&mut overlay);
+
+        // the (white) player arrow is blended over the 3D view - it is never painted opaque
+        let mut blended_cnt = 0;
+        for y in 0..200 {
+            for x in 0..320 {
+                let (below, above) = (view_3d.read_pixel(x, y).unwrap(), overlay.read_pixel(x, y).unwrap());
+                if above != below && below != WHITE {
+                    assert!(above != WHITE, "pixel {x},{y}");
+                    blended_cnt += (above == WHITE.blend_over(below, AMAP_OVERLAY_ALPHA)) as u32;
+                }
+            }
+        }
+        assert!(blended_cnt >= 10, "{blended_cnt}");
+    }
+}
@@ -1,26 +1,87 @@
 //! Main lib for the RustooM Doom-like engine/demo
 
 mod angle;
+mod cheats;
+mod clock;
+mod console;
+mod dynres;
+#[cfg(feature = "fixed_point")]
+mod fixed;
 mod font;
 mod game;
 mod gamecfg;
 mod graphics;
+mod hud;
+mod layer;
 mod level;
 mod map;
 mod map_items;
+mod map_titles;
+mod menu;
+#[cfg(feature = "mmap")]
+mod mmap;
 mod painter;
 mod palette;
 mod pixmap;
+mod player;
 mod sdl_wrapper;
+mod settings;
+mod sound;
+mod specials;
 mod things;
+mod trig;
 mod utils;
 mod wad;
+mod walls;
 
-pub use game::*;
-pub use gamecfg::*;
-pub use painter::*;
-pub use sdl_wrapper::*;
-pub use wad::*;
+/// The stable API for embedding the engine (the internal modules are not public).
+///
+/// ```
+/// use rustoom::prelude::*;
+///
+/// # fn main() -> Result<(), String> {
+/// let wad_bytes = std::fs::read("DOOM1.WAD").map_err(|e| e.to_string())?;
+/// let wad_data = WadData::from_bytes("DOOM1.WAD", true, wad_bytes.into())?;
+/// let cfg = GameConfig::new(wad_data, 320, 200);
+/// let mut doom_game = DoomGame::new(cfg)?;
+/// // paint a few frames, without opening a window (`run_sdl_loop` opens one, for playing)
+/// let sdl_config = SdlConfiguration::new("RusTooM", 320, 200, 3, SleepKind::YIELD);
+/// let frame = run_headless(&sdl_config, &mut doom_game, 3, 1.0 / 35.0);
+/// assert_eq!(frame.len(), 320 * 200 * 3);
+/// # Ok(())
+/// # }
+/// ```
+pub mod prelude {
+    pub use crate::angle::Angle;
+    pub use crate::clock::GameClock;
+    pub use crate::game::DoomGame;
+    pub use crate::gamecfg::GameConfig;
+    pub use crate::level::{Camera, RenderStats};
+    pub use crate::map_items::Vertex;
+    pub use crate::map_titles::level_title;
+    pub use crate::painter::{BlendedPainter, ClippedPainter, Painter, RGB};
+    pub use crate::palette::{TranslatedColorMapper, Translation};
+    pub use crate::pixmap::patch_from_rgba;
+    pub use crate::sdl_wrapper::{
+        run_headless, run_sdl_loop, save_ppm, GraphicsLoop, ScaleKind, SdlConfiguration, SleepKind,
+    };
+    pub use crate::settings::{UserSettings, DEFAULT_FOV};
+    pub use crate::sound::{SoundData, SoundEvent};
+    pub use crate::wad::{WadData, WadError};
+}
+
+// shortcuts for the internal modules (`use crate::*`)
+pub(crate) use angle::Angle;
+pub(crate) use clock::GameClock;
+pub(crate) use gamecfg::*;
+pub(crate) use level::{Camera, RenderStats};
+pub(crate) use map_items::Vertex;
+pub(crate) use map_titles::level_title;
+pub(crate) use painter::*;
+pub(crate) use sdl_wrapper::*;
+pub(crate) use settings::*;
+pub(crate) use sound::*;
+pub(crate) use wad::*;
 
 // TODO clean up unused colors (+ move them in another mod ?)
 pub const BLACK: RGB = RGB { r: 0, g: 0, b: 0 };
@@ -6,7 +6,8 @@
 // This magic line prevents the opening of a terminal when launching a release build
 #![cfg_attr(not(any(test, debug_assertions)), windows_subsystem = "windows")]
 
-use rustoom::*;
+use rustoom::prelude::*;
+use std::path::Path;
 
 const SCR_WIDTH: i32 = 480;
 const SCR_HEIGHT: i32 = 360;
@@ -16,17 +17,195 @@ const SLEEP_KIND: SleepKind = SleepKind::YIELD;
 //const WAD_PATH: &str = "s:\\DOOM_Quake\\IWADs\\HERETIC.WAD";
 //const WAD_PATH: &str = "s:\\DOOM_Quake\\IWADs\\DOOM2.WAD";
 const WAD_PATH: &str = "DOOM1.WAD";
+const SETTINGS_PATH: &str = "rustoom.ini";
 
 fn main() -> Result<(), String> {
+    let args = parse_args(std::env::args().skip(1))?;
+
+    // load the user settings (or use the defaults, if there are none saved yet)
+    let (settings, settings_warnings) = UserSettings::load(SETTINGS_PATH)?;
+    for warning in settings_warnings {
+        eprintln!("WARNING: {warning}");
+    }
+
     // build the game engine
-    let wad_data = WadData::load(WAD_PATH, true)?;
-    let cfg = GameConfig::new(wad_data, SCR_WIDTH, SCR_HEIGHT);
+    let wad_data = WadData::load(&args.iwad_path, true)?;
+    for warning in wad_data.warnings() {
+        eprintln!("WARNING: {warning}");
+    }
+    for pwad_path in &args.pwad_paths {
+        // TODO load the PWADs on top of the IWAD, once merging WADs is supported
+        eprintln!("WARNING: PWADs are not supported yet => ignoring {pwad_path}");
+    }
+    let cfg = GameConfig::with_fov(wad_data, SCR_WIDTH, SCR_HEIGHT, settings.fov);
     let mut doom_game = DoomGame::new(cfg)?;
+    doom_game.apply_settings(&settings);
+    if let Some(skill) = args.skill {
+        doom_game.set_skill(skill);
+    }
+    if let Some(map_name) = &args.warp {
+        doom_game.load_map_by_name(map_name)?;
+    }
+
+    // non-interactive mode: render a single frame into an image, then exit
+    if let Some(render) = args.render {
+        render_frame(&mut doom_game, &render)?;
+        println!("Rendered {} into {}", render.map_name, render.out_path);
+        return Ok(());
+    }
 
     // main game loop
     let sdl_config = SdlConfiguration::new("RusTooM", SCR_WIDTH, SCR_HEIGHT, PIXEL_SIZE, SLEEP_KIND);
     run_sdl_loop(&sdl_config, &mut doom_game)?;
 
+    // save the settings if they were changed while playing (e.g. via the console),
+    // or if there is no settings file yet (so it exists, with all the defaults, for the user to edit)
+    let live_settings = doom_game.settings();
+    if *live_settings != settings || !Path::new(SETTINGS_PATH).exists() {
+        live_settings.save(SETTINGS_PATH)?;
+    }
+
     println!("RusTooM finished OK :)");
     Ok(())
 }
+
+/// Render a single frame (without SDL), from the requested viewpoint, and save it as a PPM image.
+fn render_frame(doom_game: &mut DoomGame, render: &RenderArgs) -> Result<(), String> {
+    doom_game.load_map_by_name(&render.map_name)?;
+    if let Some(pos) = render.pos {
+        doom_game.place_player(pos, render.angle.unwrap_or_else(|| doom_game.camera().angle));
+    } else if let Some(angle) = render.angle {
+        doom_game.place_player(doom_game.camera().pos, angle);
+    }
+    let sdl_config = SdlConfiguration::new("RusTooM", SCR_WIDTH, SCR_HEIGHT, 1, SleepKind::NONE);
+    let frame = run_headless(&sdl_config, doom_game, 1, 0.0);
+    save_ppm(&render.out_path, SCR_WIDTH, SCR_HEIGHT, &frame)
+}
+
+/// The command line arguments, like in vanilla DOOM, e.g.: `-iwad DOOM.WAD -warp E1M3 -skill 4`
+struct StartupArgs {
+    iwad_path: String,
+    pwad_paths: Vec<String>,
+    warp: Option<String>,
+    skill: Option<u8>,
+    render: Option<RenderArgs>,
+}
+
+/// The command line arguments for rendering a single frame, e.g.:
+/// `--render E1M1 --at 1056,-3616 --angle 90 --out frame.ppm`
+struct RenderArgs {
+    map_name: String,
+    pos: Option<Vertex>,
+    angle: Option<Angle>,
+    out_path: String,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<StartupArgs, String> {
+    let mut startup = StartupArgs {
+        iwad_path: String::from(WAD_PATH),
+        pwad_paths: Vec::new(),
+        warp: None,
+        skill: None,
+        render: None,
+    };
+    let mut map_name = None;
+    let mut pos = None;
+    let mut angle = None;
+    let mut out_path = String::from("frame.ppm");
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("Missing value for {arg}"));
+        match arg.as_str() {
+            "-iwad" => startup.iwad_path = value()?,
+            "-file" => startup.pwad_paths.push(value()?),
+            "-warp" => startup.warp = Some(value()?.to_ascii_uppercase()),
+            "-skill" => {
+                let v = value()?;
+                let skill = v.parse::<u8>().ok().filter(|s| (1..=5).contains(s));
+                startup.skill = Some(skill.ok_or(format!("Bad skill (must be 1 to 5): {v}"))?);
+            }
+            "--render" => map_name = Some(value()?.to_ascii_uppercase()),
+            "--at" => {
+                let v = value()?;
+                let (x, y) = v.split_once(',').ok_or(format!("Bad position: {v}"))?;
+                let parse = |s: &str| s.trim().parse::<i32>().map_err(|_| format!("Bad position: {v}"));
+                pos = Some(Vertex {
+                    x: parse(x)?,
+                    y: parse(y)?,
+                });
+            }
+            "--angle" => {
+                let v = value()?;
+                let deg = v.parse::<i32>().map_err(|_| format!("Bad angle: {v}"))?;
+                angle = Some(Angle::from_degrees(deg));
+            }
+            "--out" => out_path = value()?,
+            _ => return Err(format!("Unknown argument: {arg}")),
+        }
+    }
+    startup.render = map_name.map(|map_name| RenderArgs {
+        map_name,
+        pos,
+        angle,
+        out_path,
+    });
+    Ok(startup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> impl Iterator<Item = String> + '_ {
+        line.split_whitespace().map(String::from)
+    }
+
+    #[test]
+    fn startup_args_from_the_command_line() {
+        let startup = parse_args(args("")).unwrap();
+        assert_eq!(startup.iwad_path, WAD_PATH);
+        assert!(startup.pwad_paths.is_empty());
+        assert_eq!(startup.warp, None);
+        assert_eq!(startup.skill, None);
+        assert!(startup.render.is_none());
+
+        let startup = parse_args(args("-iwad doom2.wad -file a.wad -warp e1m3 -skill 4 -file b.wad")).unwrap();
+        assert_eq!(startup.iwad_path, "doom2.wad");
+        assert_eq!(startup.pwad_paths, ["a.wad", "b.wad"]);
+        assert_eq!(startup.warp.as_deref(), Some("E1M3"));
+        assert_eq!(startup.skill, Some(4));
+        assert!(startup.render.is_none());
+    }
+
+    #[test]
+    fn bad_command_line_args_are_rejected() {
+        assert!(parse_args(args("-skill 0")).is_err());
+        assert!(parse_args(args("-skill 6")).is_err());
+        assert!(parse_args(args("-skill hard")).is_err());
+        assert!(parse_args(args("-warp")).is_err());
+        assert!(parse_args(args("-nomonsters")).is_err());
+    }
+
+    #[test]
+    fn render_mode_saves_one_frame() {
+        let out_path = std::env::temp_dir().join("rustoom_render_test.ppm");
+        let out_path = out_path.to_str().unwrap();
+        let command_line = format!("--render e1m1 --at 1056,-3616 --angle 90 --out {out_path}");
+        let startup = parse_args(args(&command_line)).unwrap();
+        let render = startup.render.unwrap();
+        assert_eq!(render.map_name, "E1M1");
+
+        let cfg = GameConfig::new(WadData::load(&startup.iwad_path, true).unwrap(), SCR_WIDTH, SCR_HEIGHT);
+        let mut doom_game = DoomGame::new(cfg).unwrap();
+        render_frame(&mut doom_game, &render).unwrap();
+        assert_eq!(doom_game.camera().pos, Vertex { x: 1056, y: -3616 });
+        assert_eq!(doom_game.camera().angle.deg(), 90);
+
+        let image = std::fs::read(out_path).unwrap();
+        std::fs::remove_file(out_path).unwrap();
+        let header = format!("P6\n{SCR_WIDTH} {SCR_HEIGHT}\n255\n");
+        assert!(image.starts_with(header.as_bytes()));
+        assert_eq!(image.len(), header.len() + (SCR_WIDTH * SCR_HEIGHT * 3) as usize);
+        // something was painted
+        assert!(image[header.len()..].iter().any(|&b| b != 0));
+    }
+}
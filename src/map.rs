@@ -1,7 +1,11 @@
 //! Store maps from the WAD, just as collections of lump bytes.
 //! Used as immutable storage, from which to build each level map when it becomes active.
 
-use crate::{map_items::*, things::Thing, utils::*};
+use crate::{
+    map_items::*,
+    things::{Thing, MAX_PLAYERS},
+    utils::*,
+};
 use bytes::Bytes;
 
 // Indexes for various MapData lumps
@@ -17,11 +21,16 @@ const IDX_REJECT: usize = 8;
 const IDX_BLOCKMAP: usize = 9;
 const LUMP_CNT: usize = 10;
 
+// BLOCKMAP cells are 128x128 map units
+const BLOCKMAP_CELL_SHIFT: i32 = 7;
+const BLOCKMAP_HEADER_SIZE: usize = 8;
+
 pub struct MapData {
     name: String,
     lumps: Box<[Bytes; LUMP_CNT]>,
     bound_min: Vertex,
     bound_max: Vertex,
+    is_hexen_format: bool,
 }
 
 impl MapData {
@@ -32,6 +41,7 @@ impl MapData {
             lumps,
             bound_min: Default::default(),
             bound_max: Default::default(),
+            is_hexen_format: false,
         }
     }
 
@@ -40,6 +50,12 @@ impl MapData {
         self.lumps.iter().all(|b| b.len() > 0)
     }
 
+    /// Hexen maps have an extra BEHAVIOR lump, plus different THINGS and LINEDEFS formats.
+    #[inline]
+    pub fn is_hexen_format(&self) -> bool {
+        self.is_hexen_format
+    }
+
     #[inline]
     pub fn name(&self) -> &str {
         &self.name
@@ -55,6 +71,10 @@ impl MapData {
         Vertex::from_lump(&self.lumps[IDX_VERTEXES], idx)
     }
 
+    pub fn vertices(&self) -> impl Iterator<Item = Vertex> + '_ {
+        (0..self.vertex_count()).map(|idx| self.vertex(idx))
+    }
+
     #[inline]
     pub fn thing_count(&self) -> usize {
         self.lumps[IDX_THINGS].len() / THING_SIZE
@@ -66,6 +86,30 @@ impl MapData {
         Thing::from(bytes)
     }
 
+    pub fn things(&self) -> impl Iterator<Item = Thing> + '_ {
+        (0..self.thing_count()).map(|idx| self.thing(idx))
+    }
+
+    /// The starts of players 1 to 4 (the single player start is the first one).
+    /// Like in vanilla DOOM, if a map has several starts for the same player, the last one is used
+    /// (the others would only spawn "voodoo dolls").
+    pub fn player_starts(&self) -> [Option<Thing>; MAX_PLAYERS] {
+        let mut starts = [None, None, None, None];
+        for thing in self.things() {
+            if let player_nr @ 1..=4 = thing.type_code() {
+                starts[player_nr as usize - 1] = Some(thing);
+            }
+        }
+        starts
+    }
+
+    /// The things to spawn in a single player game, on the given skill level (0 = all skill levels):
+    /// the multiplayer-only things and starts are left out.
+    pub fn single_player_things(&self, skill: u8) -> impl Iterator<Item = Thing> + '_ {
+        self.things()
+            .filter(move |th| th.is_on_skill_level(skill) && !th.is_multiplayer_start())
+    }
+
     #[inline]
     pub fn linedef_count(&self) -> usize {
         self.lumps[IDX_LINEDEFS].len() / LINEDEF_SIZE
@@ -76,16 +120,46 @@ impl MapData {
         LineDef::from_lump(&self.lumps[IDX_LINEDEFS], idx, &self.lumps[IDX_VERTEXES])
     }
 
+    pub fn linedefs(&self) -> impl Iterator<Item = LineDef> + '_ {
+        (0..self.linedef_count()).map(|idx| self.linedef(idx))
+    }
+
+    #[inline]
+    pub fn sidedef_count(&self) -> usize {
+        self.lumps[IDX_SIDEDEFS].len() / SIDEDEF_SIZE
+    }
+
     #[inline]
     pub fn sidedef(&self, idx: usize) -> SideDef {
         SideDef::from_lump(&self.lumps[IDX_SIDEDEFS], idx)
     }
 
+    /// Like `sidedef`, but bounds-safe (for indices read from the map lumps, which may be corrupt).
+    #[inline]
+    pub fn try_sidedef(&self, idx: usize) -> Option<SideDef> {
+        (idx < self.sidedef_count()).then(|| self.sidedef(idx))
+    }
+
+    #[inline]
+    pub fn sector_count(&self) -> usize {
+        self.lumps[IDX_SECTORS].len() / SECTOR_SIZE
+    }
+
     #[inline]
     pub fn sector(&self, idx: usize) -> Sector {
         Sector::from_lump(&self.lumps[IDX_SECTORS], idx)
     }
 
+    /// Like `sector`, but bounds-safe (for indices read from the map lumps, which may be corrupt).
+    #[inline]
+    pub fn try_sector(&self, idx: usize) -> Option<Sector> {
+        (idx < self.sector_count()).then(|| self.sector(idx))
+    }
+
+    pub fn sectors(&self) -> impl Iterator<Item = Sector> + '_ {
+        (0..self.sector_count()).map(|idx| self.sector(idx))
+    }
+
     #[inline]
     pub fn root_bsp_node_idx(&self) -> u16 {
         ((self.lumps[IDX_NODES].len() / NODE_SIZE) - 1) as u16
@@ -101,6 +175,11 @@ impl MapData {
         self.lumps[IDX_SEGS].len() / SEG_SIZE
     }
 
+    #[inline]
+    pub fn ssector_count(&self) -> usize {
+        self.lumps[IDX_SSECTORS].len() / SSECTOR_SIZE
+    }
+
     pub fn sub_sector(&self, idx: usize) -> Vec<Seg> {
         // from SSECTORS, extract the seg count and first seg index
         let bytes = checked_slice(&self.lumps[IDX_SSECTORS], idx, SSECTOR_SIZE);
@@ -136,15 +215,128 @@ impl MapData {
         self.bound_max.y
     }
 
+    /// The center of the map's bounding box.
+    #[inline]
+    pub fn center(&self) -> Vertex {
+        Vertex {
+            x: (self.bound_min.x + self.bound_max.x) / 2,
+            y: (self.bound_min.y + self.bound_max.y) / 2,
+        }
+    }
+
+    /// The (width, height) of the map's bounding box.
+    #[inline]
+    pub fn dimensions(&self) -> (i32, i32) {
+        (self.bound_max.x - self.bound_min.x, self.bound_max.y - self.bound_min.y)
+    }
+
     /// Use the REJECT table to check if there is line of sight between the player and the monster
     pub fn check_line_of_sight(&self, player_sect_idx: u16, monster_sect_idx: u16) -> bool {
-        let sector_count = self.lumps[IDX_SECTORS].len() / SECTOR_SIZE;
-        let pli = player_sect_idx as usize;
-        let moi = monster_sect_idx as usize;
-        let bit_idx = moi * sector_count + pli;
+        // the monster is the one looking (= the source)
+        !self.is_rejected(monster_sect_idx as usize, player_sect_idx as usize)
+    }
+
+    /// Expand the REJECT table into a matrix, for inspection: `matrix[source][target]` is true
+    /// if there is surely NO line of sight from the `source` sector to the `target` sector.
+    pub fn reject_matrix(&self) -> Vec<Vec<bool>> {
+        let sector_count = self.sector_count();
+        (0..sector_count)
+            .map(|source| {
+                (0..sector_count)
+                    .map(|target| self.is_rejected(source, target))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The REJECT table is a bit array with one row per source sector (the sector of the one looking)
+    /// and one column per target sector, with the bits in LSB-first order (like `P_CheckSight` reads it).
+    /// A set bit means "no line of sight". The lump may be truncated (or empty), which means "visible".
+    fn is_rejected(&self, source_sect_idx: usize, target_sect_idx: usize) -> bool {
+        let bit_idx = source_sect_idx * self.sector_count() + target_sect_idx;
         let byte_idx = bit_idx >> 3;
         let bit_mask = 1 << (bit_idx & 0x07);
-        (self.lumps[IDX_REJECT])[byte_idx] & bit_mask == 0
+        let reject = &self.lumps[IDX_REJECT];
+        byte_idx < reject.len() && (reject[byte_idx] & bit_mask) != 0
+    }
+
+    /// Collect the (unique) indexes of the linedefs from all the BLOCKMAP cells touched
+    /// by the rectangle with the given corners, in increasing order.
+    /// If the BLOCKMAP is unusable, all the linedefs are returned.
+    pub fn blockmap_lines(&self, v1: Vertex, v2: Vertex) -> Vec<u16> {
+        let blockmap = &self.lumps[IDX_BLOCKMAP];
+        if blockmap.len() < BLOCKMAP_HEADER_SIZE {
+            return (0..self.linedef_count() as u16).collect();
+        }
+        let origin_x = buf_to_i16(&blockmap[0..2]) as i32;
+        let origin_y = buf_to_i16(&blockmap[2..4]) as i32;
+        let columns = buf_to_u16(&blockmap[4..6]) as i32;
+        let rows = buf_to_u16(&blockmap[6..8]) as i32;
+        let cell_x1 = ((Ord::min(v1.x, v2.x) - origin_x) >> BLOCKMAP_CELL_SHIFT).max(0);
+        let cell_x2 = ((Ord::max(v1.x, v2.x) - origin_x) >> BLOCKMAP_CELL_SHIFT).min(columns - 1);
+        let cell_y1 = ((Ord::min(v1.y, v2.y) - origin_y) >> BLOCKMAP_CELL_SHIFT).max(0);
+        let cell_y2 = ((Ord::max(v1.y, v2.y) - origin_y) >> BLOCKMAP_CELL_SHIFT).min(rows - 1);
+
+        let mut lines = Vec::new();
+        for cy in cell_y1..=cell_y2 {
+            for cx in cell_x1..=cell_x2 {
+                let cell_idx = (cy * columns + cx) as usize;
+                let offs_pos = BLOCKMAP_HEADER_SIZE + cell_idx * 2;
+                if offs_pos + 2 > blockmap.len() {
+                    continue;
+                }
+                // the offset is in 16-bit words; each list ends with 0xFFFF
+                let mut pos = buf_to_u16(&blockmap[offs_pos..offs_pos + 2]) as usize * 2;
+                // the lists usually start with a (dummy) 0, but some node builders leave it out
+                if blockmap.get(pos..pos + 2) == Some(&[0, 0]) {
+                    pos += 2;
+                }
+                while pos + 2 <= blockmap.len() {
+                    let line_idx = buf_to_u16(&blockmap[pos..pos + 2]);
+                    if line_idx == 0xFFFF {
+                        break;
+                    }
+                    lines.push(line_idx);
+                    pos += 2;
+                }
+            }
+        }
+        lines.sort_unstable();
+        lines.dedup();
+        lines
+    }
+
+    /// Check the map for (non-fatal) problems, which broken PWADs sometimes have.
+    pub fn validation_warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for (idx, line) in self.linedefs().enumerate() {
+            if (line.flags & LINE_TWO_SIDED) != 0 && !line.has_both_sides() {
+                warnings.push(format!(
+                    "Map {}: linedef #{idx} is two-sided, but one of its sides is missing",
+                    self.name
+                ));
+            }
+        }
+        for (idx, sector) in self.sectors().enumerate() {
+            if sector.light_level > 255 {
+                warnings.push(format!(
+                    "Map {}: sector #{idx} has an out of range light level: {}",
+                    self.name, sector.light_level
+                ));
+            }
+        }
+        if self.player_starts()[0].is_none() {
+            warnings.push(format!("Map {}: there is no player 1 start", self.name));
+        }
+        let reject_size = (self.sector_count() * self.sector_count()).div_ceil(8);
+        if self.lumps[IDX_REJECT].len() < reject_size {
+            warnings.push(format!(
+                "Map {}: the REJECT lump is truncated ({} < {reject_size} bytes)",
+                self.name,
+                self.lumps[IDX_REJECT].len()
+            ));
+        }
+        warnings
     }
 
     pub fn add_lump(&mut self, lump: &str, bytes: &Bytes) -> bool {
@@ -159,6 +351,11 @@ impl MapData {
             "SECTORS" => IDX_SECTORS,
             "REJECT" => IDX_REJECT,
             "BLOCKMAP" => IDX_BLOCKMAP,
+            "BEHAVIOR" => {
+                // Hexen's extra map lump => still part of this map
+                self.is_hexen_format = true;
+                return true;
+            }
             _ => usize::MAX,
         };
         // check if it was a valid lump; if not => return false, to signal the end of the map lumps
@@ -179,8 +376,7 @@ impl MapData {
     fn compute_map_bounds(&mut self) {
         let mut bl = self.vertex(0);
         let mut tr = bl;
-        for i in 1..self.vertex_count() {
-            let v = self.vertex(i);
+        for v in self.vertices().skip(1) {
             bl.x = Ord::min(bl.x, v.x);
             bl.y = Ord::min(bl.y, v.y);
             tr.x = Ord::max(tr.x, v.x);
@@ -199,6 +395,133 @@ impl Clone for MapData {
             lumps,
             bound_min: self.bound_min,
             bound_max: self.bound_max,
+            is_hexen_format: self.is_hexen_format,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::WadData;
+
+    #[test]
+    fn blockmap_finds_every_line_in_its_cells() {
+        let wad_data = WadData::load("DOOM1.WAD", true).unwrap();
+        let map = wad_data.map(0);
+        for (idx, line) in map.linedefs().enumerate() {
+            let lines = map.blockmap_lines(line.v1, line.v2);
+            assert!(lines.contains(&(idx as u16)), "linedef {idx}");
+            assert!(lines.len() < map.linedef_count());
+        }
+    }
+
+    #[test]
+    fn iterators_yield_every_item() {
+        let wad_data = WadData::load("DOOM1.WAD", true).unwrap();
+        let map = wad_data.map(0);
+        assert_eq!(map.linedefs().count(), map.linedef_count());
+        assert_eq!(map.sectors().count(), map.sector_count());
+        assert_eq!(map.things().count(), map.thing_count());
+        assert_eq!(map.vertices().count(), map.vertex_count());
+        assert_eq!(
+            map.linedefs().last().unwrap().v1,
+            map.linedef(map.linedef_count() - 1).v1
+        );
+    }
+
+    #[test]
+    fn center_is_the_middle_of_the_bounding_box() {
+        let wad_data = WadData::load("DOOM1.WAD", true).unwrap();
+        let map = wad_data.map(0);
+        let min_x = map.vertices().map(|v| v.x).min().unwrap();
+        let max_x = map.vertices().map(|v| v.x).max().unwrap();
+        let min_y = map.vertices().map(|v| v.y).min().unwrap();
+        let max_y = map.vertices().map(|v| v.y).max().unwrap();
+        assert_eq!(map.dimensions(), (max_x - min_x, max_y - min_y));
+        let center = map.center();
+        assert_eq!((center.x, center.y), ((min_x + max_x) / 2, (min_y + max_y) / 2));
+    }
+
+    #[test]
+    fn reject_matrix_of_e1m1() {
+        let wad_data = WadData::load("DOOM1.WAD", true).unwrap();
+        let map = wad_data.map(0);
+        let matrix = map.reject_matrix();
+        assert_eq!(matrix.len(), map.sector_count());
+        for (sect_idx, row) in matrix.iter().enumerate() {
+            assert_eq!(row.len(), map.sector_count());
+            // every sector can see itself
+            assert!(!row[sect_idx]);
         }
+        // the very first bits of the lump: sector 0 can see sector 1, but not sector 5
+        assert!(!matrix[0][1]);
+        assert!(matrix[0][5]);
+        assert!(matrix[5][0]);
+        assert!(map.check_line_of_sight(1, 0));
+        assert!(!map.check_line_of_sight(5, 0));
+    }
+
+    #[test]
+    fn single_player_things_leave_out_the_multiplayer_ones() {
+        let wad_data = WadData::load("DOOM1.WAD", true).unwrap();
+        let map = wad_data.map(0);
+        let multiplayer_only = map.things().filter(|th| th.is_multiplayer_only()).count();
+        let multiplayer_starts = map.things().filter(|th| th.is_multiplayer_start()).count();
+        assert!(multiplayer_only > 0 && multiplayer_starts > 0);
+
+        let single_player: Vec<_> = map.single_player_things(0).collect();
+        assert!(single_player
+            .iter()
+            .all(|th| !th.is_multiplayer_only() && !th.is_multiplayer_start()));
+        assert!(single_player.iter().any(|th| th.type_code() == 1));
+        // (no multiplayer start is flagged as multiplayer-only)
+        assert_eq!(
+            single_player.len(),
+            map.thing_count() - multiplayer_only - multiplayer_starts
+        );
+    }
+
+    #[test]
+    fn out_of_range_sidedefs_and_sectors_are_none() {
+        let wad_data = WadData::load("DOOM1.WAD", true).unwrap();
+        let map = wad_data.map(0);
+        let (side_cnt, sect_cnt) = (map.sidedef_count(), map.sector_count());
+        assert!(map.try_sidedef(0).is_some() && map.try_sidedef(side_cnt - 1).is_some());
+        assert!(map.try_sidedef(side_cnt).is_none());
+        // (0xFFFF = "no sidedef", on one-sided lines)
+        assert!(map.try_sidedef(0xFFFF).is_none());
+        assert!(map.try_sector(0).is_some() && map.try_sector(sect_cnt - 1).is_some());
+        assert!(map.try_sector(sect_cnt).is_none());
+        assert!(map.try_sector(usize::MAX).is_none());
+    }
+
+    #[test]
+    fn player_starts_of_all_four_players() {
+        use crate::map_items::THING_SIZE;
+        use crate::wad::test_wads::*;
+
+        let wad_data = WadData::load("DOOM1.WAD", true).unwrap();
+        let starts = wad_data.map(0).player_starts();
+        for (idx, start) in starts.iter().enumerate() {
+            assert_eq!(start.as_ref().unwrap().type_code(), idx as u16 + 1);
+        }
+        let positions: Vec<_> = starts.iter().map(|st| st.as_ref().unwrap().pos).collect();
+        assert!((1..4).all(|i| !positions[..i].contains(&positions[i])));
+
+        // with a second player 1 start, the last one is used (like in vanilla)
+        let mut lumps = doom1_lumps();
+        let lump_idx = map_lump_idx(&lumps, "E1M1", "THINGS");
+        let things = &mut lumps[lump_idx].1;
+        let ofs = (0..things.len())
+            .step_by(THING_SIZE)
+            .find(|ofs| things[ofs + 6..ofs + 8] == [1, 0])
+            .unwrap();
+        let mut voodoo = things[ofs..ofs + THING_SIZE].to_vec();
+        voodoo[0..2].copy_from_slice(&(positions[0].x as i16 + 64).to_le_bytes());
+        things.extend_from_slice(&voodoo);
+        let wad_data = build_wad(&lumps).unwrap();
+        let starts = wad_data.map(0).player_starts();
+        assert_eq!(starts[0].as_ref().unwrap().pos.x, positions[0].x + 64);
+        assert_eq!(starts[1].as_ref().unwrap().pos, positions[1]);
     }
 }
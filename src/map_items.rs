@@ -1,6 +1,6 @@
 //! Structs for the various items found in a map.
 
-use crate::{angle::Angle, utils::*};
+use crate::{angle::Angle, trig, utils::*};
 use std::ops::{Add, Sub};
 
 // Lump item sizes
@@ -13,6 +13,22 @@ pub const SSECTOR_SIZE: usize = 4;
 pub const NODE_SIZE: usize = 28;
 pub const SECTOR_SIZE: usize = 26;
 
+// LineDef flags
+pub const LINE_BLOCKS: u16 = 0x0001;
+pub const LINE_BLOCKS_MONSTERS: u16 = 0x0002;
+pub const LINE_TWO_SIDED: u16 = 0x0004;
+#[allow(dead_code)] // only used by the (not yet wired) wall bands
+pub const LINE_UPPER_UNPEGGED: u16 = 0x0008;
+#[allow(dead_code)] // only used by the (not yet wired) wall bands
+pub const LINE_LOWER_UNPEGGED: u16 = 0x0010;
+pub const LINE_SECRET: u16 = 0x0020;
+pub const LINE_BLOCKS_SND: u16 = 0x0040;
+pub const LINE_NEVER_ON_AMAP: u16 = 0x0080;
+pub const LINE_ALWAYS_ON_AMAP: u16 = 0x0100;
+
+/// Sidedef index used by linedefs which do not have a sidedef on one of their sides.
+pub const NO_SIDEDEF: u16 = 0xFFFF;
+
 /// A Vertex is a point in the 2D top-view space of a level map.<br/>
 /// **Note:** the Y axis goes *upwards* (towards North), like in a normal xOy system,
 /// and not like on screen, where the Y axis goes downwards.
@@ -50,7 +66,7 @@ impl Vertex {
 
     #[inline]
     pub fn polar_translate(&self, dist: f64, angle: Angle) -> Self {
-        let (s, c) = angle.rad().sin_cos();
+        let (s, c) = trig::fine_sin_cos(angle);
         Self {
             x: self.x + ((dist * c) as i32),
             y: self.y + ((dist * s) as i32),
@@ -107,10 +123,63 @@ impl LineDef {
             left_side_idx: buf_to_u16(&bytes[12..14]),
         }
     }
+
+    #[inline]
+    pub fn has_both_sides(&self) -> bool {
+        self.right_side_idx != NO_SIDEDEF && self.left_side_idx != NO_SIDEDEF
+    }
+
+    /// Check if the line stops the sound from propagating (vanilla DOOM lets the sound
+    /// through one such line, but not through two).
+    // TODO use this for waking up the monsters, once there is sound propagation
+    #[inline]
+    pub fn blocks_sound(&self) -> bool {
+        (self.flags & LINE_BLOCKS_SND) != 0
+    }
+
+    /// Check if a point is on the front (right) side of the line, like `P_PointOnLineSide` does.
+    /// Points which are exactly on the line are considered to be on the back side.
+    #[inline]
+    pub fn is_on_front_side(&self, point: Vertex) -> bool {
+        let dir = self.v2 - self.v1;
+        let pvect = point - self.v1;
+        let cross_product = (dir.x as i64) * (pvect.y as i64) - (dir.y as i64) * (pvect.x as i64);
+        cross_product < 0
+    }
+
+    /// If the segment `from -> to` crosses this line, return where it crosses it,
+    /// as a fraction of the segment's length (0.0 = at `from`, 1.0 = at `to`).
+    pub fn intersection_fraction(&self, from: Vertex, to: Vertex) -> Option<f64> {
+        let (rx, ry) = ((to.x - from.x) as f64, (to.y - from.y) as f64);
+        let (sx, sy) = ((self.v2.x - self.v1.x) as f64, (self.v2.y - self.v1.y) as f64);
+        let denom = rx * sy - ry * sx;
+        if denom == 0.0 {
+            // parallel
+            return None;
+        }
+        let (qx, qy) = ((self.v1.x - from.x) as f64, (self.v1.y - from.y) as f64);
+        let t = (qx * sy - qy * sx) / denom;
+        let u = (qx * ry - qy * rx) / denom;
+        if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+            Some(t)
+        } else {
+            None
+        }
+    }
 }
 
 //----------------------------
 
+/// The texture key for the "-" texture name, which means "no texture".
+pub const NO_TEXTURE_KEY: u64 = b'-' as u64;
+
+/// Check if a texture key means "no texture" on purpose: the "-" marker, or an empty name
+/// (which some editors write instead) - as opposed to a texture which is missing from the WAD.
+#[inline]
+pub fn is_no_texture(key: u64) -> bool {
+    key == NO_TEXTURE_KEY || key == 0
+}
+
 pub struct SideDef {
     pub x_offset: i16,
     pub y_offset: i16,
@@ -132,6 +201,21 @@ impl SideDef {
             sector_idx: buf_to_u16(&bytes[28..30]),
         }
     }
+
+    #[inline]
+    pub fn has_upper(&self) -> bool {
+        !is_no_texture(self.upper_texture_key)
+    }
+
+    #[inline]
+    pub fn has_lower(&self) -> bool {
+        !is_no_texture(self.lower_texture_key)
+    }
+
+    #[inline]
+    pub fn has_middle(&self) -> bool {
+        !is_no_texture(self.middle_texture_key)
+    }
 }
 
 //----------------------------
@@ -163,9 +247,39 @@ impl Sector {
 
 //----------------------------
 
+/// An axis-aligned bounding box, in map coordinates (the min/max corners are included).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoundingBox {
+    pub min: Vertex,
+    pub max: Vertex,
+}
+
+impl BoundingBox {
+    /// Parse a bounding box, stored as: top, bottom, left, right.
+    fn from_tblr(tblr: &[i16]) -> Self {
+        Self {
+            min: Vertex {
+                x: tblr[2] as i32,
+                y: tblr[1] as i32,
+            },
+            max: Vertex {
+                x: tblr[3] as i32,
+                y: tblr[0] as i32,
+            },
+        }
+    }
+
+    #[inline]
+    pub fn intersects(&self, other: &BoundingBox) -> bool {
+        self.min.x <= other.max.x && other.min.x <= self.max.x && self.min.y <= other.max.y && other.min.y <= self.max.y
+    }
+}
+
 pub struct BspNode {
     vect_orig: Vertex,
     vect_dir: Vertex,
+    pub right_bbox: BoundingBox,
+    pub left_bbox: BoundingBox,
     right_child: u16,
     left_child: u16,
 }
@@ -183,14 +297,26 @@ impl BspNode {
                 x: vect[2] as i32,
                 y: vect[3] as i32,
             },
+            right_bbox: BoundingBox::from_tblr(&vect[4..8]),
+            left_bbox: BoundingBox::from_tblr(&vect[8..12]),
             right_child: buf_to_u16(&bytes[24..26]),
             left_child: buf_to_u16(&bytes[26..28]),
         }
     }
 
+    /// Both children, each with its bounding box: (right, left).
+    #[inline]
+    pub fn children_with_bboxes(&self) -> [(u16, BoundingBox); 2] {
+        [(self.right_child, self.right_bbox), (self.left_child, self.left_bbox)]
+    }
+
     /// Returns the indices of the children of this node, based on the position of a point:
     /// * if the point is on the *left* side => returns *(left_child_idx, right_child_idx)*
     /// * if the point is on the *right* side => returns *(right_child_idx, left_child_idx)*
+    ///
+    /// A point exactly on the partition line counts as being on the left side (like `R_PointOnSide`).
+    /// E.g. for a partition from (0,0) going north (dir = (0,1)), the point (5,3) gives
+    /// a cross product of 5*1 - 3*0 = 5 > 0 => it is on the right (east) side.
     #[inline]
     pub fn child_indices_based_on_point_pos(&self, point: Vertex) -> (u16, u16) {
         let pvect = point - self.vect_orig;
@@ -234,3 +360,43 @@ impl Seg {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A node from (0,0) going north (dir = (0,1)), with right child 1 and left child 2.
+    fn north_node() -> BspNode {
+        let mut bytes = [0; NODE_SIZE];
+        bytes[6..8].copy_from_slice(&1_i16.to_le_bytes());
+        bytes[24..26].copy_from_slice(&1_u16.to_le_bytes());
+        bytes[26..28].copy_from_slice(&2_u16.to_le_bytes());
+        BspNode::from_lump(&bytes, 0)
+    }
+
+    #[test]
+    fn child_indices_based_on_point_pos() {
+        let node = north_node();
+        // cross product: 5*1 - 3*0 = 5 > 0 => right (east) side
+        assert_eq!(node.child_indices_based_on_point_pos(Vertex { x: 5, y: 3 }), (1, 2));
+        assert_eq!(node.child_indices_based_on_point_pos(Vertex { x: -5, y: 3 }), (2, 1));
+        assert_eq!(node.child_indices_based_on_point_pos(Vertex { x: 1, y: -100 }), (1, 2));
+        // exactly on the partition line => left side
+        assert_eq!(node.child_indices_based_on_point_pos(Vertex { x: 0, y: 7 }), (2, 1));
+    }
+
+    #[test]
+    fn dash_texture_names_mean_no_texture() {
+        // upper: "-", lower: empty, middle: a real (but missing) texture
+        let mut bytes = [0; SIDEDEF_SIZE];
+        bytes[4] = b'-';
+        bytes[20..28].copy_from_slice(b"NOSUCHTX");
+        bytes[28..30].copy_from_slice(&3_u16.to_le_bytes());
+        let side = SideDef::from_lump(&bytes, 0);
+        assert_eq!(side.upper_texture_key, NO_TEXTURE_KEY);
+        assert!(!side.has_upper());
+        assert!(!side.has_lower());
+        assert!(side.has_middle());
+        assert_eq!(side.sector_idx, 3);
+    }
+}
@@ -0,0 +1,164 @@
+//! Human-friendly level titles (and the level order), for the maps of the original games.
+//! See [Doom Wiki](https://doomwiki.org/wiki/Doom_level_format) for the level names.
+
+use crate::specials::LevelExit;
+
+const DOOM_TITLES: [[&str; 9]; 4] = [
+    [
+        "Hangar",
+        "Nuclear Plant",
+        "Toxin Refinery",
+        "Command Control",
+        "Phobos Lab",
+        "Central Processing",
+        "Computer Station",
+        "Phobos Anomaly",
+        "Military Base",
+    ],
+    [
+        "Deimos Anomaly",
+        "Containment Area",
+        "Refinery",
+        "Deimos Lab",
+        "Command Center",
+        "Halls of the Damned",
+        "Spawning Vats",
+        "Tower of Babel",
+        "Fortress of Mystery",
+    ],
+    [
+        "Hell Keep",
+        "Slough of Despair",
+        "Pandemonium",
+        "House of Pain",
+        "Unholy Cathedral",
+        "Mt. Erebus",
+        "Limbo",
+        "Dis",
+        "Warrens",
+    ],
+    [
+        "Hell Beneath",
+        "Perfect Hatred",
+        "Sever the Wicked",
+        "Unruly Evil",
+        "They Will Repent",
+        "Against Thee Wickedly",
+        "And Hell Followed",
+        "Unto the Cruel",
+        "Fear",
+    ],
+];
+
+const DOOM2_TITLES: [&str; 32] = [
+    "Entryway",
+    "Underhalls",
+    "The Gantlet",
+    "The Focus",
+    "The Waste Tunnels",
+    "The Crusher",
+    "Dead Simple",
+    "Tricks and Traps",
+    "The Pit",
+    "Refueling Base",
+    "'O' of Destruction!",
+    "The Factory",
+    "Downtown",
+    "The Inmost Dens",
+    "Industrial Zone",
+    "Suburbs",
+    "Tenements",
+    "The Courtyard",
+    "The Citadel",
+    "Gotcha!",
+    "Nirvana",
+    "The Catacombs",
+    "Barrels o' Fun",
+    "The Chasm",
+    "Bloodfalls",
+    "The Abandoned Mines",
+    "Monster Condo",
+    "The Spirit World",
+    "The Living End",
+    "Icon of Sin",
+    "Wolfenstein",
+    "Grosse",
+];
+
+/// Get the human-friendly title of a map, e.g. "E1M1: Hangar" or "MAP01: Entryway".
+/// For maps which are not part of the original games, the map name is returned as is.
+pub fn level_title(map_name: &str) -> String {
+    match builtin_title(map_name) {
+        Some(title) => format!("{map_name}: {title}"),
+        None => map_name.to_string(),
+    }
+}
+
+/// The map which follows a map of the original games, like in vanilla DOOM (including the secret levels).
+/// Returns None for the last map of an episode (or of the game), and for maps which are not
+/// part of the original games.
+pub fn next_builtin_map(map_name: &str, exit: LevelExit) -> Option<String> {
+    let b = map_name.as_bytes();
+    if b.len() == 4 && b[0] == b'E' && b[2] == b'M' {
+        // ExMy: the secret level is always M9, and it returns to the map after the one it is entered from
+        let episode = (b[1] as char).to_digit(10)?;
+        let map = (b[3] as char).to_digit(10)?;
+        let secret_from = match episode {
+            1 => 3,
+            2 => 5,
+            3 => 6,
+            4 => 2,
+            _ => return None,
+        };
+        let next = match (map, exit) {
+            (9, _) => secret_from + 1,
+            (m, LevelExit::Secret) if m == secret_from => 9,
+            (1..=7, _) => map + 1,
+            _ => return None,
+        };
+        Some(format!("E{episode}M{next}"))
+    } else if b.len() == 5 && map_name.starts_with("MAP") {
+        // MAPxx: MAP15 leads to the secret levels MAP31 and MAP32, which return to MAP16
+        let map = crate::utils::atoi(&map_name[3..])?;
+        let next = match (map, exit) {
+            (15, LevelExit::Secret) => 31,
+            (31, LevelExit::Secret) => 32,
+            (31 | 32, _) => 16,
+            (1..=29, _) => map + 1,
+            _ => return None,
+        };
+        Some(format!("MAP{next:02}"))
+    } else {
+        None
+    }
+}
+
+fn builtin_title(map_name: &str) -> Option<&'static str> {
+    let b = map_name.as_bytes();
+    if b.len() == 4 && b[0] == b'E' && b[2] == b'M' {
+        // ExMy
+        let episode = (b[1] as usize).checked_sub('1' as usize)?;
+        let map = (b[3] as usize).checked_sub('1' as usize)?;
+        DOOM_TITLES.get(episode)?.get(map).copied()
+    } else if b.len() == 5 && map_name.starts_with("MAP") {
+        // MAPxx
+        let map = crate::utils::atoi(&map_name[3..])? as usize;
+        DOOM2_TITLES.get(map.checked_sub(1)?).copied()
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_maps_get_their_canonical_titles() {
+        assert_eq!(level_title("E1M1"), "E1M1: Hangar");
+        assert_eq!(level_title("MAP01"), "MAP01: Entryway");
+        // unknown (e.g. PWAD) maps just keep their name
+        assert_eq!(level_title("E9M9"), "E9M9");
+        assert_eq!(level_title("MYMAP"), "MYMAP");
+    }
+}
@@ -0,0 +1,115 @@
+//! Level select menu: lists the maps (by title, in play order), to jump to any of them.
+
+use crate::font::{Font, LINE_HEIGHT};
+use crate::*;
+
+/// How much the game view behind the menu is darkened (0 = not at all, 255 = black).
+const BG_DARKEN_ALPHA: u8 = 176;
+const TITLE_COLOR: RGB = RED;
+const ENTRY_COLOR: RGB = LIGHT_GREY;
+const SELECTED_COLOR: RGB = YELLOW;
+
+pub struct LevelMenu {
+    open: bool,
+    /// The map names and their titles, in play order.
+    entries: Vec<(String, String)>,
+    selected: usize,
+}
+
+impl LevelMenu {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            entries: Vec::new(),
+            selected: 0,
+        }
+    }
+
+    #[inline]
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Open the menu, listing the maps of the WAD, with the current map selected.
+    pub fn open(&mut self, wad: &WadData, current_map: &str) {
+        self.entries = wad
+            .ordered_map_indices()
+            .into_iter()
+            .map(|idx| {
+                let name = wad.map_name(idx).to_string();
+                let title = wad.map_title(&name);
+                (name, title)
+            })
+            .collect();
+        self.selected = self
+            .entries
+            .iter()
+            .position(|(name, _)| name == current_map)
+            .unwrap_or(0);
+        self.open = true;
+    }
+
+    #[inline]
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    /// Move the selection up (negative) or down (positive), wrapping around.
+    pub fn move_selection(&mut self, delta: i32) {
+        if !self.entries.is_empty() {
+            let cnt = self.entries.len() as i32;
+            self.selected = (self.selected as i32 + delta).rem_euclid(cnt) as usize;
+        }
+    }
+
+    /// The name of the selected map.
+    pub fn selected_map(&self) -> Option<&str> {
+        self.entries.get(self.selected).map(|(name, _)| name.as_str())
+    }
+
+    pub fn paint(&self, font: &Font, painter: &mut dyn Painter) {
+        if !self.open {
+            return;
+        }
+        let width = painter.get_screen_width();
+        let height = painter.get_screen_height();
+        for y in 0..height {
+            for x in 0..width {
+                painter.draw_pixel_blended(x, y, BLACK, BG_DARKEN_ALPHA);
+            }
+        }
+
+        font.draw_text(20, 8, "Select a level:", TITLE_COLOR, painter);
+        // scroll, to keep the selected entry visible
+        let top = 8 + 2 * LINE_HEIGHT;
+        let visible_cnt = ((height - top) / LINE_HEIGHT).max(1) as usize;
+        let first = self.selected.saturating_sub(visible_cnt - 1);
+        let mut y = top;
+        for (idx, (_, title)) in self.entries.iter().enumerate().skip(first).take(visible_cnt) {
+            if idx == self.selected {
+                font.draw_text(8, y, ">", SELECTED_COLOR, painter);
+                font.draw_text(20, y, title, SELECTED_COLOR, painter);
+            } else {
+                font.draw_text(20, y, title, ENTRY_COLOR, painter);
+            }
+            y += LINE_HEIGHT;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selection_wraps_around() {
+        let wad_data = WadData::load("DOOM1.WAD", true).unwrap();
+        let mut menu = LevelMenu::new();
+        menu.open(&wad_data, "E1M2");
+        assert_eq!(menu.selected_map(), Some("E1M2"));
+        menu.move_selection(-2);
+        assert_eq!(menu.selected_map(), Some("E1M9"));
+        menu.move_selection(10);
+        assert_eq!(menu.selected_map(), Some("E1M1"));
+    }
+}
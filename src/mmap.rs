@@ -0,0 +1,83 @@
+//! Read-only memory-mapped files, wrapped as `Bytes` (only on Unix-like systems, for now).
+
+use bytes::Bytes;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+
+/// Memory-map a whole file, read-only. The mapping lives as long as the returned `Bytes`
+/// (or any slice of it) is alive.
+pub fn map_file(path: &str) -> io::Result<Bytes> {
+    let file = File::open(path)?;
+    let len = file.metadata()?.len() as usize;
+    if len == 0 {
+        // (empty mappings are not allowed)
+        return Ok(Bytes::new());
+    }
+    // SAFETY: the mapping is read-only and private, and it is only unmapped when dropped.
+    // Like with any memory-mapped file, the file should not be truncated while mapped.
+    let ptr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ,
+            libc::MAP_PRIVATE,
+            file.as_raw_fd(),
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+    // (the file can be closed - the mapping stays valid)
+    Ok(Bytes::from_owner(MappedFile { ptr, len }))
+}
+
+struct MappedFile {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+// SAFETY: the mapped memory is never written to, so it can be shared between threads.
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+impl AsRef<[u8]> for MappedFile {
+    fn as_ref(&self) -> &[u8] {
+        // SAFETY: the mapping is valid (and readable) for `len` bytes, until dropped
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        // SAFETY: this is the mapping created in `map_file`, and nothing refers to it anymore
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WadData;
+
+    #[test]
+    fn mapped_file_has_the_same_bytes() {
+        let mapped = map_file("DOOM1.WAD").unwrap();
+        assert_eq!(mapped, std::fs::read("DOOM1.WAD").unwrap());
+    }
+
+    #[test]
+    fn mmap_and_ram_wads_have_identical_lumps() {
+        let in_ram = WadData::load("DOOM1.WAD", true).unwrap();
+        let mapped = WadData::load_mmap("DOOM1.WAD", true).unwrap();
+        assert!(in_ram.changed_lumps(&mapped).is_empty());
+        assert_eq!(in_ram.map_count(), mapped.map_count());
+        for name in ["PLAYPAL", "E1M1", "LINEDEFS", "DSPISTOL", "STBAR"] {
+            assert_eq!(in_ram.lump_size(name), mapped.lump_size(name), "{name}");
+            assert_eq!(in_ram.lump_hash(name), mapped.lump_hash(name), "{name}");
+        }
+    }
+}
@@ -12,6 +12,29 @@ impl RGB {
     pub fn from(r: u8, g: u8, b: u8) -> Self {
         RGB { r, g, b }
     }
+
+    /// Blend this color over another one: alpha = 0 means only the other one, 255 means only this one.
+    #[inline]
+    pub fn blend_over(&self, other: RGB, alpha: u8) -> Self {
+        let mix = |a: u8, b: u8| ((a as u32 * alpha as u32 + b as u32 * (255 - alpha as u32)) / 255) as u8;
+        RGB {
+            r: mix(self.r, other.r),
+            g: mix(self.g, other.g),
+            b: mix(self.b, other.b),
+        }
+    }
+
+    /// Linear interpolation between this color (t = 0) and another one (t = 1).
+    #[inline]
+    pub fn lerp(&self, other: RGB, t: f64) -> Self {
+        let t = t.clamp(0.0, 1.0);
+        let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+        RGB {
+            r: mix(self.r, other.r),
+            g: mix(self.g, other.g),
+            b: mix(self.b, other.b),
+        }
+    }
 }
 
 /// Painter interface, to be passed to client code so it can perform painting.
@@ -25,6 +48,28 @@ pub trait Painter {
     /// This is the only abstract method. The others are based on this one.
     fn draw_pixel(&mut self, x: i32, y: i32, color: RGB);
 
+    /// Read back a pixel (needed for blending), if the painter supports it.
+    fn read_pixel(&self, _x: i32, _y: i32) -> Option<RGB> {
+        None
+    }
+
+    /// Draw a translucent pixel (alpha = 0 is invisible, 255 is opaque).
+    /// If the painter cannot read back pixels, the pixel is drawn only if it is mostly opaque.
+    fn draw_pixel_blended(&mut self, x: i32, y: i32, color: RGB, alpha: u8) {
+        if alpha == 255 {
+            self.draw_pixel(x, y, color);
+        } else if let Some(background) = self.read_pixel(x, y) {
+            self.draw_pixel(x, y, color.blend_over(background, alpha));
+        } else if alpha >= 128 {
+            self.draw_pixel(x, y, color);
+        }
+    }
+
+    /// Fill the whole screen with one color.
+    fn clear(&mut self, color: RGB) {
+        self.fill_rect(0, 0, self.get_screen_width(), self.get_screen_height(), color);
+    }
+
     fn draw_rect(&mut self, x: i32, y: i32, w: i32, h: i32, color: RGB) {
         if w > 0 && h > 0 {
             let x2 = x + w - 1;
@@ -77,6 +122,64 @@ pub trait Painter {
         }
     }
 
+    /// Draw a line which is `width` pixels thick, as a filled rectangle around it
+    /// (a width of 1 is the same as `draw_line`).
+    fn draw_thick_line(&mut self, x1: i32, y1: i32, x2: i32, y2: i32, width: i32, color: RGB) {
+        if width <= 1 || (x1 == x2 && y1 == y2) {
+            self.draw_line(x1, y1, x2, y2, color);
+            return;
+        }
+        // offset both ends by half of the width, perpendicular to the line (through the pixel centers
+        // for odd widths, through the pixel corners for even ones), rounding the same way on both sides
+        let (dx, dy) = ((x2 - x1) as f64, (y2 - y1) as f64);
+        let len = (dx * dx + dy * dy).sqrt();
+        let half = (width as f64) / 2.0;
+        let (nx, ny) = (-dy / len * half, dx / len * half);
+        let center = if width % 2 == 1 { 0.5 } else { 0.0 };
+        let corner = |x: i32, y: i32, sign: f64| {
+            let cx = x + (center + sign * nx).round() as i32;
+            let cy = y + (center + sign * ny).round() as i32;
+            (cx, cy)
+        };
+        let (a, b) = (corner(x1, y1, 1.0), corner(x1, y1, -1.0));
+        let (c, d) = (corner(x2, y2, -1.0), corner(x2, y2, 1.0));
+        self.fill_triangle(a, b, c, color);
+        self.fill_triangle(a, c, d, color);
+    }
+
+    /// Draw an anti-aliased line (Xiaolin Wu's algorithm): each step paints the two pixels closest
+    /// to the ideal line, blended by how much the line covers them.
+    /// Without `read_pixel` support, this looks like a (slightly jagged) normal line.
+    fn draw_line_aa(&mut self, x1: i32, y1: i32, x2: i32, y2: i32, color: RGB) {
+        let steep = (y2 - y1).abs() > (x2 - x1).abs();
+        // work as if the line was "flat" (mostly horizontal) and going right
+        let (mut x1, mut y1, mut x2, mut y2) = if steep { (y1, x1, y2, x2) } else { (x1, y1, x2, y2) };
+        if x1 > x2 {
+            std::mem::swap(&mut x1, &mut x2);
+            std::mem::swap(&mut y1, &mut y2);
+        }
+        let gradient = if x1 == x2 {
+            0.0
+        } else {
+            ((y2 - y1) as f64) / ((x2 - x1) as f64)
+        };
+        let mut y = y1 as f64;
+        for x in x1..=x2 {
+            let base = y.floor();
+            let coverage = y - base;
+            let alpha_far = (coverage * 255.0).round() as u8;
+            let (px, py) = (x, base as i32);
+            if steep {
+                self.draw_pixel_blended(py, px, color, 255 - alpha_far);
+                self.draw_pixel_blended(py + 1, px, color, alpha_far);
+            } else {
+                self.draw_pixel_blended(px, py, color, 255 - alpha_far);
+                self.draw_pixel_blended(px, py + 1, color, alpha_far);
+            }
+            y += gradient;
+        }
+    }
+
     fn draw_horiz_line(&mut self, x1: i32, x2: i32, y: i32, color: RGB) {
         if x1 == x2 {
             self.draw_pixel(x1, y, color);
@@ -99,6 +202,39 @@ pub trait Painter {
         }
     }
 
+    fn fill_triangle(&mut self, p1: (i32, i32), p2: (i32, i32), p3: (i32, i32), color: RGB) {
+        self.fill_triangle_blended(p1, p2, p3, color, 255);
+    }
+
+    /// Fill a translucent triangle.
+    /// Only the pixels with their center inside the triangle are painted, with the right and bottom
+    /// edges excluded - so triangles which share an edge (e.g. a fan) do not paint any pixel twice.
+    fn fill_triangle_blended(&mut self, p1: (i32, i32), p2: (i32, i32), p3: (i32, i32), color: RGB, alpha: u8) {
+        let mut pts = [p1, p2, p3];
+        pts.sort_unstable_by_key(|p| p.1);
+        let [(x1, y1), (x2, y2), (x3, y3)] = pts.map(|(x, y)| (x as f64, y as f64));
+        // the X where an edge crosses the horizontal line at Y
+        let edge_x = |xa: f64, ya: f64, xb: f64, yb: f64, y: f64| xa + (xb - xa) * (y - ya) / (yb - ya);
+        for y in pts[0].1..pts[2].1 {
+            let yc = y as f64 + 0.5;
+            if yc < y1 || yc >= y3 {
+                continue;
+            }
+            let xa = edge_x(x1, y1, x3, y3, yc);
+            let xb = if yc < y2 {
+                edge_x(x1, y1, x2, y2, yc)
+            } else {
+                edge_x(x2, y2, x3, y3, yc)
+            };
+            let (xmin, xmax) = if xa < xb { (xa, xb) } else { (xb, xa) };
+            let xstart = (xmin - 0.5).ceil() as i32;
+            let xend = (xmax - 0.5).ceil() as i32;
+            for x in xstart..xend {
+                self.draw_pixel_blended(x, y, color, alpha);
+            }
+        }
+    }
+
     fn draw_circle(&mut self, x: i32, y: i32, r: i32, color: RGB) {
         self.draw_ellipse(x, y, r, r, color);
     }
@@ -160,3 +296,165 @@ pub trait Painter {
         }
     }
 }
+
+/// Painter which only paints inside a rectangle (the clip rect) of another painter.
+/// The coordinates are NOT translated - they are still relative to the whole screen.
+pub struct ClippedPainter<'a> {
+    inner: &'a mut dyn Painter,
+    x1: i32,
+    y1: i32,
+    x2: i32,
+    y2: i32,
+}
+
+impl<'a> ClippedPainter<'a> {
+    pub fn new(inner: &'a mut dyn Painter, x: i32, y: i32, w: i32, h: i32) -> Self {
+        ClippedPainter {
+            inner,
+            x1: x,
+            y1: y,
+            x2: x + w.max(0),
+            y2: y + h.max(0),
+        }
+    }
+
+    #[inline]
+    fn is_inside(&self, x: i32, y: i32) -> bool {
+        x >= self.x1 && x < self.x2 && y >= self.y1 && y < self.y2
+    }
+}
+
+impl Painter for ClippedPainter<'_> {
+    fn get_screen_width(&self) -> i32 {
+        self.inner.get_screen_width()
+    }
+
+    fn get_screen_height(&self) -> i32 {
+        self.inner.get_screen_height()
+    }
+
+    fn draw_pixel(&mut self, x: i32, y: i32, color: RGB) {
+        if self.is_inside(x, y) {
+            self.inner.draw_pixel(x, y, color);
+        }
+    }
+
+    fn read_pixel(&self, x: i32, y: i32) -> Option<RGB> {
+        self.inner.read_pixel(x, y)
+    }
+
+    fn clear(&mut self, color: RGB) {
+        self.inner
+            .fill_rect(self.x1, self.y1, self.x2 - self.x1, self.y2 - self.y1, color);
+    }
+
+    fn fill_rect(&mut self, x: i32, y: i32, w: i32, h: i32, color: RGB) {
+        // intersect with the clip rect, so the (maybe faster) inner fill_rect can be used
+        let (x1, y1) = (x.max(self.x1), y.max(self.y1));
+        let (x2, y2) = ((x + w).min(self.x2), (y + h).min(self.y2));
+        self.inner.fill_rect(x1, y1, x2 - x1, y2 - y1, color);
+    }
+}
+
+/// Painter which paints everything translucent, over what the other painter already has.
+pub struct BlendedPainter<'a> {
+    inner: &'a mut dyn Painter,
+    alpha: u8,
+}
+
+impl<'a> BlendedPainter<'a> {
+    pub fn new(inner: &'a mut dyn Painter, alpha: u8) -> Self {
+        BlendedPainter { inner, alpha }
+    }
+}
+
+impl Painter for BlendedPainter<'_> {
+    fn get_screen_width(&self) -> i32 {
+        self.inner.get_screen_width()
+    }
+
+    fn get_screen_height(&self) -> i32 {
+        self.inner.get_screen_height()
+    }
+
+    fn draw_pixel(&mut self, x: i32, y: i32, color: RGB) {
+        self.inner.draw_pixel_blended(x, y, color, self.alpha);
+    }
+
+    fn read_pixel(&self, x: i32, y: i32) -> Option<RGB> {
+        self.inner.read_pixel(x, y)
+    }
+
+    fn draw_pixel_blended(&mut self, x: i32, y: i32, color: RGB, alpha: u8) {
+        let alpha = (alpha as u32 * self.alpha as u32 / 255) as u8;
+        self.inner.draw_pixel_blended(x, y, color, alpha);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::Layer;
+    use crate::{BLACK, WHITE};
+
+    fn painted_pixels(layer: &Layer) -> Vec<(i32, i32)> {
+        let (w, h) = (layer.get_screen_width(), layer.get_screen_height());
+        let pixels = (0..h).flat_map(|y| (0..w).map(move |x| (x, y)));
+        pixels.filter(|&(x, y)| layer.read_pixel(x, y).is_some()).collect()
+    }
+
+    #[test]
+    fn thick_line_has_the_given_width() {
+        for width in [2, 3, 4, 5] {
+            let mut layer = Layer::new(64, 64);
+            layer.draw_thick_line(10, 32, 50, 32, width, WHITE);
+            // each column in the middle of the line is `width` pixels tall
+            for x in 12..=48 {
+                let column: Vec<i32> = (0..64).filter(|&y| layer.read_pixel(x, y).is_some()).collect();
+                assert_eq!(column.len(), width as usize, "column {x} of width {width}");
+                assert!(column.contains(&32));
+            }
+        }
+    }
+
+    #[test]
+    fn thick_line_is_symmetric() {
+        for width in [3, 5] {
+            let mut layer = Layer::new(64, 64);
+            layer.draw_thick_line(10, 12, 50, 42, width, WHITE);
+            let pixels = painted_pixels(&layer);
+            assert!(!pixels.is_empty());
+
+            // same pixels when drawn from the other end
+            let mut reversed = Layer::new(64, 64);
+            reversed.draw_thick_line(50, 42, 10, 12, width, WHITE);
+            assert_eq!(painted_pixels(&reversed), pixels);
+
+            // about as many pixels on each side of the line
+            let side = |&(x, y): &(i32, i32)| (50 - 10) * (y - 12) - (42 - 12) * (x - 10);
+            let left = pixels.iter().filter(|p| side(p) > 0).count() as i32;
+            let right = pixels.iter().filter(|p| side(p) < 0).count() as i32;
+            assert!((left - right).abs() <= pixels.len() as i32 / 10, "{left} vs {right}");
+        }
+    }
+
+    #[test]
+    fn anti_aliased_line_blends_two_pixels_per_step() {
+        let mut layer = Layer::new(32, 32);
+        layer.fill_rect(0, 0, 32, 32, BLACK);
+        layer.draw_line_aa(0, 0, 30, 10, WHITE);
+        for x in 0..=30 {
+            let column: Vec<RGB> = (0..32)
+                .filter_map(|y| layer.read_pixel(x, y))
+                .filter(|c| *c != BLACK)
+                .collect();
+            // the two pixels closest to the line add up to the full color (give or take the rounding)
+            let total: i32 = column.iter().map(|c| c.r as i32).sum();
+            assert!(column.len() <= 2 && (total - 255).abs() <= 2, "column {x}");
+        }
+        // horizontal lines are exactly on the pixels
+        layer.draw_line_aa(0, 20, 30, 20, WHITE);
+        assert!((0..=30).all(|x| layer.read_pixel(x, 20) == Some(WHITE)));
+        assert!((0..=30).all(|x| layer.read_pixel(x, 21) == Some(BLACK)));
+    }
+}
@@ -2,14 +2,42 @@
 
 use crate::{pixmap::ColorMapper, RGB};
 use bytes::Bytes;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 
+// The standard PLAYPAL layout: 14 palettes, used for tinting the screen
+// - see: https://doomwiki.org/wiki/PLAYPAL
+const PALETTE_SIZE: usize = 768;
+const STANDARD_PALETTE_COUNT: usize = 14;
+const FIRST_DAMAGE_PALETTE: usize = 1;
+const DAMAGE_PALETTE_COUNT: usize = 8;
+const FIRST_BONUS_PALETTE: usize = 9;
+const BONUS_PALETTE_COUNT: usize = 4;
+const RADSUIT_PALETTE: usize = 13;
+
+// The standard COLORMAP layout: 32 light levels (bright to dark), invulnerability, all-black
+// - see: https://doomwiki.org/wiki/COLORMAP
+const COLORMAP_SIZE: usize = 256;
+const STANDARD_COLORMAP_COUNT: usize = 34;
+const LIGHT_COLORMAP_COUNT: usize = 32;
+const INVULN_COLORMAP: usize = 32;
+
+/// The gamma correction levels: 0 (off) to 4, like in vanilla DOOM.
+const GAMMA_LEVEL_COUNT: usize = 5;
+
+/// The palettes and colormaps from the WAD.
+/// The palette/colormap selection can be changed via a shared reference, since the palette is part
+/// of the (shared) game config - so it uses atomics, to also be usable from multiple threads.
 pub struct Palette {
     colormaps: Bytes,
     palletes: Bytes,
     cmap_cnt: usize,
-    cmap_selection: usize,
+    cmap_selection: AtomicUsize,
     pal_cnt: usize,
-    pal_selection: usize,
+    pal_selection: AtomicUsize,
+    fullbright: AtomicBool,
+    gamma_tables: [[u8; 256]; GAMMA_LEVEL_COUNT],
+    gamma_level: AtomicUsize,
+    generation: AtomicU64,
 }
 
 impl Palette {
@@ -18,22 +46,44 @@ impl Palette {
             colormaps: Bytes::new(),
             palletes: Bytes::new(),
             cmap_cnt: 0,
-            cmap_selection: 0,
+            cmap_selection: AtomicUsize::new(0),
             pal_cnt: 0,
-            pal_selection: 0,
+            pal_selection: AtomicUsize::new(0),
+            fullbright: AtomicBool::new(false),
+            gamma_tables: build_gamma_tables(),
+            gamma_level: AtomicUsize::new(0),
+            generation: AtomicU64::new(0),
         }
     }
 
-    pub fn init_palettes(&mut self, bytes: &Bytes) {
-        self.pal_cnt = bytes.len() / 768;
-        self.palletes = bytes.clone();
-        self.pal_selection = 0;
+    /// Returns a warning, if there are fewer palettes than usual.
+    pub fn init_palettes(&mut self, bytes: &Bytes) -> Option<String> {
+        // ignore any trailing partial palette, so that lookups never go past the end
+        self.pal_cnt = bytes.len() / PALETTE_SIZE;
+        self.palletes = bytes.slice(0..self.pal_cnt * PALETTE_SIZE);
+        self.pal_selection.store(0, Ordering::Relaxed);
+        self.bump_generation();
+        (self.pal_cnt < STANDARD_PALETTE_COUNT).then(|| {
+            format!(
+                "PLAYPAL has only {} palettes, instead of {STANDARD_PALETTE_COUNT} => screen tints will not work",
+                self.pal_cnt
+            )
+        })
     }
 
-    pub fn init_colormaps(&mut self, bytes: &Bytes) {
-        self.cmap_cnt = bytes.len() / 256;
-        self.colormaps = bytes.clone();
-        self.cmap_selection = 0;
+    /// Returns a warning, if there are fewer colormaps than usual.
+    pub fn init_colormaps(&mut self, bytes: &Bytes) -> Option<String> {
+        // ignore any trailing partial colormap, so that lookups never go past the end
+        self.cmap_cnt = bytes.len() / COLORMAP_SIZE;
+        self.colormaps = bytes.slice(0..self.cmap_cnt * COLORMAP_SIZE);
+        self.cmap_selection.store(0, Ordering::Relaxed);
+        self.bump_generation();
+        (self.cmap_cnt < STANDARD_COLORMAP_COUNT).then(|| {
+            format!(
+                "COLORMAP has only {} colormaps, instead of {STANDARD_COLORMAP_COUNT} => lighting may look wrong",
+                self.cmap_cnt
+            )
+        })
     }
 
     #[inline]
@@ -43,48 +93,495 @@ impl Palette {
 
     #[inline]
     pub fn get_main_palette(&self) -> Bytes {
-        if self.palletes.len() >= 768 {
-            self.palletes.slice(0..768)
+        if self.palletes.len() >= PALETTE_SIZE {
+            self.palletes.slice(0..PALETTE_SIZE)
         } else {
             Bytes::new()
         }
     }
 
-    pub fn select_palette(&mut self, pal: usize) -> Result<(), String> {
+    /// Find the index of the main palette color which is closest to the given color.
+    /// (uses the squared euclidean distance in RGB space, like most WAD tools do)
+    pub fn nearest_index(&self, color: RGB) -> u8 {
+        let main_pal = self.get_main_palette();
+        let mut best_idx = 0;
+        let mut best_dist = u32::MAX;
+        for (idx, rgb) in main_pal.chunks_exact(3).enumerate() {
+            let dr = (rgb[0] as i32) - (color.r as i32);
+            let dg = (rgb[1] as i32) - (color.g as i32);
+            let db = (rgb[2] as i32) - (color.b as i32);
+            let dist = (dr * dr + dg * dg + db * db) as u32;
+            if dist < best_dist {
+                best_idx = idx;
+                best_dist = dist;
+                if dist == 0 {
+                    break;
+                }
+            }
+        }
+        best_idx as u8
+    }
+
+    #[inline]
+    pub fn palette_count(&self) -> usize {
+        self.pal_cnt
+    }
+
+    /// The index of the red palette used when the player takes damage.
+    /// Intensity goes from 1 (faintest) to 8 (strongest); 0 means no tint (the main palette).
+    #[inline]
+    pub fn damage_palette(&self, intensity: usize) -> usize {
+        self.tint_palette(FIRST_DAMAGE_PALETTE, DAMAGE_PALETTE_COUNT, intensity)
+    }
+
+    /// The index of the yellow palette used when the player picks up an item.
+    /// Intensity goes from 1 (faintest) to 4 (strongest); 0 means no tint (the main palette).
+    #[inline]
+    pub fn bonus_palette(&self, intensity: usize) -> usize {
+        self.tint_palette(FIRST_BONUS_PALETTE, BONUS_PALETTE_COUNT, intensity)
+    }
+
+    /// The index of the green palette used while wearing the radiation suit.
+    #[inline]
+    pub fn radsuit_palette(&self) -> usize {
+        self.tint_palette(RADSUIT_PALETTE, 1, 1)
+    }
+
+    #[inline]
+    pub fn colormap_count(&self) -> usize {
+        self.cmap_cnt
+    }
+
+    /// A colormap index which is always valid, even for WADs with fewer colormaps than usual:
+    /// the requested index is clamped to the available colormaps (0 if there are none).
+    #[inline]
+    pub fn safe_colormap(&self, requested: usize) -> usize {
+        requested.min(self.cmap_cnt.saturating_sub(1))
+    }
+
+    /// The index of the colormap for a light level, from 0 (brightest) to 31 (darkest).
+    /// Larger levels are clamped to the darkest available light colormap.
+    #[inline]
+    pub fn light_colormap(&self, level: usize) -> usize {
+        self.safe_colormap(level.min(LIGHT_COLORMAP_COUNT - 1))
+    }
+
+    /// The index of the (inverted grayscale) colormap used while invulnerable.
+    /// Falls back to the full-bright colormap if the WAD does not have it.
+    #[inline]
+    pub fn invuln_colormap(&self) -> usize {
+        if INVULN_COLORMAP < self.cmap_cnt {
+            INVULN_COLORMAP
+        } else {
+            self.fullbright_colormap()
+        }
+    }
+
+    /// The index of the colormap with no light diminishing.
+    #[inline]
+    pub fn fullbright_colormap(&self) -> usize {
+        self.safe_colormap(0)
+    }
+
+    /// In full-bright mode (e.g. light amplification visor), the selected colormap is ignored
+    /// and everything is painted using the full-bright colormap.
+    #[inline]
+    pub fn set_fullbright(&self, fullbright: bool) {
+        self.fullbright.store(fullbright, Ordering::Relaxed);
+        self.bump_generation();
+    }
+
+    #[inline]
+    pub fn is_fullbright(&self) -> bool {
+        self.fullbright.load(Ordering::Relaxed)
+    }
+
+    pub fn select_palette(&self, pal: usize) -> Result<(), String> {
         if pal >= self.pal_cnt {
             Err(format!("Invalid palette index: {pal} >= {}", self.pal_cnt))
         } else {
-            self.pal_selection = pal * 768;
+            self.pal_selection.store(pal * PALETTE_SIZE, Ordering::Relaxed);
+            self.bump_generation();
             Ok(())
         }
     }
 
-    pub fn select_colormap(&mut self, cmap: usize) -> Result<(), String> {
+    pub fn select_colormap(&self, cmap: usize) -> Result<(), String> {
         if cmap >= self.cmap_cnt {
             Err(format!("Invalid colormap index: {cmap} >= {}", self.cmap_cnt))
         } else {
-            self.cmap_selection = cmap * 256;
+            self.cmap_selection.store(cmap * COLORMAP_SIZE, Ordering::Relaxed);
+            self.bump_generation();
             Ok(())
         }
     }
+
+    /// Set the gamma correction level: 0 (off) to 4 (brightest). Larger levels are clamped.
+    /// Unlike the palette selection, this is a user setting, so it is kept from one level to the next.
+    pub fn set_gamma(&self, level: u8) {
+        let level = (level as usize).min(GAMMA_LEVEL_COUNT - 1);
+        if self.gamma_level.swap(level, Ordering::Relaxed) != level {
+            self.bump_generation();
+        }
+    }
+
+    #[inline]
+    pub fn gamma(&self) -> u8 {
+        self.gamma_level.load(Ordering::Relaxed) as u8
+    }
+
+    /// Go back to the main palette and colormap, with full-bright mode off
+    /// (so that tints and colormaps do not carry over from one level to the next).
+    pub fn reset_selection(&self) {
+        self.pal_selection.store(0, Ordering::Relaxed);
+        self.cmap_selection.store(0, Ordering::Relaxed);
+        self.fullbright.store(false, Ordering::Relaxed);
+        self.bump_generation();
+    }
+
+    /// A counter which changes whenever the colors produced by this palette may change
+    /// (e.g. another palette or colormap is selected), so that any per-color caches
+    /// can be rebuilt (only) when needed.
+    #[inline]
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    fn bump_generation(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    //-----------------
+
+    fn map_color(&self, color: u8, cmap_selection: usize) -> RGB {
+        self.try_map_color(color, cmap_selection).unwrap_or_else(|| {
+            // data is NOT SET (or malformed) !!
+            // => just grayscale it :/
+            RGB::from(color, color, color)
+        })
+    }
+
+    /// Bounds-checked color lookup, so that corrupt lumps cannot cause a panic.
+    fn try_map_color(&self, color: u8, cmap_selection: usize) -> Option<RGB> {
+        // get palette index from color map ...
+        let cmap_idx = cmap_selection + (color as usize);
+        let pal_entry = 3 * (*self.colormaps.get(cmap_idx)? as usize);
+        // and find out the palette location of r, g, b
+        let pal_idx = self.pal_selection.load(Ordering::Relaxed) + pal_entry;
+        let rgb = self.palletes.get(pal_idx..pal_idx + 3)?;
+        let gamma = &self.gamma_tables[self.gamma_level.load(Ordering::Relaxed)];
+        Some(RGB::from(
+            gamma[rgb[0] as usize],
+            gamma[rgb[1] as usize],
+            gamma[rgb[2] as usize],
+        ))
+    }
+
+    /// Pick a tint palette, falling back to the main palette for intensity 0
+    /// or if the WAD does not have that many palettes.
+    fn tint_palette(&self, first: usize, count: usize, intensity: usize) -> usize {
+        if intensity == 0 {
+            return 0;
+        }
+        let pal = first + intensity.min(count) - 1;
+        if pal < self.pal_cnt {
+            pal
+        } else {
+            0
+        }
+    }
+}
+
+/// The gamma correction tables: each level brightens the darker colors more (level 0 changes nothing).
+/// Vanilla DOOM uses hand-made tables - these are power curves, which look about the same.
+fn build_gamma_tables() -> [[u8; 256]; GAMMA_LEVEL_COUNT] {
+    let mut tables = [[0; 256]; GAMMA_LEVEL_COUNT];
+    for (level, table) in tables.iter_mut().enumerate() {
+        let exponent = 1.0 / (1.0 + 0.25 * level as f64);
+        for (i, entry) in table.iter_mut().enumerate() {
+            *entry = (255.0 * (i as f64 / 255.0).powf(exponent)).round() as u8;
+        }
+    }
+    tables
 }
 
 impl ColorMapper for Palette {
     fn byte2rgb(&self, color: u8) -> RGB {
-        if self.cmap_cnt == 0 || self.pal_cnt == 0 {
-            // data is NOT SET !!
-            // => just grayscale it :/
-            RGB::from(color, color, color)
+        if self.is_fullbright() {
+            self.byte2rgb_fullbright(color)
         } else {
-            // get palette index from color map ...
-            let cmap_idx = self.cmap_selection + (color as usize);
-            let pal_entry = 3 * (self.colormaps[cmap_idx] as usize);
-            // and find out the palette location of r, g, b
-            let pal_idx = self.pal_selection + pal_entry;
-            let r = self.palletes[pal_idx];
-            let g = self.palletes[pal_idx + 1];
-            let b = self.palletes[pal_idx + 2];
-            RGB::from(r, g, b)
+            self.map_color(color, self.cmap_selection.load(Ordering::Relaxed))
         }
     }
+
+    fn byte2rgb_fullbright(&self, color: u8) -> RGB {
+        self.map_color(color, self.fullbright_colormap() * COLORMAP_SIZE)
+    }
+}
+
+//-----------------
+
+// The player colors: the green ramp of the palette is remapped to another color ramp
+// - see: https://doomwiki.org/wiki/Translation_table
+const GREEN_RAMP_START: u8 = 0x70;
+const RAMP_LENGTH: u8 = 16;
+const INDIGO_RAMP_START: u8 = 0x60;
+const BROWN_RAMP_START: u8 = 0x40;
+const RED_RAMP_START: u8 = 0x20;
+
+/// A translation table: remaps palette indices, to recolor some sprites (e.g. the other players).
+#[derive(Clone, PartialEq, Eq)]
+pub struct Translation([u8; 256]);
+
+impl Translation {
+    /// The identity translation (no color changes).
+    pub fn identity() -> Self {
+        let mut table = [0; 256];
+        for (i, entry) in table.iter_mut().enumerate() {
+            *entry = i as u8;
+        }
+        Self(table)
+    }
+
+    /// Remap a range of palette indices to another range (of the same length).
+    pub fn with_range(mut self, from_start: u8, to_start: u8, len: u8) -> Self {
+        for i in 0..len {
+            self.0[from_start.wrapping_add(i) as usize] = to_start.wrapping_add(i);
+        }
+        self
+    }
+
+    /// The standard player translations, like in vanilla DOOM: green to indigo, brown and red.
+    pub fn player_translations() -> [Self; 3] {
+        [INDIGO_RAMP_START, BROWN_RAMP_START, RED_RAMP_START]
+            .map(|ramp_start| Self::identity().with_range(GREEN_RAMP_START, ramp_start, RAMP_LENGTH))
+    }
+
+    #[inline]
+    pub fn translate(&self, color: u8) -> u8 {
+        self.0[color as usize]
+    }
+}
+
+/// Color mapper which applies a translation table, before using another mapper (e.g. the palette).
+pub struct TranslatedColorMapper<'a> {
+    pub translation: &'a Translation,
+    pub base: &'a dyn ColorMapper,
+}
+
+impl ColorMapper for TranslatedColorMapper<'_> {
+    fn byte2rgb(&self, color: u8) -> RGB {
+        self.base.byte2rgb(self.translation.translate(color))
+    }
+
+    fn byte2rgb_fullbright(&self, color: u8) -> RGB {
+        self.base.byte2rgb_fullbright(self.translation.translate(color))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn palette_with(pal_cnt: usize, cmap_cnt: usize) -> Palette {
+        let mut pal = Palette::new();
+        pal.init_palettes(&Bytes::from(vec![0; pal_cnt * PALETTE_SIZE]));
+        pal.init_colormaps(&Bytes::from(vec![0; cmap_cnt * COLORMAP_SIZE]));
+        pal
+    }
+
+    #[test]
+    fn tint_palettes_of_a_standard_playpal() {
+        let pal = palette_with(14, 34);
+        assert_eq!(pal.palette_count(), 14);
+        assert_eq!(pal.damage_palette(0), 0);
+        assert_eq!(pal.damage_palette(1), 1);
+        assert_eq!(pal.damage_palette(8), 8);
+        assert_eq!(pal.damage_palette(100), 8);
+        assert_eq!(pal.bonus_palette(1), 9);
+        assert_eq!(pal.bonus_palette(4), 12);
+        assert_eq!(pal.radsuit_palette(), 13);
+    }
+
+    #[test]
+    fn gamma_brightens_the_dark_colors() {
+        let mut pal = Palette::new();
+        let mut playpal = vec![0; PALETTE_SIZE];
+        playpal[3..9].copy_from_slice(&[64, 128, 0, 255, 255, 255]);
+        pal.init_palettes(&Bytes::from(playpal));
+        pal.init_colormaps(&Bytes::from((0..=255).collect::<Vec<u8>>()));
+        let color = pal.byte2rgb(1);
+        assert!(color == RGB::from(64, 128, 0));
+
+        let generation = pal.generation();
+        pal.set_gamma(2);
+        assert!(pal.generation() > generation);
+        let brighter = pal.byte2rgb(1);
+        assert!(brighter.r > 64 && brighter.g > 128 && brighter.b == 0);
+        // white stays white
+        assert!(pal.byte2rgb(2) == RGB::from(255, 255, 255));
+        pal.set_gamma(100);
+        assert_eq!(pal.gamma(), 4);
+        pal.set_gamma(0);
+        assert!(pal.byte2rgb(1) == color);
+    }
+
+    #[test]
+    fn short_playpal_falls_back_to_the_main_palette() {
+        let mut pal = Palette::new();
+        assert!(pal.init_palettes(&Bytes::from(vec![0; 3 * PALETTE_SIZE])).is_some());
+        assert_eq!(pal.damage_palette(3), 0);
+        assert_eq!(pal.radsuit_palette(), 0);
+    }
+
+    #[test]
+    fn named_colormaps_of_a_standard_colormap_lump() {
+        let pal = palette_with(14, 34);
+        assert_eq!(pal.colormap_count(), 34);
+        assert_eq!(pal.invuln_colormap(), 32);
+        assert_eq!(pal.fullbright_colormap(), 0);
+        assert_eq!(pal.light_colormap(31), 31);
+        assert_eq!(pal.light_colormap(40), 31);
+    }
+
+    #[test]
+    fn invuln_colormap_falls_back_to_fullbright_when_missing() {
+        let pal = palette_with(14, 32);
+        assert_eq!(pal.invuln_colormap(), pal.fullbright_colormap());
+    }
+
+    #[test]
+    fn fullbright_ignores_the_selected_colormap() {
+        // palette: color 0 is white, everything else is black
+        let mut playpal = vec![0; PALETTE_SIZE];
+        playpal[0..3].copy_from_slice(&[255, 255, 255]);
+        // colormap 0 is the identity, the darker ones map everything to color 1
+        let mut colormaps: Vec<u8> = (0..=255).collect();
+        colormaps.resize(34 * COLORMAP_SIZE, 1);
+        let mut pal = Palette::new();
+        pal.init_palettes(&Bytes::from(playpal));
+        pal.init_colormaps(&Bytes::from(colormaps));
+
+        pal.select_colormap(20).unwrap();
+        assert!(pal.byte2rgb(0) == RGB::from(0, 0, 0));
+        assert!(pal.byte2rgb_fullbright(0) == RGB::from(255, 255, 255));
+        pal.set_fullbright(true);
+        assert!(pal.byte2rgb(0) == RGB::from(255, 255, 255));
+    }
+
+    #[test]
+    fn every_change_bumps_the_generation() {
+        let mut pal = Palette::new();
+        let mut last = pal.generation();
+        let mut check_bumped = |pal: &Palette| {
+            assert!(pal.generation() > last);
+            last = pal.generation();
+        };
+        pal.init_palettes(&Bytes::from(vec![0; 14 * PALETTE_SIZE]));
+        check_bumped(&pal);
+        pal.init_colormaps(&Bytes::from(vec![0; 34 * COLORMAP_SIZE]));
+        check_bumped(&pal);
+        pal.select_palette(3).unwrap();
+        check_bumped(&pal);
+        pal.select_colormap(20).unwrap();
+        check_bumped(&pal);
+        pal.set_fullbright(true);
+        check_bumped(&pal);
+        pal.reset_selection();
+        check_bumped(&pal);
+
+        // failed changes do not change anything
+        let before = pal.generation();
+        assert!(pal.select_palette(14).is_err());
+        assert!(pal.select_colormap(34).is_err());
+        assert_eq!(pal.generation(), before);
+    }
+
+    #[test]
+    fn red_translation_turns_the_green_player_red() {
+        use crate::layer::Layer;
+        use crate::utils::hash_lump_name;
+        use crate::{Painter, WadData};
+
+        let wad_data = WadData::load("DOOM1.WAD", true).unwrap();
+        let pal = wad_data.palette();
+        let [_, _, red] = Translation::player_translations();
+        let red_mapper = TranslatedColorMapper {
+            translation: &red,
+            base: pal,
+        };
+        // the green ramp becomes the red ramp
+        for i in 0..RAMP_LENGTH {
+            let c = red_mapper.byte2rgb(GREEN_RAMP_START + i);
+            assert!(c == pal.byte2rgb(RED_RAMP_START + i));
+            assert!(c.r > c.g && c.r > c.b);
+        }
+        // the other colors are not changed
+        assert!(red_mapper.byte2rgb(GREEN_RAMP_START - 1) == pal.byte2rgb(GREEN_RAMP_START - 1));
+        assert!(red_mapper.byte2rgb(GREEN_RAMP_START + RAMP_LENGTH) == pal.byte2rgb(GREEN_RAMP_START + RAMP_LENGTH));
+
+        let sprite = wad_data.graphics().get_patch(hash_lump_name(b"PLAYA1")).unwrap();
+        let count_pixels = |mapper: &dyn ColorMapper, is_counted: fn(RGB) -> bool| {
+            // (the sprite's offsets place it around the painted point)
+            let mut layer = Layer::new(200, 200);
+            sprite.paint(100, 100, &mut layer, mapper);
+            let pixels = (0..200).flat_map(|y| (0..200).map(move |x| (x, y)));
+            pixels
+                .filter_map(|(x, y)| layer.read_pixel(x, y))
+                .filter(|c| is_counted(*c))
+                .count()
+        };
+        let is_green = |c: RGB| c.g > c.r.saturating_add(32) && c.g > c.b.saturating_add(32);
+        let is_red = |c: RGB| c.r > c.g.saturating_add(32) && c.r > c.b.saturating_add(32);
+        let green_cnt = count_pixels(pal, is_green);
+        assert!(green_cnt > 100, "{green_cnt}");
+        assert_eq!(count_pixels(&red_mapper, is_green), 0);
+        assert!(count_pixels(&red_mapper, is_red) >= count_pixels(pal, is_red) + green_cnt);
+    }
+
+    #[test]
+    fn truncated_lumps_fall_back_to_grayscale() {
+        let mut playpal = vec![0; PALETTE_SIZE];
+        playpal[3 * 7..3 * 7 + 3].copy_from_slice(&[10, 20, 30]);
+        let mut pal = Palette::new();
+        pal.init_palettes(&Bytes::from(playpal));
+        // not even one full colormap
+        assert!(pal.init_colormaps(&Bytes::from(vec![7; 100])).is_some());
+        assert_eq!(pal.colormap_count(), 0);
+        assert!(pal.try_map_color(5, 0).is_none());
+        for color in [0, 5, 99, 100, 255] {
+            assert!(pal.byte2rgb(color) == RGB::from(color, color, color));
+            assert!(pal.byte2rgb_fullbright(color) == RGB::from(color, color, color));
+        }
+
+        // one and a half colormaps => only the full one is used
+        assert!(pal
+            .init_colormaps(&Bytes::from(vec![7; COLORMAP_SIZE * 3 / 2]))
+            .is_some());
+        assert_eq!(pal.colormap_count(), 1);
+        assert!(pal.try_map_color(255, COLORMAP_SIZE).is_none());
+        assert!(pal.byte2rgb(255) == RGB::from(10, 20, 30));
+
+        // a truncated palette (not even one full palette) => grayscale too
+        pal.init_palettes(&Bytes::from(vec![0; 100]));
+        assert!(pal.byte2rgb(200) == RGB::from(200, 200, 200));
+    }
+
+    #[test]
+    fn safe_colormap_clamps_to_the_available_colormaps() {
+        let pal = palette_with(14, 34);
+        assert_eq!(pal.safe_colormap(5), 5);
+        assert_eq!(pal.safe_colormap(33), 33);
+        assert_eq!(pal.safe_colormap(34), 33);
+        assert_eq!(pal.safe_colormap(usize::MAX), 33);
+        // with fewer colormaps, the light levels are clamped too
+        let pal = palette_with(14, 20);
+        assert_eq!(pal.safe_colormap(25), 19);
+        assert_eq!(pal.light_colormap(31), 19);
+        // no colormaps at all => 0 (the lookups then fall back to grayscale)
+        let pal = palette_with(14, 0);
+        assert_eq!(pal.safe_colormap(25), 0);
+        assert_eq!(pal.fullbright_colormap(), 0);
+    }
 }
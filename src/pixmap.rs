@@ -1,13 +1,27 @@
 //! Pixel Maps (Patches, Flats, Fonts)
 
+use crate::palette::Palette;
 use crate::utils::*;
 use crate::*;
 use bytes::Bytes;
+use std::ops::Range;
+
+// the column caches must also be shareable between threads, with the `threaded` feature
+#[cfg(not(feature = "threaded"))]
+type LazyCache<T> = std::cell::OnceCell<T>;
+#[cfg(feature = "threaded")]
+type LazyCache<T> = std::sync::OnceLock<T>;
 
 /// Trait which provides color mapping at runtime (u8 -> RGB).
 pub trait ColorMapper {
     /// Map a byte value to a color.
     fn byte2rgb(&self, color: u8) -> RGB;
+
+    /// Map a byte value to a color, at full brightness, regardless of the current light level
+    /// (e.g. for muzzle flashes or light amplification).
+    fn byte2rgb_fullbright(&self, color: u8) -> RGB {
+        self.byte2rgb(color)
+    }
 }
 
 /// Pixel map structure.
@@ -18,6 +32,8 @@ pub struct PixMap {
     height: u16,
     kind: PixMapKind,
     data: Bytes,
+    /// Patches only: the column offsets, parsed when the patch is first painted.
+    column_offsets: LazyCache<Vec<usize>>,
 }
 
 impl PixMap {
@@ -31,29 +47,51 @@ impl PixMap {
             height: height as u16,
             kind: PixMapKind::PlaceHolder,
             data: Bytes::new(),
+            column_offsets: LazyCache::new(),
         }
     }
 
+    /// Build a pixmap from the bytes of a flat lump.
+    /// A flat with an invalid size becomes a 64x64 placeholder.
     pub fn from_flat(flat_bytes: &Bytes) -> Self {
-        let data = flat_bytes.clone();
-        let height = (data.len() >> 6) as u16;
-        Self {
+        Self::try_from_flat(flat_bytes).unwrap_or_else(|_| Self::new_placeholder(64, 64))
+    }
+
+    /// Build a pixmap from the bytes of a flat lump, checking its size.
+    /// Flats are always 64 pixels wide - usually 64x64, but some (animated) flats are 64x65.
+    pub fn try_from_flat(flat_bytes: &Bytes) -> Result<Self, String> {
+        let len = flat_bytes.len();
+        if len == 0 || (len & 63) != 0 {
+            return Err(format!("Invalid flat size: {len} is not a multiple of 64"));
+        }
+        Ok(Self {
             width: 64,
-            height,
+            height: (len >> 6) as u16,
             kind: PixMapKind::Flat,
-            data,
-        }
+            data: flat_bytes.clone(),
+            column_offsets: LazyCache::new(),
+        })
     }
 
+    /// Build a pixmap from the bytes of a patch lump.
+    /// The patch structure is validated here, once, so that painting can skip all the bounds checks.
+    /// A corrupt or truncated patch becomes a placeholder (of the same size, if the header is readable).
     pub fn from_patch(patch_bytes: &Bytes) -> Self {
+        if patch_bytes.len() < 8 {
+            return Self::new_empty();
+        }
         let data = patch_bytes.clone();
         let width = buf_to_u16(&data[0..=1]);
         let height = buf_to_u16(&data[2..=3]);
+        if !is_valid_patch(&data, width as usize) {
+            return Self::new_placeholder(width as usize, height as usize);
+        }
         Self {
             width,
             height,
             kind: PixMapKind::Patch,
             data,
+            column_offsets: LazyCache::new(),
         }
     }
 
@@ -88,6 +126,55 @@ impl PixMap {
         }
     }
 
+    /// The screen rectangle `(x, y, width, height)` for painting this patch as a sprite (billboard),
+    /// at the given scale, for a thing which is projected at `screen_x` with its feet at `floor_y`.
+    /// A sprite's offsets point to its "hot spot": the left offset is from the left edge to the
+    /// sprite's center and the top offset is from the top edge down to its feet, which sit on the floor.
+    pub fn sprite_screen_rect(&self, screen_x: i32, floor_y: i32, scale: f64) -> (i32, i32, i32, i32) {
+        let left = -self.x_offset() as f64 * scale;
+        let top = -self.y_offset() as f64 * scale;
+        let width = (self.width as f64 * scale).round() as i32;
+        let height = (self.height as f64 * scale).round() as i32;
+        (
+            screen_x - left.round() as i32,
+            floor_y - top.round() as i32,
+            width,
+            height,
+        )
+    }
+
+    /// Paint this patch as a sprite, scaled (with nearest-neighbor sampling) into its screen rectangle
+    /// (see `sprite_screen_rect`), so that its feet are on `floor_y` and it is centered on `screen_x`.
+    pub fn paint_sprite(
+        &self,
+        screen_x: i32,
+        floor_y: i32,
+        scale: f64,
+        painter: &mut dyn Painter,
+        mapper: &dyn ColorMapper,
+    ) {
+        if self.width == 0 || self.height == 0 || scale <= 0.0 {
+            return;
+        }
+        let (x, y, w, h) = self.sprite_screen_rect(screen_x, floor_y, scale);
+        if self.kind != PixMapKind::Patch {
+            painter.fill_rect(x, y, w, h, RGB::from(255, 0, 255));
+            return;
+        }
+        for sx in 0..w {
+            let col = (((sx as f64 + 0.5) / scale) as usize).min(self.width as usize - 1);
+            self.for_each_post(col, |dy, pixels| {
+                let len = pixels.len() as i32;
+                let top = ((dy as f64) * scale).round() as i32;
+                let bottom = (((dy + len) as f64) * scale).round() as i32;
+                for sy in top..bottom {
+                    let idx = (((sy as f64 + 0.5) / scale) as i32 - dy).clamp(0, len - 1);
+                    painter.draw_pixel(x + sx, y + sy, mapper.byte2rgb(pixels[idx as usize]));
+                }
+            });
+        }
+    }
+
     pub fn paint(&self, x: i32, y: i32, painter: &mut dyn Painter, mapper: &dyn ColorMapper) {
         if self.width > 0 && self.height > 0 {
             match self.kind {
@@ -98,6 +185,32 @@ impl PixMap {
         }
     }
 
+    /// Paint this pixmap magnified by an integer factor (each pixel becomes a `scale` x `scale` square),
+    /// with all its colors multiplied by a tint (e.g. for menus or the HUD).
+    /// The offsets are scaled too, so (x, y) is the (unscaled) origin, just like for `paint`.
+    pub fn paint_scaled_tinted(
+        &self,
+        x: i32,
+        y: i32,
+        scale: i32,
+        tint: RGB,
+        painter: &mut dyn Painter,
+        base_mapper: &dyn ColorMapper,
+    ) {
+        let scale = scale.max(1);
+        let mut scaled_painter = ScaledPainter {
+            inner: painter,
+            x,
+            y,
+            scale,
+        };
+        let tinted_mapper = TintColorMapper {
+            tint,
+            base: base_mapper,
+        };
+        self.paint(0, 0, &mut scaled_painter, &tinted_mapper);
+    }
+
     fn paint_pink(&self, x: i32, y: i32, painter: &mut dyn Painter) {
         for dy in 0..self.height as i32 {
             for dx in 0..self.width as i32 {
@@ -133,6 +246,29 @@ impl PixMap {
         );
     }
 
+    /// The column offsets of a patch, parsed only once (on first use).
+    fn column_offsets(&self) -> &[usize] {
+        self.column_offsets.get_or_init(|| {
+            (0..self.width as usize)
+                .map(|x| buf_to_u32(&self.data[8 + 4 * x..12 + 4 * x]) as usize)
+                .collect()
+        })
+    }
+
+    /// Call a function for each post of a patch column, with the post's Y start and its pixels.
+    fn for_each_post(&self, col: usize, mut f: impl FnMut(i32, &[u8])) {
+        let mut col_idx = self.column_offsets()[col];
+        loop {
+            let dy = self.data[col_idx] as i32;
+            if dy == 0xFF {
+                break;
+            }
+            let len = self.data[col_idx + 1] as usize;
+            f(dy, &self.data[(col_idx + 3)..(col_idx + 3 + len)]);
+            col_idx += 4 + len;
+        }
+    }
+
     fn paint_patch_customized(
         &self,
         x: i32,
@@ -145,11 +281,9 @@ impl PixMap {
         h: i32,
         clip: bool,
     ) {
-        let mut ofs_idx = 8;
-        for dx in 0..self.width as i32 {
-            // find the column index
-            let mut col_idx = buf_to_u32(&self.data[ofs_idx..ofs_idx + 4]) as usize;
-            ofs_idx += 4;
+        for (dx, col_ofs) in self.column_offsets().iter().enumerate() {
+            let dx = dx as i32;
+            let mut col_idx = *col_ofs;
             // optimization: skip column in clip mode, if outside view port
             let xx = dx + x_offs;
             if clip && (xx < 0 || xx >= w) {
@@ -178,11 +312,137 @@ impl PixMap {
 
 //----------------------
 
+/// Color mapper which multiplies the colors of another mapper by a tint (white = no change).
+pub struct TintColorMapper<'a> {
+    pub tint: RGB,
+    pub base: &'a dyn ColorMapper,
+}
+
+impl TintColorMapper<'_> {
+    #[inline]
+    fn apply(&self, color: RGB) -> RGB {
+        let mul = |c: u8, t: u8| ((c as u32 * t as u32) / 255) as u8;
+        RGB::from(
+            mul(color.r, self.tint.r),
+            mul(color.g, self.tint.g),
+            mul(color.b, self.tint.b),
+        )
+    }
+}
+
+impl ColorMapper for TintColorMapper<'_> {
+    fn byte2rgb(&self, color: u8) -> RGB {
+        self.apply(self.base.byte2rgb(color))
+    }
+
+    fn byte2rgb_fullbright(&self, color: u8) -> RGB {
+        self.apply(self.base.byte2rgb_fullbright(color))
+    }
+}
+
+/// Painter which draws each pixel as a `scale` x `scale` square, starting from (x, y).
+struct ScaledPainter<'a> {
+    inner: &'a mut dyn Painter,
+    x: i32,
+    y: i32,
+    scale: i32,
+}
+
+impl Painter for ScaledPainter<'_> {
+    fn get_screen_width(&self) -> i32 {
+        self.inner.get_screen_width() / self.scale
+    }
+
+    fn get_screen_height(&self) -> i32 {
+        self.inner.get_screen_height() / self.scale
+    }
+
+    fn draw_pixel(&mut self, x: i32, y: i32, color: RGB) {
+        let s = self.scale;
+        self.inner.fill_rect(self.x + x * s, self.y + y * s, s, s, color);
+    }
+}
+
+//----------------------
+
+/// Convert an external RGBA image (4 bytes per pixel, row by row) into the bytes of a DOOM patch,
+/// which can then be loaded via `PixMap::from_patch`.
+/// Each pixel is mapped to the nearest color of the main palette, while (mostly) transparent pixels
+/// (alpha < 128) are simply left out of the column posts.
+pub fn patch_from_rgba(width: u16, height: u16, rgba: &[u8], palette: &Palette) -> Result<Bytes, String> {
+    let w = width as usize;
+    let h = height as usize;
+    if w == 0 || h == 0 {
+        return Err(format!("Invalid image size: {width}x{height}"));
+    }
+    // post offsets are stored in a byte, and 0xFF marks the end of column
+    if h > 254 {
+        return Err(format!("Image too tall to be a patch: {height} > 254"));
+    }
+    if rgba.len() != w * h * 4 {
+        return Err(format!("RGBA buffer size mismatch: {} != {w}x{h}x4", rgba.len()));
+    }
+
+    // header: width, height, x and y offsets, followed by the column offsets
+    let mut bytes = Vec::with_capacity(8 + 4 * w + w * (h + 5));
+    bytes.extend_from_slice(&width.to_le_bytes());
+    bytes.extend_from_slice(&height.to_le_bytes());
+    bytes.extend_from_slice(&[0, 0, 0, 0]);
+    bytes.resize(8 + 4 * w, 0);
+
+    for x in 0..w {
+        let col_ofs = bytes.len() as u32;
+        bytes[8 + 4 * x..12 + 4 * x].copy_from_slice(&col_ofs.to_le_bytes());
+        let mut y = 0;
+        while y < h {
+            // skip transparent pixels
+            if rgba[(y * w + x) * 4 + 3] < 128 {
+                y += 1;
+                continue;
+            }
+            // collect a post of opaque pixels
+            let post_start = y;
+            let mut post = Vec::with_capacity(h - y);
+            while y < h && rgba[(y * w + x) * 4 + 3] >= 128 {
+                let idx = (y * w + x) * 4;
+                post.push(palette.nearest_index(RGB::from(rgba[idx], rgba[idx + 1], rgba[idx + 2])));
+                y += 1;
+            }
+            // post: y start, length, unused byte, the pixels, another unused byte
+            bytes.push(post_start as u8);
+            bytes.push(post.len() as u8);
+            bytes.push(0);
+            bytes.extend_from_slice(&post);
+            bytes.push(0);
+        }
+        bytes.push(0xFF);
+    }
+
+    Ok(Bytes::from(bytes))
+}
+
+//----------------------
+
+/// How to sample a texture column, for painting it scaled (see `Texture::paint_column`).
+pub struct ColumnSampling {
+    /// The texture column (wraps around the texture width).
+    pub u: i32,
+    /// The texture row (V) at the first painted screen row.
+    pub v_start: f64,
+    /// How many texture rows to advance, per screen row.
+    pub v_step: f64,
+    pub tiles_vertically: bool,
+}
+
 /// Texture = a collection of Patches.
 pub struct Texture {
     width: u16,
     height: u16,
     patches: Vec<TexturePatch>,
+    is_placeholder: bool,
+    /// The pixels of all the columns, composed from the patches (`height` pixels per column,
+    /// None = transparent), built when the texture is first painted.
+    columns: LazyCache<Vec<Option<u8>>>,
 }
 
 impl Texture {
@@ -191,9 +451,32 @@ impl Texture {
             width,
             height,
             patches: Vec::with_capacity(patch_cnt),
+            is_placeholder: false,
+            columns: LazyCache::new(),
+        }
+    }
+
+    /// A (pink) placeholder texture, to stand in for a missing one.
+    pub fn new_placeholder(width: u16, height: u16) -> Texture {
+        let tex_patch = TexturePatch {
+            pixmap: PixMap::new_placeholder(width as usize, height as usize),
+            x_orig: 0,
+            y_orig: 0,
+        };
+        Texture {
+            width,
+            height,
+            patches: vec![tex_patch],
+            is_placeholder: true,
+            columns: LazyCache::new(),
         }
     }
 
+    #[inline]
+    pub fn is_placeholder(&self) -> bool {
+        self.is_placeholder
+    }
+
     pub fn add_patch(&mut self, patch_bytes: &Bytes, x_orig: i16, y_orig: i16) {
         let tex_patch = TexturePatch {
             pixmap: PixMap::from_patch(patch_bytes),
@@ -201,6 +484,8 @@ impl Texture {
             y_orig,
         };
         self.patches.push(tex_patch);
+        // (the columns must be composed again, with the new patch)
+        self.columns = LazyCache::new();
     }
 
     #[inline]
@@ -213,9 +498,76 @@ impl Texture {
         self.height
     }
 
+    /// Paint one (scaled) column of this texture, on the given screen rows, sampled as specified.
+    /// The transparent pixels (gaps between posts) are skipped, so whatever is behind stays visible.
+    /// If the texture does not tile vertically (e.g. two-sided middle textures), it is drawn only once.
+    pub fn paint_column(
+        &self,
+        x: i32,
+        rows: Range<i32>,
+        sampling: &ColumnSampling,
+        painter: &mut dyn Painter,
+        mapper: &dyn ColorMapper,
+    ) {
+        if self.width == 0 || self.height == 0 {
+            return;
+        }
+        let column = self.column_pixels(sampling.u.rem_euclid(self.width as i32));
+        let height = self.height as i32;
+        let y_top = rows.start;
+        for y in rows {
+            let v = (sampling.v_start + ((y - y_top) as f64) * sampling.v_step).floor() as i32;
+            let v = if sampling.tiles_vertically {
+                v.rem_euclid(height)
+            } else {
+                v
+            };
+            if v < 0 || v >= height {
+                continue;
+            }
+            if self.is_placeholder {
+                painter.draw_pixel(x, y, RGB::from(255, 0, 255));
+            } else if let Some(pixcode) = column[v as usize] {
+                painter.draw_pixel(x, y, mapper.byte2rgb(pixcode));
+            }
+        }
+    }
+
+    /// The pixels of one column (None = transparent).
+    fn column_pixels(&self, u: i32) -> &[Option<u8>] {
+        let columns = self
+            .columns
+            .get_or_init(|| (0..self.width as i32).flat_map(|u| self.compose_column(u)).collect());
+        let height = self.height as usize;
+        &columns[(u as usize) * height..(u as usize + 1) * height]
+    }
+
+    /// Compose the pixels of one column, from all the patches (None = transparent).
+    fn compose_column(&self, u: i32) -> Vec<Option<u8>> {
+        let mut column = vec![None; self.height as usize];
+        for patch in self.patches.iter().filter(|p| p.pixmap.kind == PixMapKind::Patch) {
+            let px = u - patch.x_orig as i32;
+            if px < 0 || px >= patch.pixmap.width as i32 {
+                continue;
+            }
+            patch.pixmap.for_each_post(px as usize, |dy, pixels| {
+                for (i, pixcode) in pixels.iter().enumerate() {
+                    let y = patch.y_orig as i32 + dy + i as i32;
+                    if y >= 0 && y < self.height as i32 {
+                        column[y as usize] = Some(*pixcode);
+                    }
+                }
+            });
+        }
+        column
+    }
+
     pub fn paint(&self, x: i32, y: i32, painter: &mut dyn Painter, mapper: &dyn ColorMapper) {
-        if self.width > 0 && self.height > 0 {
-            for patch in &self.patches {
+        if self.is_placeholder {
+            self.patches[0].pixmap.paint(x, y, painter, mapper);
+        } else if self.width > 0 && self.height > 0 {
+            // skip the corrupt patches (which became placeholders)
+            for patch in self.patches.iter().filter(|p| p.pixmap.kind == PixMapKind::Patch) {
                 patch.pixmap.paint_patch_customized(
                     x,
                     y,
@@ -235,6 +587,33 @@ impl Texture {
 //----------------------
 // Internal stuff
 
+/// Check that all the column offsets and posts of a patch are within the lump's bounds.
+fn is_valid_patch(data: &[u8], width: usize) -> bool {
+    if data.len() < 8 + 4 * width {
+        return false;
+    }
+    for x in 0..width {
+        let mut col_idx = buf_to_u32(&data[8 + 4 * x..12 + 4 * x]) as usize;
+        loop {
+            match data.get(col_idx) {
+                None => return false,
+                Some(0xFF) => break,
+                Some(_) => {}
+            }
+            // post: y start, length, unused byte, the pixels, another unused byte
+            let len = match data.get(col_idx + 1) {
+                Some(len) => *len as usize,
+                None => return false,
+            };
+            if col_idx + 3 + len > data.len() {
+                return false;
+            }
+            col_idx += 4 + len;
+        }
+    }
+    true
+}
+
 /// Internal enum for the various kinds of pixel maps.
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum PixMapKind {
@@ -248,3 +627,190 @@ struct TexturePatch {
     x_orig: i16,
     y_orig: i16,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::Layer;
+
+    /// A palette whose first 16 colors are all distinct and exactly representable.
+    fn test_palette() -> Palette {
+        let mut playpal = vec![0; 768];
+        for i in 0..16 {
+            playpal[i * 3..i * 3 + 3].copy_from_slice(&[i as u8 * 16, 255 - i as u8 * 16, 64]);
+        }
+        let mut pal = Palette::new();
+        pal.init_palettes(&Bytes::from(playpal));
+        pal.init_colormaps(&Bytes::from((0..=255).collect::<Vec<u8>>()));
+        pal
+    }
+
+    #[test]
+    fn rgba_4x4_round_trips_through_a_patch() {
+        let pal = test_palette();
+        let patch = PixMap::from_patch(&test_patch_bytes(&pal));
+        assert_eq!((patch.width(), patch.height()), (4, 4));
+
+        let mut layer = Layer::new(4, 4);
+        patch.paint(0, 0, &mut layer, &pal);
+        for i in 0..16 {
+            let (x, y) = (i % 4, i / 4);
+            let expected = (i != 5).then(|| RGB::from(i as u8 * 16, 255 - i as u8 * 16, 64));
+            assert!(layer.read_pixel(x, y) == expected, "pixel {x},{y}");
+        }
+    }
+
+    #[test]
+    fn rgba_with_wrong_size_is_rejected() {
+        let pal = test_palette();
+        assert!(patch_from_rgba(4, 4, &[0; 60], &pal).is_err());
+        assert!(patch_from_rgba(0, 4, &[], &pal).is_err());
+    }
+
+    #[test]
+    fn sprite_feet_are_on_the_floor() {
+        let pal = test_palette();
+        // the sprite's hot spot: 2 pixels from the left edge, 4 pixels below the top edge
+        let mut bytes = test_patch_bytes(&pal).to_vec();
+        bytes[4..6].copy_from_slice(&2_i16.to_le_bytes());
+        bytes[6..8].copy_from_slice(&4_i16.to_le_bytes());
+        let sprite = PixMap::from_patch(&Bytes::from(bytes));
+        assert_eq!(sprite.sprite_screen_rect(50, 80, 2.0), (46, 72, 8, 8));
+        assert_eq!(sprite.sprite_screen_rect(50, 80, 0.5), (49, 78, 2, 2));
+
+        let mut layer = Layer::new(100, 100);
+        sprite.paint_sprite(50, 80, 2.0, &mut layer, &pal);
+        for y in 0..100 {
+            for x in 0..100 {
+                let expected = if (46..54).contains(&x) && (72..80).contains(&y) {
+                    let i = (x - 46) / 2 + (y - 72) / 2 * 4;
+                    (i != 5).then(|| RGB::from(i as u8 * 16, 255 - i as u8 * 16, 64))
+                } else {
+                    None
+                };
+                assert!(layer.read_pixel(x, y) == expected, "pixel {x},{y}");
+            }
+        }
+    }
+
+    /// A 4x4 patch built from RGBA data: each pixel uses its own palette color,
+    /// except for one transparent pixel.
+    fn test_patch_bytes(pal: &Palette) -> Bytes {
+        let mut rgba = Vec::new();
+        for i in 0..16 {
+            let alpha = if i == 5 { 0 } else { 255 };
+            rgba.extend_from_slice(&[i as u8 * 16, 255 - i as u8 * 16, 64, alpha]);
+        }
+        patch_from_rgba(4, 4, &rgba, pal).unwrap()
+    }
+
+    #[test]
+    fn truncated_patch_is_painted_as_placeholder() {
+        let pal = test_palette();
+        let bytes = test_patch_bytes(&pal);
+        for len in [0, 7, 8, 20, bytes.len() - 1] {
+            let patch = PixMap::from_patch(&bytes.slice(0..len));
+            let mut layer = Layer::new(8, 8);
+            patch.paint(2, 2, &mut layer, &pal);
+            if len >= 8 {
+                // (the header is still readable => a placeholder of the same size)
+                assert_eq!((patch.width(), patch.height()), (4, 4));
+                assert!(layer.read_pixel(5, 5) == Some(RGB::from(255, 0, 255)));
+            }
+        }
+    }
+
+    #[test]
+    fn patch_paints_the_same_with_cached_column_offsets() {
+        let wad_data = crate::WadData::load("DOOM1.WAD", true).unwrap();
+        let pal = wad_data.palette();
+        let (_, bytes) = wad_data.graphics().iter_patches_sorted().next().unwrap();
+        let patch = PixMap::from_patch(bytes);
+        let (w, h) = (patch.width() as i32, patch.height() as i32);
+        let paint = || {
+            let mut layer = Layer::new(w, h);
+            patch.paint(-patch.x_offset(), -patch.y_offset(), &mut layer, pal);
+            (0..w * h).map(|i| layer.read_pixel(i % w, i / w)).collect::<Vec<_>>()
+        };
+        assert!(patch.column_offsets.get().is_none());
+        let before = paint();
+        assert!(patch.column_offsets.get().is_some());
+        assert!(before == paint());
+        assert!(before.iter().any(Option::is_some));
+    }
+
+    #[test]
+    fn flat_size_must_be_a_multiple_of_64() {
+        assert!(PixMap::try_from_flat(&Bytes::from(vec![0; 4000])).is_err());
+        assert!(PixMap::try_from_flat(&Bytes::new()).is_err());
+        let flat = PixMap::try_from_flat(&Bytes::from(vec![0; 64 * 65])).unwrap();
+        assert_eq!((flat.width(), flat.height()), (64, 65));
+        // the lenient version falls back to a placeholder
+        let flat = PixMap::from_flat(&Bytes::from(vec![0; 100]));
+        assert!(flat.kind == PixMapKind::PlaceHolder);
+        assert_eq!((flat.width(), flat.height()), (64, 64));
+    }
+
+    #[test]
+    fn white_patch_tinted_red_is_red() {
+        let mut playpal = vec![0; 768];
+        playpal[3..6].copy_from_slice(&[255, 255, 255]);
+        let mut pal = Palette::new();
+        pal.init_palettes(&Bytes::from(playpal));
+        pal.init_colormaps(&Bytes::from((0..=255).collect::<Vec<u8>>()));
+        // a white 2x2 patch, with a transparent top-right pixel
+        const WHITE_PIXEL: [u8; 4] = [255, 255, 255, 255];
+        let rgba = [WHITE_PIXEL, [0; 4], WHITE_PIXEL, WHITE_PIXEL].concat();
+        let patch = PixMap::from_patch(&patch_from_rgba(2, 2, &rgba, &pal).unwrap());
+
+        let mut layer = Layer::new(20, 20);
+        patch.paint_scaled_tinted(10, 10, 3, RGB::from(255, 0, 0), &mut layer, &pal);
+        for y in 0..20 {
+            for x in 0..20 {
+                let inside = (10..16).contains(&x) && (10..16).contains(&y);
+                let transparent = x >= 13 && y < 13;
+                let expected = (inside && !transparent).then(|| RGB::from(255, 0, 0));
+                assert!(layer.read_pixel(x, y) == expected, "pixel {x},{y}");
+            }
+        }
+    }
+
+    #[test]
+    fn transparent_texture_pixels_keep_the_background() {
+        let pal = test_palette();
+        let mut texture = Texture::new(4, 4, 1);
+        texture.add_patch(&test_patch_bytes(&pal), 0, 0);
+        let background = RGB::from(1, 2, 3);
+        let mut layer = Layer::new(4, 8);
+        layer.fill_rect(0, 0, 4, 8, background);
+        // a two-sided middle texture: drawn only once, even if the column is taller
+        let paint = |layer: &mut Layer| {
+            for u in 0..4 {
+                let sampling = ColumnSampling {
+                    u,
+                    v_start: 0.0,
+                    v_step: 1.0,
+                    tiles_vertically: false,
+                };
+                texture.paint_column(u, 0..8, &sampling, layer, &pal);
+            }
+        };
+        paint(&mut layer);
+        assert!(texture.columns.get().is_some());
+        for y in 0..8 {
+            for x in 0..4 {
+                let i = x + y * 4;
+                let expected = if y >= 4 || i == 5 {
+                    background
+                } else {
+                    RGB::from(i as u8 * 16, 255 - i as u8 * 16, 64)
+                };
+                assert!(layer.read_pixel(x, y) == Some(expected), "pixel {x},{y}");
+            }
+        }
+        // painting again (from the cached columns) gives the same result
+        let before: Vec<_> = (0..32).map(|i| layer.read_pixel(i % 4, i / 4)).collect();
+        paint(&mut layer);
+        assert!((0..32).all(|i| layer.read_pixel(i % 4, i / 4) == before[i as usize]));
+    }
+}
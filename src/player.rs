@@ -0,0 +1,117 @@
+//! The player's state: health, armor, keys etc.
+
+// Key flags
+pub const KEY_BLUE_CARD: u8 = 1 << 0;
+pub const KEY_YELLOW_CARD: u8 = 1 << 1;
+pub const KEY_RED_CARD: u8 = 1 << 2;
+pub const KEY_BLUE_SKULL: u8 = 1 << 3;
+pub const KEY_YELLOW_SKULL: u8 = 1 << 4;
+pub const KEY_RED_SKULL: u8 = 1 << 5;
+const ALL_KEYS: u8 = KEY_BLUE_CARD | KEY_YELLOW_CARD | KEY_RED_CARD | KEY_BLUE_SKULL | KEY_YELLOW_SKULL | KEY_RED_SKULL;
+
+// Weapon flags (the fist and the pistol are always owned)
+pub const WEAPON_FIST: u8 = 1 << 0;
+pub const WEAPON_PISTOL: u8 = 1 << 1;
+pub const WEAPON_SHOTGUN: u8 = 1 << 2;
+pub const WEAPON_CHAINGUN: u8 = 1 << 3;
+pub const WEAPON_ROCKET_LAUNCHER: u8 = 1 << 4;
+pub const WEAPON_PLASMA_RIFLE: u8 = 1 << 5;
+pub const WEAPON_BFG: u8 = 1 << 6;
+pub const WEAPON_CHAINSAW: u8 = 1 << 7;
+const ALL_WEAPONS: u8 = WEAPON_FIST
+    | WEAPON_PISTOL
+    | WEAPON_SHOTGUN
+    | WEAPON_CHAINGUN
+    | WEAPON_ROCKET_LAUNCHER
+    | WEAPON_PLASMA_RIFLE
+    | WEAPON_BFG
+    | WEAPON_CHAINSAW;
+
+/// The maximum ammo, per type (without a backpack): bullets, shells, cells, rockets.
+pub const AMMO_MAX: [i32; 4] = [200, 50, 300, 50];
+
+/// Key colors, as required by locked doors.
+/// A door of a given color can be opened by the key card OR the skull key of that color.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyColor {
+    Blue,
+    Yellow,
+    Red,
+}
+
+impl KeyColor {
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyColor::Blue => "blue",
+            KeyColor::Yellow => "yellow",
+            KeyColor::Red => "red",
+        }
+    }
+
+    /// The key flags (card + skull) which match this color.
+    pub fn key_flags(&self) -> u8 {
+        match self {
+            KeyColor::Blue => KEY_BLUE_CARD | KEY_BLUE_SKULL,
+            KeyColor::Yellow => KEY_YELLOW_CARD | KEY_YELLOW_SKULL,
+            KeyColor::Red => KEY_RED_CARD | KEY_RED_SKULL,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlayerState {
+    pub health: i32,
+    pub armor: i32,
+    pub keys: u8,
+    pub weapons: u8,
+    /// Bullets, shells, cells, rockets.
+    pub ammo: [i32; 4],
+    /// God mode: no damage is taken.
+    // TODO use this, once there is damage
+    pub god_mode: bool,
+}
+
+impl PlayerState {
+    pub fn new() -> Self {
+        Self {
+            health: 100,
+            armor: 0,
+            keys: 0,
+            weapons: WEAPON_FIST | WEAPON_PISTOL,
+            ammo: [50, 0, 0, 0],
+            god_mode: false,
+        }
+    }
+
+    #[inline]
+    pub fn has_key(&self, color: KeyColor) -> bool {
+        (self.keys & color.key_flags()) != 0
+    }
+
+    pub fn give_all_keys(&mut self) {
+        self.keys = ALL_KEYS;
+    }
+
+    /// All the weapons, with full ammo and full armor (like the IDFA cheat).
+    pub fn give_weapons_and_ammo(&mut self) {
+        self.weapons = ALL_WEAPONS;
+        self.ammo = AMMO_MAX;
+        self.armor = 200;
+    }
+
+    /// Everything: full health, plus what `give_weapons_and_ammo` gives, plus all the keys
+    /// (like the IDKFA cheat, which however does not give health).
+    pub fn give_all(&mut self) {
+        self.health = 100;
+        self.give_weapons_and_ammo();
+        self.give_all_keys();
+    }
+
+    /// The state to carry over into the next level (keys are only valid in the level they were found in).
+    pub fn for_next_level(&self) -> Self {
+        Self {
+            keys: 0,
+            ..self.clone()
+        }
+    }
+}
@@ -8,6 +8,9 @@ use sdl2::pixels::PixelFormatEnum;
 
 use std::time::{Duration, Instant};
 
+/// By default, a single (very) long frame does not count for more than this (in seconds).
+const DEFAULT_MAX_ELAPSED_TIME: f64 = 0.1;
+
 /// Enum for if/how to slep during each game loop execution.
 #[derive(PartialEq, Eq)]
 pub enum SleepKind {
@@ -16,6 +19,17 @@ pub enum SleepKind {
     SLEEP(u32),
 }
 
+/// How the painted frame is scaled up, to the window size.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ScaleKind {
+    /// SDL stretches the frame to the window, with nearest-neighbor filtering (crisp pixels).
+    Nearest,
+    /// The frame is first upscaled 2x (each pixel becomes 2x2 pixels), then SDL stretches it to
+    /// the window with linear filtering. This reduces the shimmering on non-integer window sizes,
+    /// while keeping the pixels mostly crisp.
+    Upscale2x,
+}
+
 /// The configuration to be used for initializing SDL.
 pub struct SdlConfiguration {
     title: String,
@@ -23,6 +37,11 @@ pub struct SdlConfiguration {
     scr_height: i32,
     pixel_size: i32,
     sleep_kind: SleepKind,
+    fixed_timestep: Option<f64>,
+    max_elapsed_time: f64,
+    elapsed_smoothing: f64,
+    present_vsync: bool,
+    scale_kind: ScaleKind,
 }
 
 impl SdlConfiguration {
@@ -36,8 +55,49 @@ impl SdlConfiguration {
             scr_height,
             pixel_size,
             sleep_kind,
+            fixed_timestep: None,
+            max_elapsed_time: DEFAULT_MAX_ELAPSED_TIME,
+            elapsed_smoothing: 0.0,
+            present_vsync: false,
+            scale_kind: ScaleKind::Nearest,
         }
     }
+
+    /// Choose how the painted frame is scaled up, to the window size (default: `ScaleKind::Nearest`).
+    pub fn with_scale_kind(mut self, scale_kind: ScaleKind) -> Self {
+        self.scale_kind = scale_kind;
+        self
+    }
+
+    /// Synchronize presenting the screen with the display's refresh rate.
+    /// With vsync on, the `SleepKind` is ignored (waiting for vsync already avoids hogging the CPU).
+    pub fn with_vsync(mut self, present_vsync: bool) -> Self {
+        self.present_vsync = present_vsync;
+        self
+    }
+
+    /// Limit the elapsed time reported for one frame (in seconds), so that one hitch
+    /// (e.g. dragging the window) does not make everything jump a long way ahead.
+    pub fn with_max_elapsed_time(mut self, max_seconds: f64) -> Self {
+        assert!(max_seconds > 0.0);
+        self.max_elapsed_time = max_seconds;
+        self
+    }
+
+    /// Smooth out the elapsed times, exponentially: 0 = no smoothing, up to (almost) 1 = very smooth.
+    pub fn with_elapsed_smoothing(mut self, smoothing: f64) -> Self {
+        assert!((0.0..1.0).contains(&smoothing));
+        self.elapsed_smoothing = smoothing;
+        self
+    }
+
+    /// Update the state at a fixed rate (e.g. 35 updates per second, like vanilla DOOM),
+    /// independently of the painting rate. Painting is interpolated between the updates.
+    pub fn with_fixed_update_rate(mut self, updates_per_second: u32) -> Self {
+        assert!(updates_per_second > 0);
+        self.fixed_timestep = Some(1.0 / (updates_per_second as f64));
+        self
+    }
 }
 
 /// Trait to be implemented by clients of `run_sdl_loop`.
@@ -49,8 +109,21 @@ pub trait GraphicsLoop {
     /// Update the internal state.
     fn update_state(&mut self, elapsed_time: f64) -> bool;
 
+    /// With a fixed update rate: set how far (0..1) the painting is between the previous
+    /// and the current state update, so that painting can interpolate between them.
+    fn set_interpolation(&mut self, _alpha: f64) {}
+
     /// Paint the world, based on the updated internal state.
     fn paint(&self, painter: &mut dyn Painter);
+
+    /// Called about once per second, with the latest measured frame rate.
+    fn notify_fps(&mut self, _fps: u32) {}
+
+    /// Called after each update: return a new (logical) screen size, to switch to it.
+    /// The window keeps its size - the screen is just scaled differently.
+    fn take_requested_screen_size(&mut self) -> Option<(i32, i32)> {
+        None
+    }
 }
 
 /// Main function to run the continuous SDL loop
@@ -61,30 +134,68 @@ pub fn run_sdl_loop(cfg: &SdlConfiguration, gfx_loop: &mut dyn GraphicsLoop) ->
 
     let win_width = (cfg.scr_width * cfg.pixel_size) as u32;
     let win_height = (cfg.scr_height * cfg.pixel_size) as u32;
-    let scr_width = cfg.scr_width as u32;
-    let scr_height = cfg.scr_height as u32;
+    let mut scr_width = cfg.scr_width;
+    let mut scr_height = cfg.scr_height;
+
+    // the filtering used by SDL when stretching the texture (it must be set before creating the texture)
+    let (scale_quality, upscale) = match cfg.scale_kind {
+        ScaleKind::Nearest => ("0", 1),
+        ScaleKind::Upscale2x => ("1", 2),
+    };
+    sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", scale_quality);
 
     // create window
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
-    let window = video_subsystem
+    // prefer an OpenGL window, but fall back to a plain one (e.g. on systems without OpenGL)
+    let window = match video_subsystem
         .window(&cfg.title, win_width, win_height)
         .position_centered()
         .opengl()
         .build()
-        .map_err(|e| e.to_string())?;
-    let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+    {
+        Ok(window) => window,
+        Err(gl_err) => video_subsystem
+            .window(&cfg.title, win_width, win_height)
+            .position_centered()
+            .build()
+            .map_err(|e| format!("Failed to create the window: {e} (with OpenGL: {gl_err})"))?,
+    };
+    let canvas_builder = window.into_canvas();
+    let canvas_builder = if cfg.present_vsync {
+        canvas_builder.present_vsync()
+    } else {
+        canvas_builder
+    };
+    let mut canvas = canvas_builder.build().map_err(|e| e.to_string())?;
 
-    // create texture, to paint on
+    // create texture, to paint on (when upscaling, the frame is painted in a separate buffer)
     let texture_creator = canvas.texture_creator();
     let mut screen_buffer = texture_creator
-        .create_texture_streaming(PixelFormatEnum::RGB24, scr_width, scr_height)
+        .create_texture_streaming(
+            PixelFormatEnum::RGB24,
+            (scr_width * upscale) as u32,
+            (scr_height * upscale) as u32,
+        )
         .map_err(|e| e.to_string())?;
+    let mut frame_buffer = vec![
+        0_u8;
+        if upscale > 1 {
+            (scr_width * scr_height * 3) as usize
+        } else {
+            0
+        }
+    ];
 
-    let mut timer = FpsAndElapsedCounter::new();
+    let mut timer = FpsAndElapsedCounter::new(cfg.max_elapsed_time, cfg.elapsed_smoothing);
     let mut last_fps = 42;
+    let mut time_accumulator = 0.0;
     let mut event_pump = sdl_context.event_pump()?;
 
+    // make sure the typed text arrives as `TextInput` events (e.g. for a console),
+    // which are forwarded to `handle_event`, just like the key events
+    video_subsystem.text_input().start();
+
     // Main game loop
     'running: loop {
         // consume the event loop
@@ -105,6 +216,9 @@ pub fn run_sdl_loop(cfg: &SdlConfiguration, gfx_loop: &mut dyn GraphicsLoop) ->
 
         // compute time
         let elapsed_time = timer.update_and_get_ellapsed_time();
+        if let Some(fps) = timer.take_new_fps() {
+            gfx_loop.notify_fps(fps);
+        }
         if last_fps != timer.fps {
             last_fps = timer.fps;
             let title_with_fps = format!("{} - FPS: {}", cfg.title, last_fps);
@@ -115,29 +229,62 @@ pub fn run_sdl_loop(cfg: &SdlConfiguration, gfx_loop: &mut dyn GraphicsLoop) ->
         }
 
         // update the internal state
-        if !gfx_loop.update_state(elapsed_time) {
+        if !update_state(cfg, gfx_loop, elapsed_time, &mut time_accumulator) {
             break 'running;
         }
 
+        // switch the screen resolution, if requested
+        if let Some((new_width, new_height)) = gfx_loop.take_requested_screen_size() {
+            assert!(new_width > 0 && new_height > 0);
+            scr_width = new_width;
+            scr_height = new_height;
+            screen_buffer = texture_creator
+                .create_texture_streaming(
+                    PixelFormatEnum::RGB24,
+                    (scr_width * upscale) as u32,
+                    (scr_height * upscale) as u32,
+                )
+                .map_err(|e| e.to_string())?;
+            if upscale > 1 {
+                frame_buffer = vec![0_u8; (scr_width * scr_height * 3) as usize];
+            }
+        }
+
         // paint the screen, using a SDL2 streaming texture
         // - see: https://github.com/Rust-SDL2/rust-sdl2/blob/master/examples/renderer-texture.rs
         // - see: https://www.reddit.com/r/cpp_questions/comments/eqwsao/sdl_rendering_way_too_slow/
-        screen_buffer.with_lock(None, |buffer: &mut [u8], pitch: usize| {
-            // all painting must be done in this closure
+        if upscale > 1 {
             let mut painter = InternalTexturePainter {
-                buffer,
-                pitch,
-                scr_width: cfg.scr_width,
-                scr_height: cfg.scr_height,
+                buffer: &mut frame_buffer,
+                pitch: (scr_width * 3) as usize,
+                scr_width,
+                scr_height,
             };
             gfx_loop.paint(&mut painter);
-        })?;
+            screen_buffer.with_lock(None, |buffer: &mut [u8], pitch: usize| {
+                upscale_2x(&frame_buffer, scr_width as usize, scr_height as usize, buffer, pitch);
+            })?;
+        } else {
+            screen_buffer.with_lock(None, |buffer: &mut [u8], pitch: usize| {
+                // all painting must be done in this closure
+                let mut painter = InternalTexturePainter {
+                    buffer,
+                    pitch,
+                    scr_width,
+                    scr_height,
+                };
+                gfx_loop.paint(&mut painter);
+            })?;
+        }
 
         // paint texture on screen
         canvas.copy(&screen_buffer, None, None)?;
         canvas.present();
 
         // sleep a bit, so we don't hog the CPU
+        if cfg.present_vsync {
+            continue;
+        }
         match cfg.sleep_kind {
             SleepKind::SLEEP(nanos) => {
                 std::thread::sleep(Duration::new(0, nanos));
@@ -152,6 +299,54 @@ pub fn run_sdl_loop(cfg: &SdlConfiguration, gfx_loop: &mut dyn GraphicsLoop) ->
     Ok(())
 }
 
+/// Run the game loop without SDL (e.g. for automated tests, on headless systems), for some frames:
+/// no events, a fixed elapsed time per frame, and painting in an in-memory buffer.
+/// Returns the last painted frame, as RGB24 bytes (`width * 3` bytes per row).
+pub fn run_headless(
+    cfg: &SdlConfiguration,
+    gfx_loop: &mut dyn GraphicsLoop,
+    frame_count: u32,
+    elapsed_time: f64,
+) -> Vec<u8> {
+    let mut scr_width = cfg.scr_width;
+    let mut scr_height = cfg.scr_height;
+    let mut buffer = vec![0_u8; (scr_width * scr_height * 3) as usize];
+    let mut time_accumulator = 0.0;
+    for _ in 0..frame_count {
+        if !update_state(cfg, gfx_loop, elapsed_time, &mut time_accumulator) {
+            break;
+        }
+        if let Some((new_width, new_height)) = gfx_loop.take_requested_screen_size() {
+            assert!(new_width > 0 && new_height > 0);
+            scr_width = new_width;
+            scr_height = new_height;
+            buffer = vec![0_u8; (scr_width * scr_height * 3) as usize];
+        }
+        let mut painter = InternalTexturePainter {
+            buffer: &mut buffer,
+            pitch: (scr_width * 3) as usize,
+            scr_width,
+            scr_height,
+        };
+        gfx_loop.paint(&mut painter);
+    }
+    buffer
+}
+
+/// Save an RGB24 frame (e.g. from `run_headless`) as a binary PPM image.
+pub fn save_ppm(path: &str, width: i32, height: i32, rgb: &[u8]) -> Result<(), String> {
+    let expected_len = (width * height * 3) as usize;
+    if rgb.len() != expected_len {
+        return Err(format!(
+            "Bad frame size for {width}x{height}: {} bytes instead of {expected_len}",
+            rgb.len()
+        ));
+    }
+    let mut bytes = format!("P6\n{width} {height}\n255\n").into_bytes();
+    bytes.extend_from_slice(rgb);
+    std::fs::write(path, bytes).map_err(|err| format!("Failed to save {path}: {err}"))
+}
+
 //--------------------------------
 // Internal details
 
@@ -179,41 +374,331 @@ impl<'a> Painter for InternalTexturePainter<'a> {
             self.buffer[offset + 2] = color.b;
         }
     }
+
+    fn read_pixel(&self, x: i32, y: i32) -> Option<RGB> {
+        if x >= 0 && y >= 0 && x < self.scr_width && y < self.scr_height {
+            let offset = (y as usize) * self.pitch + (x as usize) * 3;
+            Some(RGB::from(
+                self.buffer[offset],
+                self.buffer[offset + 1],
+                self.buffer[offset + 2],
+            ))
+        } else {
+            None
+        }
+    }
+
+    // faster than the default: fill the first row, then copy it over all the other rows
+    fn clear(&mut self, color: RGB) {
+        let row_len = (self.scr_width as usize) * 3;
+        for pixel in self.buffer[..row_len].chunks_exact_mut(3) {
+            pixel[0] = color.r;
+            pixel[1] = color.g;
+            pixel[2] = color.b;
+        }
+        for y in 1..(self.scr_height as usize) {
+            self.buffer.copy_within(0..row_len, y * self.pitch);
+        }
+    }
+
+    // faster than the default: the row is clipped once, then its pixels are written contiguously
+    fn draw_horiz_line(&mut self, x1: i32, x2: i32, y: i32, color: RGB) {
+        let (xmin, xmax) = if x1 < x2 { (x1, x2) } else { (x2, x1) };
+        let xmin = xmin.max(0);
+        let xmax = xmax.min(self.scr_width - 1);
+        if y < 0 || y >= self.scr_height || xmin > xmax {
+            return;
+        }
+        let start = (y as usize) * self.pitch + (xmin as usize) * 3;
+        let end = (y as usize) * self.pitch + (xmax as usize + 1) * 3;
+        for pixel in self.buffer[start..end].chunks_exact_mut(3) {
+            pixel[0] = color.r;
+            pixel[1] = color.g;
+            pixel[2] = color.b;
+        }
+    }
+
+    fn fill_rect(&mut self, x: i32, y: i32, w: i32, h: i32, color: RGB) {
+        if w > 0 && h > 0 {
+            let ymin = y.max(0);
+            let ymax = (y + h).min(self.scr_height);
+            for yy in ymin..ymax {
+                self.draw_horiz_line(x, x + w - 1, yy, color);
+            }
+        }
+    }
+}
+
+/// Update the state once (or, with a fixed update rate, as many times as the elapsed time covers).
+/// Returns false if the game loop should stop.
+fn update_state(
+    cfg: &SdlConfiguration,
+    gfx_loop: &mut dyn GraphicsLoop,
+    elapsed_time: f64,
+    time_accumulator: &mut f64,
+) -> bool {
+    match cfg.fixed_timestep {
+        Some(timestep) => {
+            *time_accumulator += elapsed_time;
+            while *time_accumulator >= timestep {
+                *time_accumulator -= timestep;
+                if !gfx_loop.update_state(timestep) {
+                    return false;
+                }
+            }
+            gfx_loop.set_interpolation(*time_accumulator / timestep);
+            true
+        }
+        None => gfx_loop.update_state(elapsed_time),
+    }
+}
+
+/// Upscale an RGB24 frame (without padding) 2x: each pixel becomes 2x2 pixels, in the destination
+/// buffer (which has the given pitch).
+fn upscale_2x(src: &[u8], width: usize, height: usize, dst: &mut [u8], dst_pitch: usize) {
+    let src_pitch = width * 3;
+    for (y, src_row) in src.chunks_exact(src_pitch).take(height).enumerate() {
+        let row_start = 2 * y * dst_pitch;
+        let dst_row = &mut dst[row_start..(row_start + 2 * src_pitch)];
+        for (src_pixel, dst_pixels) in src_row.chunks_exact(3).zip(dst_row.chunks_exact_mut(6)) {
+            dst_pixels[0..3].copy_from_slice(src_pixel);
+            dst_pixels[3..6].copy_from_slice(src_pixel);
+        }
+        // the second row is the same as the first one
+        dst.copy_within(row_start..(row_start + 2 * src_pitch), row_start + dst_pitch);
+    }
 }
 
 struct FpsAndElapsedCounter {
     time_sum: f64,
     time_cnt: u32,
     fps: u32,
+    has_new_fps: bool,
     last_moment: Instant,
+    max_elapsed_time: f64,
+    smoothing: f64,
+    smoothed_time: Option<f64>,
 }
 
 impl FpsAndElapsedCounter {
-    fn new() -> Self {
+    fn new(max_elapsed_time: f64, smoothing: f64) -> Self {
         FpsAndElapsedCounter {
             time_cnt: 0,
             time_sum: 0.0,
             last_moment: Instant::now(),
             fps: 0,
+            has_new_fps: false,
+            max_elapsed_time,
+            smoothing,
+            smoothed_time: None,
         }
     }
 
     fn update_and_get_ellapsed_time(&mut self) -> f64 {
         // compute time
         let next_moment = Instant::now();
-        let elapsed_time = next_moment.duration_since(self.last_moment).as_secs_f64();
+        let raw_elapsed_time = next_moment.duration_since(self.last_moment).as_secs_f64();
         self.last_moment = next_moment;
 
-        // compute FPS
-        self.time_sum += elapsed_time;
+        // compute FPS (from the real elapsed time)
+        self.time_sum += raw_elapsed_time;
         self.time_cnt += 1;
         if self.time_sum >= 1.0 {
             let avg = self.time_sum / (self.time_cnt as f64);
             self.fps = if avg <= 0.0 { 999999 } else { (1.0 / avg) as u32 };
+            self.has_new_fps = true;
             self.time_cnt = 0;
             self.time_sum = 0.0;
         }
 
+        self.smooth_elapsed_time(raw_elapsed_time)
+    }
+
+    /// Returns the FPS, only once after each new measurement.
+    fn take_new_fps(&mut self) -> Option<u32> {
+        std::mem::take(&mut self.has_new_fps).then_some(self.fps)
+    }
+
+    /// Clamp (and optionally smooth) the raw elapsed time.
+    fn smooth_elapsed_time(&mut self, raw_elapsed_time: f64) -> f64 {
+        let elapsed_time = raw_elapsed_time.min(self.max_elapsed_time);
+        let elapsed_time = match self.smoothed_time {
+            Some(prev) => prev * self.smoothing + elapsed_time * (1.0 - self.smoothing),
+            None => elapsed_time,
+        };
+        self.smoothed_time = Some(elapsed_time);
         elapsed_time
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::Layer;
+    use crate::BLACK;
+
+    #[test]
+    fn long_frames_are_clamped() {
+        let mut timer = FpsAndElapsedCounter::new(DEFAULT_MAX_ELAPSED_TIME, 0.0);
+        assert_eq!(timer.smooth_elapsed_time(0.02), 0.02);
+        assert_eq!(timer.smooth_elapsed_time(2.5), DEFAULT_MAX_ELAPSED_TIME);
+        assert_eq!(timer.smooth_elapsed_time(0.02), 0.02);
+    }
+
+    #[test]
+    fn smoothing_spreads_out_a_spike() {
+        let mut timer = FpsAndElapsedCounter::new(1.0, 0.5);
+        assert_eq!(timer.smooth_elapsed_time(0.02), 0.02);
+        let spike = timer.smooth_elapsed_time(0.5);
+        assert!((spike - 0.26).abs() < 1e-9);
+        let after = timer.smooth_elapsed_time(0.02);
+        assert!(after < spike && after > 0.02);
+    }
+
+    #[test]
+    fn vsync_is_off_unless_requested() {
+        let cfg = SdlConfiguration::new("test", 320, 200, 2, SleepKind::YIELD);
+        assert!(!cfg.present_vsync);
+        let cfg = cfg.with_vsync(true);
+        assert!(cfg.present_vsync);
+        // the other settings are kept
+        assert!(cfg.sleep_kind == SleepKind::YIELD);
+        assert_eq!((cfg.scr_width, cfg.scr_height, cfg.pixel_size), (320, 200, 2));
+        assert!(!cfg.with_vsync(false).present_vsync);
+    }
+
+    #[test]
+    fn fast_fill_rect_paints_like_the_default() {
+        const W: i32 = 8;
+        const H: i32 = 6;
+        const PITCH: usize = (W as usize) * 3 + 2;
+        let mut buffer = vec![0_u8; PITCH * (H as usize)];
+        let mut fast = InternalTexturePainter {
+            buffer: &mut buffer,
+            pitch: PITCH,
+            scr_width: W,
+            scr_height: H,
+        };
+        // the layer uses the default (pixel by pixel) implementations
+        let mut layer = Layer::new(W, H);
+        let rects = [
+            (1, 1, 3, 2),
+            (-2, -1, 4, 3),
+            (5, 4, 10, 10),
+            (2, 2, 0, 3),
+            (3, 0, -2, 2),
+            (0, 5, 8, 1),
+        ];
+        for (idx, &(x, y, w, h)) in rects.iter().enumerate() {
+            let color = RGB::from(idx as u8 * 40, 100, 200 - idx as u8 * 30);
+            fast.fill_rect(x, y, w, h, color);
+            layer.fill_rect(x, y, w, h, color);
+        }
+        fast.draw_horiz_line(6, -3, 3, RGB::from(1, 2, 3));
+        layer.draw_horiz_line(6, -3, 3, RGB::from(1, 2, 3));
+        for y in 0..H {
+            for x in 0..W {
+                let expected = layer.read_pixel(x, y).unwrap_or(BLACK);
+                assert!(fast.read_pixel(x, y) == Some(expected), "pixel ({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn clear_fills_every_pixel() {
+        const PITCH: usize = 5 * 3 + 1;
+        let mut buffer = vec![7_u8; PITCH * 4];
+        let mut painter = InternalTexturePainter {
+            buffer: &mut buffer,
+            pitch: PITCH,
+            scr_width: 5,
+            scr_height: 4,
+        };
+        painter.clear(RGB::from(10, 20, 30));
+        for row in buffer.chunks_exact(PITCH) {
+            assert_eq!(row[..15], [10, 20, 30].repeat(5));
+            // the padding at the end of the row is not touched
+            assert_eq!(row[15], 7);
+        }
+    }
+
+    #[test]
+    fn upscale_turns_each_pixel_into_2x2_pixels() {
+        // 2x2 pixels: red, green / blue, white
+        let src = [255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255];
+        // (the destination rows have 2 bytes of padding)
+        let dst_pitch = 4 * 3 + 2;
+        let mut dst = vec![9; dst_pitch * 4];
+        upscale_2x(&src, 2, 2, &mut dst, dst_pitch);
+        let (red, green, blue, white) = ([255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255]);
+        let expected_rows = [
+            [red, red, green, green].concat(),
+            [red, red, green, green].concat(),
+            [blue, blue, white, white].concat(),
+            [blue, blue, white, white].concat(),
+        ];
+        for (row, expected) in dst.chunks_exact(dst_pitch).zip(expected_rows) {
+            assert_eq!(row[..12], expected);
+            assert_eq!(row[12..], [9, 9]);
+        }
+    }
+
+    /// Counts the updates, paints the update count in the first pixel, and stops after `max_updates`.
+    struct CountingLoop {
+        updates: u8,
+        max_updates: u8,
+        resize_to: Option<(i32, i32)>,
+    }
+
+    impl GraphicsLoop for CountingLoop {
+        fn handle_event(&mut self, _event: &Event) -> bool {
+            true
+        }
+
+        fn update_state(&mut self, _elapsed_time: f64) -> bool {
+            self.updates += 1;
+            self.updates <= self.max_updates
+        }
+
+        fn paint(&self, painter: &mut dyn Painter) {
+            painter.draw_pixel(0, 0, RGB::from(self.updates, 0, 0));
+        }
+
+        fn take_requested_screen_size(&mut self) -> Option<(i32, i32)> {
+            self.resize_to.take()
+        }
+    }
+
+    #[test]
+    fn headless_loop_runs_without_sdl() {
+        let cfg = SdlConfiguration::new("test", 4, 3, 1, SleepKind::NONE);
+        let mut gfx_loop = CountingLoop {
+            updates: 0,
+            max_updates: 10,
+            resize_to: None,
+        };
+        let frame = run_headless(&cfg, &mut gfx_loop, 5, 0.02);
+        assert_eq!(frame.len(), 4 * 3 * 3);
+        assert_eq!(frame[0], 5);
+
+        // the loop can stop early, and change the screen size
+        let mut gfx_loop = CountingLoop {
+            updates: 0,
+            max_updates: 2,
+            resize_to: Some((2, 2)),
+        };
+        let frame = run_headless(&cfg, &mut gfx_loop, 5, 0.02);
+        assert_eq!(frame.len(), 2 * 2 * 3);
+        assert_eq!((gfx_loop.updates, frame[0]), (3, 2));
+
+        // with a fixed update rate, the updates follow the elapsed time (not the frames)
+        let cfg = cfg.with_fixed_update_rate(100);
+        let mut gfx_loop = CountingLoop {
+            updates: 0,
+            max_updates: 100,
+            resize_to: None,
+        };
+        run_headless(&cfg, &mut gfx_loop, 5, 0.0201);
+        assert_eq!(gfx_loop.updates, 10);
+    }
+}
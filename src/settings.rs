@@ -0,0 +1,266 @@
+//! User preferences (FOV, gamma, key bindings etc), persisted as a plain INI-like text file.
+//!
+//! The format is one `key = value` pair per line, with `#` or `;` starting a comment line.
+//! Key bindings are stored as `bind.<action> = <key>, <key>, ...`, using the SDL key names.
+
+use crate::game::default_key_bindings;
+use sdl2::keyboard::Keycode;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+
+pub const DEFAULT_FOV: i32 = 90;
+pub(crate) const FOV_MIN: i32 = 60;
+pub(crate) const FOV_MAX: i32 = 120;
+const GAMMA_MAX: u8 = 4;
+const MIN_FPS_MAX: u32 = 200;
+const AUTOMAP_THICKNESS_MAX: i32 = 5;
+const FOG_DENSITY_MAX: f64 = 0.01;
+const BIND_PREFIX: &str = "bind.";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserSettings {
+    /// Horizontal field of view, in degrees (for a 4:3 screen).
+    pub fov: i32,
+    /// Gamma correction level, 0 (off) to 4, like in the original game.
+    pub gamma: u8,
+    /// Multiplier for the turning speed.
+    pub turn_sensitivity: f64,
+    /// Always run (the modern default): the "slow" key walks instead. If off, the player walks,
+    /// and the "slow" key runs instead (like the run key in vanilla DOOM).
+    pub always_run: bool,
+    /// Speed multiplier for moving and turning while the "slow" key is held, 0.1 to 1.
+    pub slow_factor: f64,
+    /// How long (in seconds) a held turn takes to speed up to the full turning speed, 0 to 2.
+    pub turn_ramp_time: f64,
+    /// The turning speed at the start of a held turn, as a fraction of the full speed, 0.1 to 1.
+    pub turn_start_factor: f64,
+    /// The thickness of the automap lines, in pixels, 1 to 5.
+    pub automap_thickness: i32,
+    /// Keep the player's momentum when teleporting (like some mods do).
+    /// Off by default: vanilla DOOM stops the player on teleport.
+    pub teleport_keeps_momentum: bool,
+    /// Distance fog density, 0 (off, like vanilla) to 0.01 (very thick fog).
+    pub fog_density: f64,
+    /// The fog color, as red, green, blue.
+    pub fog_color: [u8; 3],
+    /// Dynamic resolution: when the frame rate stays below this, the resolution is lowered
+    /// (and raised back when it recovers). 0 turns it off.
+    pub min_fps: u32,
+    /// Key bindings: action name -> keys.
+    pub key_bindings: BTreeMap<String, Vec<Keycode>>,
+}
+
+impl Default for UserSettings {
+    fn default() -> Self {
+        let key_bindings = default_key_bindings()
+            .into_iter()
+            .map(|(action, keys)| (action.to_string(), keys))
+            .collect();
+        Self {
+            fov: DEFAULT_FOV,
+            gamma: 0,
+            turn_sensitivity: 1.0,
+            always_run: true,
+            slow_factor: 0.5,
+            // (vanilla DOOM turns at half speed for the first 6 tics)
+            turn_ramp_time: 6.0 / 35.0,
+            turn_start_factor: 0.5,
+            min_fps: 0,
+            automap_thickness: 1,
+            teleport_keeps_momentum: false,
+            fog_density: 0.0,
+            fog_color: [96, 96, 96],
+            key_bindings,
+        }
+    }
+}
+
+impl UserSettings {
+    /// Load the settings from a file, along with the warnings about the lines which were ignored.
+    /// If the file does not exist, the default settings are returned.
+    /// Unknown keys and invalid values are ignored (with a warning).
+    pub fn load(path: &str) -> Result<(Self, Vec<String>), String> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(Self::parse(&text)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok((Self::default(), Vec::new())),
+            Err(e) => Err(format!("Failed to read settings file {path}: {e}")),
+        }
+    }
+
+    /// Save the settings to a file.
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_text()).map_err(|e| format!("Failed to write settings file {path}: {e}"))
+    }
+
+    /// Parse the settings text. Also returns a warning for each line which was ignored.
+    pub fn parse(text: &str) -> (Self, Vec<String>) {
+        let mut settings = Self::default();
+        let mut warnings = Vec::new();
+        for (line_idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            let line_nr = line_idx + 1;
+            match line.split_once('=') {
+                Some((key, value)) => {
+                    if let Err(msg) = settings.apply_value(key.trim(), value.trim()) {
+                        warnings.push(format!("settings line {line_nr}: {msg}"));
+                    }
+                }
+                None => warnings.push(format!("settings line {line_nr}: expected `key = value`")),
+            }
+        }
+        (settings, warnings)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# RusTooM user settings\n");
+        text += &format!("fov = {}\n", self.fov);
+        text += &format!("gamma = {}\n", self.gamma);
+        text += &format!("turn_sensitivity = {}\n", self.turn_sensitivity);
+        text += &format!("always_run = {}\n", self.always_run);
+        text += &format!("slow_factor = {}\n", self.slow_factor);
+        text += &format!("turn_ramp_time = {}\n", self.turn_ramp_time);
+        text += &format!("turn_start_factor = {}\n", self.turn_start_factor);
+        text += &format!("min_fps = {}\n", self.min_fps);
+        text += &format!("automap_thickness = {}\n", self.automap_thickness);
+        text += &format!("teleport_keeps_momentum = {}\n", self.teleport_keeps_momentum);
+        text += &format!("fog_density = {}\n", self.fog_density);
+        let [r, g, b] = self.fog_color;
+        text += &format!("fog_color = {r}, {g}, {b}\n");
+        for (action, keys) in &self.key_bindings {
+            let names: Vec<String> = keys.iter().map(|k| k.name()).collect();
+            text += &format!("{BIND_PREFIX}{action} = {}\n", names.join(", "));
+        }
+        text
+    }
+
+    //-----------------
+
+    fn apply_value(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "fov" => self.fov = parse_in_range(key, value, FOV_MIN, FOV_MAX)?,
+            "gamma" => self.gamma = parse_in_range(key, value, 0, GAMMA_MAX)?,
+            "turn_sensitivity" => self.turn_sensitivity = parse_in_range(key, value, 0.1, 10.0)?,
+            "always_run" => self.always_run = parse_in_range(key, value, false, true)?,
+            "slow_factor" => self.slow_factor = parse_in_range(key, value, 0.1, 1.0)?,
+            "turn_ramp_time" => self.turn_ramp_time = parse_in_range(key, value, 0.0, 2.0)?,
+            "turn_start_factor" => self.turn_start_factor = parse_in_range(key, value, 0.1, 1.0)?,
+            "automap_thickness" => self.automap_thickness = parse_in_range(key, value, 1, AUTOMAP_THICKNESS_MAX)?,
+            "teleport_keeps_momentum" => self.teleport_keeps_momentum = parse_in_range(key, value, false, true)?,
+            "fog_density" => self.fog_density = parse_in_range(key, value, 0.0, FOG_DENSITY_MAX)?,
+            "fog_color" => self.fog_color = parse_color(key, value)?,
+            "min_fps" => self.min_fps = parse_in_range(key, value, 0, MIN_FPS_MAX)?,
+            _ => {
+                let action = key
+                    .strip_prefix(BIND_PREFIX)
+                    .ok_or_else(|| format!("unknown key `{key}`"))?;
+                let binding = self
+                    .key_bindings
+                    .get_mut(action)
+                    .ok_or_else(|| format!("unknown action `{action}`"))?;
+                // (the comma key itself cannot be bound, since it is the separator)
+                let mut keys = Vec::new();
+                for name in value.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+                    let key = Keycode::from_name(name).ok_or_else(|| format!("unknown key name `{name}`"))?;
+                    keys.push(key);
+                }
+                *binding = keys;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse a color, as `red, green, blue` (each 0 to 255).
+fn parse_color(key: &str, value: &str) -> Result<[u8; 3], String> {
+    let channels: Vec<&str> = value.split(',').collect();
+    if channels.len() != 3 {
+        return Err(format!(
+            "invalid color for `{key}` (expected `red, green, blue`): {value}"
+        ));
+    }
+    let mut color = [0; 3];
+    for (channel, text) in color.iter_mut().zip(channels) {
+        *channel = parse_in_range(key, text.trim(), 0, 255)?;
+    }
+    Ok(color)
+}
+
+fn parse_in_range<T: std::str::FromStr + PartialOrd + std::fmt::Display>(
+    key: &str,
+    value: &str,
+    min: T,
+    max: T,
+) -> Result<T, String> {
+    let v: T = value
+        .parse()
+        .map_err(|_| format!("invalid value for `{key}`: {value}"))?;
+    if v < min || v > max {
+        Err(format!("value for `{key}` out of range {min}..={max}: {value}"))
+    } else {
+        Ok(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_round_trip_through_text() {
+        let mut settings = UserSettings {
+            fov: 110,
+            gamma: 2,
+            always_run: false,
+            slow_factor: 0.25,
+            automap_thickness: 3,
+            teleport_keeps_momentum: true,
+            fog_density: 0.005,
+            fog_color: [10, 20, 30],
+            min_fps: 30,
+            ..Default::default()
+        };
+        settings
+            .key_bindings
+            .insert(String::from("use"), vec![Keycode::F, Keycode::Return]);
+
+        let (parsed, warnings) = UserSettings::parse(&settings.to_text());
+        assert!(warnings.is_empty(), "{warnings:?}");
+        assert_eq!(parsed, settings);
+    }
+
+    #[test]
+    fn settings_are_saved_and_loaded_back() {
+        let path = std::env::temp_dir().join(format!("rustoom_settings_test_{}.cfg", std::process::id()));
+        let path = path.to_str().unwrap();
+        let settings = UserSettings {
+            fov: 75,
+            gamma: 4,
+            turn_sensitivity: 1.5,
+            ..Default::default()
+        };
+        settings.save(path).unwrap();
+        let loaded = UserSettings::load(path);
+        std::fs::remove_file(path).unwrap();
+        let (loaded, warnings) = loaded.unwrap();
+        assert!(warnings.is_empty(), "{warnings:?}");
+        assert_eq!(loaded, settings);
+
+        // a missing file just means the default settings
+        let (loaded, warnings) = UserSettings::load(path).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(loaded, UserSettings::default());
+    }
+
+    #[test]
+    fn bad_lines_are_reported_and_ignored() {
+        let (parsed, warnings) = UserSettings::parse("# comment\nfov = 500\nnonsense\nbogus = 1\ngamma = 3\n");
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings[0].starts_with("settings line 2:"));
+        assert_eq!(parsed.fov, DEFAULT_FOV);
+        assert_eq!(parsed.gamma, 3);
+    }
+}
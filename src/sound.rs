@@ -0,0 +1,94 @@
+//! Sound events: the engine does not play any sounds, it only reports *what* should be heard,
+//! so that an embedder can play the matching sounds (e.g. the `DS*` lumps from the WAD).
+
+use crate::map_items::Vertex;
+use crate::utils::*;
+
+// The sound names, as in the WAD (without the `DS` prefix)
+// - see: https://doomwiki.org/wiki/Sound
+pub const SOUND_TELEPORT: &str = "TELEPT";
+/// Using a wall which does nothing.
+pub const SOUND_NO_WAY: &str = "NOWAY";
+/// Trying to open a locked door, without the key.
+pub const SOUND_OOF: &str = "OOF";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SoundEvent {
+    pub name: &'static str,
+    /// Where the sound comes from, in the map (None for sounds which are not positioned, like UI sounds).
+    pub source: Option<Vertex>,
+}
+
+impl SoundEvent {
+    #[inline]
+    pub fn at(name: &'static str, source: Vertex) -> Self {
+        Self {
+            name,
+            source: Some(source),
+        }
+    }
+}
+
+//----------------------
+
+// DMX sound lumps: an 8-byte header, then 8-bit unsigned PCM samples,
+// with 16 padding samples at the start and at the end (included in the sample count)
+// - see: https://doomwiki.org/wiki/Sound
+const DMX_FORMAT: u16 = 3;
+const DMX_HEADER_SIZE: usize = 8;
+const DMX_PADDING: usize = 16;
+
+/// The decoded samples of a sound lump.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SoundData {
+    pub sample_rate: u32,
+    /// 8-bit unsigned mono PCM (128 is silence).
+    pub samples: Vec<u8>,
+}
+
+impl SoundData {
+    /// Decode a DMX sound lump. Returns None if the lump is not in the DMX format.
+    pub fn from_dmx(lump: &[u8]) -> Option<Self> {
+        if lump.len() < DMX_HEADER_SIZE || buf_to_u16(&lump[0..2]) != DMX_FORMAT {
+            return None;
+        }
+        let sample_rate = buf_to_u16(&lump[2..4]) as u32;
+        // some lumps claim more samples than they have => use what is there
+        let sample_cnt = (buf_to_u32(&lump[4..8]) as usize).min(lump.len() - DMX_HEADER_SIZE);
+        let mut samples = &lump[DMX_HEADER_SIZE..(DMX_HEADER_SIZE + sample_cnt)];
+        if samples.len() >= 2 * DMX_PADDING {
+            samples = &samples[DMX_PADDING..(samples.len() - DMX_PADDING)];
+        }
+        Some(Self {
+            sample_rate,
+            samples: samples.to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WadData;
+
+    #[test]
+    fn pistol_sound_is_decoded() {
+        let wad_data = WadData::load("DOOM1.WAD", true).unwrap();
+        let pistol = wad_data.sound_pcm("DSPISTOL").unwrap();
+        assert_eq!(pistol.sample_rate, 11025);
+        // 5661 samples in the header, minus the padding at both ends
+        assert_eq!(pistol.samples.len(), 5661 - 2 * DMX_PADDING);
+        // the sound name works too
+        assert_eq!(wad_data.sound_pcm("pistol"), Some(pistol));
+        assert_eq!(wad_data.sound_pcm("PLAYPAL"), None);
+    }
+
+    #[test]
+    fn truncated_lump_keeps_the_samples_it_has() {
+        let lump = [3, 0, 0x11, 0x2B, 100, 0, 0, 0, 128, 129, 130];
+        let sound = SoundData::from_dmx(&lump).unwrap();
+        assert_eq!(sound.sample_rate, 11025);
+        assert_eq!(sound.samples, [128, 129, 130]);
+        assert_eq!(SoundData::from_dmx(&lump[..7]), None);
+    }
+}
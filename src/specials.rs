@@ -0,0 +1,74 @@
+//! Classification of the linedef specials (types).
+//! See [Linedef types](https://doomwiki.org/wiki/Linedef_type) at Doom Wiki.
+
+use crate::player::KeyColor;
+
+/// The key needed for activating a linedef special (locked doors and switches).
+pub fn required_key(special_type: u16) -> Option<KeyColor> {
+    match special_type {
+        // locked doors
+        26 | 32 => Some(KeyColor::Blue),
+        27 | 34 => Some(KeyColor::Yellow),
+        28 | 33 => Some(KeyColor::Red),
+        // locked switches (DOOM II)
+        99 | 133 => Some(KeyColor::Blue),
+        136 | 137 => Some(KeyColor::Yellow),
+        134 | 135 => Some(KeyColor::Red),
+        _ => None,
+    }
+}
+
+/// Walk-over teleporters, which can be used by the player.
+/// (types 125 and 126 are also teleporters, but only for monsters)
+#[inline]
+pub fn is_player_teleport(special_type: u16) -> bool {
+    matches!(special_type, 39 | 97)
+}
+
+/// Specials which are triggered by walking over (crossing) their linedef.
+/// The monster-only teleporters (125 and 126) are not included.
+pub fn is_walk_trigger(special_type: u16) -> bool {
+    is_walk_once(special_type) || is_walk_repeatable(special_type)
+}
+
+/// W1 specials: walk-over triggers which work only once.
+pub fn is_walk_once(special_type: u16) -> bool {
+    matches!(
+        special_type,
+        2..=6 | 8 | 10 | 12 | 13 | 16 | 17 | 19 | 22 | 25 | 30 | 35..=40 | 44 | 52..=54 | 56..=59
+            | 100 | 104 | 108..=110 | 119 | 121 | 124 | 130 | 141
+    )
+}
+
+/// WR specials: walk-over triggers which can be activated any number of times.
+pub fn is_walk_repeatable(special_type: u16) -> bool {
+    matches!(
+        special_type,
+        72..=77 | 79..=84 | 86..=98 | 105..=107 | 120 | 128 | 129
+    )
+}
+
+/// How a level is exited.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LevelExit {
+    Normal,
+    Secret,
+}
+
+/// Exit specials: 11 (S1) and 52 (W1) exit normally, 51 (S1) and 124 (W1) go to the secret level.
+pub fn level_exit(special_type: u16) -> Option<LevelExit> {
+    match special_type {
+        11 | 52 => Some(LevelExit::Normal),
+        51 | 124 => Some(LevelExit::Secret),
+        _ => None,
+    }
+}
+
+/// The message shown when trying to activate a locked special without the required key.
+pub fn locked_message(special_type: u16, key: KeyColor) -> String {
+    let what = match special_type {
+        26..=34 => "open this door",
+        _ => "activate this object",
+    };
+    format!("You need a {} key to {what}", key.name())
+}
@@ -6,13 +6,40 @@
 // TODO temporary !!!
 #![allow(dead_code)]
 
-use crate::{angle::*, map_items::Vertex, utils::*};
+use crate::{angle::*, map_items::Vertex, utils::*, wad::GameKind};
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+/// Type code of the teleport destination things.
+pub const TELEPORT_DEST_TYPE: u16 = 14;
+/// Type code of the deathmatch start things (the player starts have type codes 1 to 4).
+pub const DEATHMATCH_START_TYPE: u16 = 11;
+/// How many players a (vanilla) map can have starts for.
+pub const MAX_PLAYERS: usize = 4;
+
+// Thing flags
+// - see: https://doomwiki.org/wiki/Thing#Flags
+pub const THING_SKILL_1_2: u16 = 1 << 0;
+pub const THING_SKILL_3: u16 = 1 << 1;
+pub const THING_SKILL_4_5: u16 = 1 << 2;
+/// Deaf/ambush: monsters do not wake up on sounds, only when they see the player.
+pub const THING_AMBUSH: u16 = 1 << 3;
+pub const THING_MULTIPLAYER_ONLY: u16 = 1 << 4;
+
+/// The monsters of Doom (and Doom II), in the order used by `ThingType::Monster`.
+const DOOM_MONSTERS: [u16; 20] = [
+    3004, 9, 65, 3001, 3002, 58, 3006, 3005, 69, 3003, 68, 71, 66, 67, 64, 16, 7, 84, 72, 88,
+];
+/// The monsters of Heretic, in the order used by `ThingType::Monster`.
+const HERETIC_MONSTERS: [u16; 15] = [66, 5, 68, 69, 45, 46, 90, 92, 70, 64, 65, 15, 6, 9, 7];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum ThingType {
+    /// The start of a player (1 to 4).
     Player(u8),
+    /// A monster, as its index in the game's monster list.
     Monster(u8),
+    /// A weapon, as its weapon slot.
     Weapon(u8),
+    /// Ammo: the ammo kind (in vanilla order) and how much ammo it gives.
     Ammo(u8, u8),
     ArtifactItem,
     Collectible,
@@ -20,10 +47,91 @@ pub enum ThingType {
     Obstacle,
     Decoration,
     Other(u16),
-    // TODO .....
     Unknown,
 }
 
+impl ThingType {
+    /// Classify a type code. Heretic reuses many of Doom's type codes for different things
+    /// (e.g. 5 is a blue keycard in Doom, but a fire gargoyle in Heretic), so each game has
+    /// its own code table - only the starts and teleport destinations are shared.
+    pub fn classify(type_code: u16, game: GameKind) -> Self {
+        let game_specific = match game {
+            GameKind::Doom => doom_thing_type(type_code),
+            GameKind::Heretic => heretic_thing_type(type_code),
+        };
+        game_specific.unwrap_or(match type_code {
+            1..=4 => ThingType::Player(type_code as u8),
+            DEATHMATCH_START_TYPE | TELEPORT_DEST_TYPE => ThingType::Other(type_code),
+            _ => ThingType::Unknown,
+        })
+    }
+}
+
+/// See: https://doomwiki.org/wiki/Thing_types
+fn doom_thing_type(type_code: u16) -> Option<ThingType> {
+    if let Some(idx) = DOOM_MONSTERS.iter().position(|&m| m == type_code) {
+        return Some(ThingType::Monster(idx as u8));
+    }
+    let thing_type = match type_code {
+        2005 => ThingType::Weapon(1),
+        2001 | 82 => ThingType::Weapon(3),
+        2002 => ThingType::Weapon(4),
+        2003 => ThingType::Weapon(5),
+        2004 => ThingType::Weapon(6),
+        2006 => ThingType::Weapon(7),
+        2007 => ThingType::Ammo(0, 10),
+        2048 => ThingType::Ammo(0, 50),
+        2008 => ThingType::Ammo(1, 4),
+        2049 => ThingType::Ammo(1, 20),
+        2047 => ThingType::Ammo(2, 20),
+        17 => ThingType::Ammo(2, 100),
+        2010 => ThingType::Ammo(3, 1),
+        2046 => ThingType::Ammo(3, 5),
+        2013 | 2022..=2026 | 2045 | 83 => ThingType::ArtifactItem,
+        2011 | 2012 | 2014 | 2015 | 2018 | 2019 | 8 => ThingType::Collectible,
+        5 | 6 | 13 | 38..=40 => ThingType::Key,
+        25..=33 | 35..=37 | 41..=57 | 70 | 73..=78 | 85 | 86 | 2028 | 2035 => ThingType::Obstacle,
+        10 | 12 | 15 | 18..=24 | 34 | 59..=63 | 79..=81 => ThingType::Decoration,
+        87 | 89 => ThingType::Other(type_code),
+        _ => return None,
+    };
+    Some(thing_type)
+}
+
+/// See: https://doomwiki.org/wiki/Thing_types_(Heretic)
+fn heretic_thing_type(type_code: u16) -> Option<ThingType> {
+    if let Some(idx) = HERETIC_MONSTERS.iter().position(|&m| m == type_code) {
+        return Some(ThingType::Monster(idx as u8));
+    }
+    let thing_type = match type_code {
+        2005 => ThingType::Weapon(1),
+        2001 => ThingType::Weapon(3),
+        53 => ThingType::Weapon(4),
+        2004 => ThingType::Weapon(5),
+        2003 => ThingType::Weapon(6),
+        2002 => ThingType::Weapon(7),
+        10 => ThingType::Ammo(0, 10),
+        12 => ThingType::Ammo(0, 50),
+        18 => ThingType::Ammo(1, 5),
+        19 => ThingType::Ammo(1, 20),
+        54 => ThingType::Ammo(2, 10),
+        55 => ThingType::Ammo(2, 25),
+        20 => ThingType::Ammo(3, 20),
+        21 => ThingType::Ammo(3, 100),
+        22 => ThingType::Ammo(4, 1),
+        23 => ThingType::Ammo(4, 10),
+        13 => ThingType::Ammo(5, 20),
+        16 => ThingType::Ammo(5, 100),
+        30 | 32..=34 | 36 | 75 | 82..=84 | 86 => ThingType::ArtifactItem,
+        8 | 31 | 35 | 81 | 85 => ThingType::Collectible,
+        73 | 79 | 80 => ThingType::Key,
+        29 | 44 | 47 | 87 | 2035 => ThingType::Obstacle,
+        56 => ThingType::Other(type_code),
+        _ => return None,
+    };
+    Some(thing_type)
+}
+
 #[derive(Clone, Default)]
 pub struct Thing {
     pub pos: Vertex,
@@ -60,18 +168,62 @@ impl Thing {
         self.type_code
     }
 
+    #[inline]
+    pub fn thing_type(&self, game: GameKind) -> ThingType {
+        ThingType::classify(self.type_code, game)
+    }
+
     pub fn is_on_skill_level(&self, level: u8) -> bool {
-        (0 == (self.flags & 0x10)) // only use stuff from single player
+        !self.is_multiplayer_only() // only use stuff from single player
         && (0 != match level {
             0 => 1,
-            1 | 2 => self.flags & 0x01,
-            3 => self.flags & 0x02,
-            4 | 5 => self.flags & 0x04,
+            1 | 2 => self.flags & THING_SKILL_1_2,
+            3 => self.flags & THING_SKILL_3,
+            4 | 5 => self.flags & THING_SKILL_4_5,
             _ => 0,
         })
     }
 
+    #[inline]
     pub fn is_waiting_in_ambush(&self) -> bool {
-        0 != (self.flags & 0x08)
+        0 != (self.flags & THING_AMBUSH)
+    }
+
+    #[inline]
+    pub fn is_multiplayer_only(&self) -> bool {
+        0 != (self.flags & THING_MULTIPLAYER_ONLY)
+    }
+
+    /// Deathmatch starts, as well as the starts of players 2 to 4, are only used in multiplayer games.
+    #[inline]
+    pub fn is_multiplayer_start(&self) -> bool {
+        matches!(self.type_code, 2..=4 | DEATHMATCH_START_TYPE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heretic_codes_are_classified_per_game() {
+        // Heretic-only codes
+        assert_eq!(ThingType::classify(53, GameKind::Heretic), ThingType::Weapon(4));
+        assert_eq!(ThingType::classify(90, GameKind::Heretic), ThingType::Monster(6));
+        assert_eq!(ThingType::classify(90, GameKind::Doom), ThingType::Unknown);
+        // codes which mean different things in each game
+        assert_eq!(ThingType::classify(5, GameKind::Doom), ThingType::Key);
+        assert_eq!(ThingType::classify(5, GameKind::Heretic), ThingType::Monster(1));
+        assert_eq!(ThingType::classify(73, GameKind::Doom), ThingType::Obstacle);
+        assert_eq!(ThingType::classify(73, GameKind::Heretic), ThingType::Key);
+        assert_eq!(ThingType::classify(2011, GameKind::Heretic), ThingType::Unknown);
+        // shared codes
+        for game in [GameKind::Doom, GameKind::Heretic] {
+            assert_eq!(ThingType::classify(3, game), ThingType::Player(3));
+            assert_eq!(
+                ThingType::classify(TELEPORT_DEST_TYPE, game),
+                ThingType::Other(TELEPORT_DEST_TYPE)
+            );
+        }
     }
 }
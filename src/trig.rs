@@ -0,0 +1,56 @@
+//! Precomputed trigonometry, like vanilla DOOM's `finesine` / `finecosine` tables.
+//! Good enough for movement and automap drawing, without calling `sin` / `cos` every time.
+//! (for precision-sensitive code, just use `Angle::rad()` with the regular float functions)
+
+use crate::angle::Angle;
+use std::f64::consts::PI;
+use std::sync::OnceLock;
+
+/// The number of angles in a full circle, in the table.
+pub const FINE_ANGLES: usize = 8192;
+const FINE_MASK: usize = FINE_ANGLES - 1;
+
+static FINE_SINE: OnceLock<Vec<f64>> = OnceLock::new();
+
+/// Convert an angle into an index in the table (rounded to the nearest "fine" angle).
+#[inline]
+pub fn fine_angle_index(angle: Angle) -> usize {
+    ((angle.rad() * (FINE_ANGLES as f64) / (2.0 * PI)).round() as usize) & FINE_MASK
+}
+
+/// The sine and cosine of an angle, from the table.
+#[inline]
+pub fn fine_sin_cos(angle: Angle) -> (f64, f64) {
+    let table = fine_sine_table();
+    let idx = fine_angle_index(angle);
+    (table[idx], table[(idx + FINE_ANGLES / 4) & FINE_MASK])
+}
+
+fn fine_sine_table() -> &'static [f64] {
+    FINE_SINE.get_or_init(|| {
+        (0..FINE_ANGLES)
+            .map(|idx| ((idx as f64) * 2.0 * PI / (FINE_ANGLES as f64)).sin())
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_matches_sin_and_cos() {
+        // the angles are rounded to the nearest "fine" angle => at most half a step off
+        let tolerance = PI / (FINE_ANGLES as f64);
+        for i in 0..1000 {
+            let angle = Angle::from_radians((i as f64) * 0.0377 - 5.0);
+            let (s, c) = fine_sin_cos(angle);
+            assert!((s - angle.rad().sin()).abs() <= tolerance, "sin of {}", angle.rad());
+            assert!((c - angle.rad().cos()).abs() <= tolerance, "cos of {}", angle.rad());
+        }
+        // the axis-aligned angles are exact
+        assert_eq!(fine_sin_cos(Angle::from_degrees(0)), (0.0, 1.0));
+        assert_eq!(fine_sin_cos(Angle::from_degrees(90)).0, 1.0);
+        assert_eq!(fine_sin_cos(Angle::from_degrees(180)).1, -1.0);
+    }
+}
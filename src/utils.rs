@@ -58,6 +58,15 @@ pub fn hash_lump_name(name: &[u8]) -> u64 {
     key
 }
 
+/// A cheap (non-cryptographic) 64-bit hash of some bytes: FNV-1a.
+pub fn fnv1a_hash(bytes: &[u8]) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0100_0000_01b3;
+    bytes
+        .iter()
+        .fold(FNV_OFFSET_BASIS, |hash, b| (hash ^ (*b as u64)).wrapping_mul(FNV_PRIME))
+}
+
 pub fn atoi(s: &str) -> Option<u32> {
     let mut num = 0_u32;
     for b in s.bytes() {
@@ -7,52 +7,121 @@ use crate::map::*;
 use crate::palette::Palette;
 use crate::*;
 use bytes::{Bytes, BytesMut};
+use std::collections::HashMap;
+use std::fmt;
 use std::fs::*;
-use std::io::Read;
+use std::io::{self, Read};
+
+/// The game which a WAD is made for (some data, like the thing type codes, differs per game).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameKind {
+    Doom,
+    Heretic,
+}
 
 /// Stores all the data (lumps) from a WAD file.
 pub struct WadData {
+    path: String,
+    is_iwad: bool,
     maps: Vec<MapData>,
     pal: Palette,
     gfx: Graphics,
     font: Font,
+    /// All the lumps, by name (for names which appear several times, like the map lumps, the last one).
+    lumps: HashMap<String, Bytes>,
+    /// The (non-fatal) problems found while loading.
+    warnings: Vec<String>,
+    #[cfg(feature = "mmap")]
+    is_mapped: bool,
 }
 
 impl WadData {
-    pub fn load(wad_path: &str, is_iwad: bool) -> Result<WadData, String> {
+    pub fn load(wad_path: &str, is_iwad: bool) -> Result<WadData, WadError> {
         // read WAD file bytes
         let mut wad_bytes: BytesMut;
         {
-            let mut file = File::open(wad_path).map_err(|e| e.to_string())?;
-            let len = file.metadata().map_err(|e| e.to_string())?.len() as usize;
+            let mut file = File::open(wad_path)?;
+            let len = file.metadata()?.len() as usize;
             wad_bytes = BytesMut::zeroed(len);
-            file.read_exact(&mut wad_bytes).map_err(|e| e.to_string())?;
+            file.read_exact(&mut wad_bytes)?;
         }
-        let wad_bytes = wad_bytes.freeze();
+        Self::from_bytes(wad_path, is_iwad, wad_bytes.freeze())
+    }
 
+    /// Load a WAD file by memory-mapping it, instead of reading all of it into RAM.
+    /// The lumps are slices of the mapping, so only the lumps which are actually used
+    /// get paged in (useful for very large PWAD compilations).
+    #[cfg(feature = "mmap")]
+    pub fn load_mmap(wad_path: &str, is_iwad: bool) -> Result<WadData, WadError> {
+        let wad_bytes = crate::mmap::map_file(wad_path)?;
+        let mut wad = Self::from_bytes(wad_path, is_iwad, wad_bytes)?;
+        wad.is_mapped = true;
+        Ok(wad)
+    }
+
+    /// Load a WAD from bytes which are already in memory (e.g. embedded in the executable).
+    /// The path is only used in messages, and for `reload`.
+    pub fn from_bytes(wad_path: &str, is_iwad: bool, wad_bytes: Bytes) -> Result<WadData, WadError> {
         // check the WAD header
         if wad_bytes.len() <= 16 {
-            return Err(format!("WAD file {wad_path} is too small"));
+            return Err(WadError::TooSmall {
+                path: wad_path.to_string(),
+            });
         }
-        let wad_kind_str = std::str::from_utf8(&wad_bytes[0..4]).map_err(|_| String::from("Invalid WAD header"))?;
+        let wad_kind_str = String::from_utf8_lossy(&wad_bytes[0..4]);
         let expected_kind_str = if is_iwad { "IWAD" } else { "PWAD" };
-        if expected_kind_str.ne(wad_kind_str) {
-            return Err(format!(
-                "Invalid WAD type: expected {expected_kind_str}, was {wad_kind_str}"
-            ));
+        if expected_kind_str.ne(&wad_kind_str) {
+            return Err(WadError::BadMagic {
+                expected: expected_kind_str.to_string(),
+                found: wad_kind_str.to_string(),
+            });
         }
 
         let mut wad = WadData {
+            path: wad_path.to_string(),
+            is_iwad,
             maps: Vec::new(),
             pal: Palette::new(),
             gfx: Graphics::new(),
             font: Font::new(),
+            lumps: HashMap::new(),
+            warnings: Vec::new(),
+            #[cfg(feature = "mmap")]
+            is_mapped: false,
         };
         wad.parse_wad_lumps(wad_bytes)?;
         wad.validate_collected_data()?;
         Ok(wad)
     }
 
+    /// Reload the WAD file, from the same path it was originally loaded from.
+    pub fn reload(&self) -> Result<WadData, WadError> {
+        #[cfg(feature = "mmap")]
+        if self.is_mapped {
+            return Self::load_mmap(&self.path, self.is_iwad);
+        }
+        Self::load(&self.path, self.is_iwad)
+    }
+
+    #[inline]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    #[inline]
+    pub fn is_iwad(&self) -> bool {
+        self.is_iwad
+    }
+
+    /// Heretic is recognized by its music lumps (which are named `MUS_*`, instead of Doom's `D_*`).
+    pub fn game_kind(&self) -> GameKind {
+        if self.lumps.keys().any(|name| name.starts_with("MUS_")) {
+            GameKind::Heretic
+        } else {
+            GameKind::Doom
+        }
+    }
+
     #[inline]
     pub fn palette(&self) -> &Palette {
         &self.pal
@@ -70,6 +139,73 @@ impl WadData {
         &self.maps[idx].name()
     }
 
+    /// The human-friendly title of a map.
+    /// Only the maps from an IWAD get a built-in title - PWAD maps just use their name.
+    pub fn map_title(&self, map_name: &str) -> String {
+        if self.is_iwad {
+            level_title(map_name)
+        } else {
+            map_name.to_string()
+        }
+    }
+
+    /// The (non-fatal) problems found while loading the WAD (e.g. broken maps, odd lump sizes).
+    #[inline]
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// The size of a lump, in bytes.
+    pub fn lump_size(&self, name: &str) -> Option<usize> {
+        self.lumps.get(name).map(|bytes| bytes.len())
+    }
+
+    /// Decode a (DMX format) sound lump, for the embedder to play it.
+    /// The name can be the full lump name (e.g. `DSPISTOL`) or the sound name (e.g. `PISTOL`, like in `SoundEvent`).
+    pub fn sound_pcm(&self, name: &str) -> Option<SoundData> {
+        let name = name.to_ascii_uppercase();
+        let lump = self.lumps.get(&name).or_else(|| self.lumps.get(&format!("DS{name}")))?;
+        SoundData::from_dmx(lump)
+    }
+
+    /// A cheap hash of a lump's bytes, for detecting if the lump changed (e.g. after reloading).
+    pub fn lump_hash(&self, name: &str) -> Option<u64> {
+        self.lumps.get(name).map(|bytes| utils::fnv1a_hash(bytes))
+    }
+
+    /// The names of the lumps which are different in the other WAD (changed, added or removed),
+    /// sorted by name.
+    pub fn changed_lumps(&self, other: &WadData) -> Vec<String> {
+        let mut changed: Vec<String> = self
+            .lumps
+            .keys()
+            .chain(other.lumps.keys().filter(|name| !self.lumps.contains_key(*name)))
+            .filter(|name| match (self.lumps.get(*name), other.lumps.get(*name)) {
+                (Some(mine), Some(theirs)) => {
+                    mine.len() != theirs.len() || utils::fnv1a_hash(mine) != utils::fnv1a_hash(theirs)
+                }
+                _ => true,
+            })
+            .cloned()
+            .collect();
+        changed.sort_unstable();
+        changed
+    }
+
+    /// The indices of all the maps, in play order: sorted by their episode and map numbers
+    /// (ExMy, then MAPxx), regardless of their order in the WAD directory.
+    /// The maps with other names come last, in directory order.
+    pub fn ordered_map_indices(&self) -> Vec<usize> {
+        let mut indices: Vec<usize> = (0..self.maps.len()).collect();
+        // (the sort is stable => same keys keep the directory order)
+        indices.sort_by_key(|idx| map_order_key(self.maps[*idx].name()));
+        indices
+    }
+
+    pub fn map_index(&self, name: &str) -> Option<usize> {
+        self.maps.iter().position(|m| m.name().eq_ignore_ascii_case(name))
+    }
+
     #[inline]
     pub fn map(&self, idx: usize) -> &MapData {
         // TODO panic-safe error handling ?!?
@@ -89,7 +225,7 @@ impl WadData {
 
     //-----------------
 
-    fn parse_wad_lumps(&mut self, wad_bytes: Bytes) -> Result<(), String> {
+    fn parse_wad_lumps(&mut self, wad_bytes: Bytes) -> Result<(), WadError> {
         let lump_count = utils::buf_to_u32(&wad_bytes[4..8]) as usize;
         let dir_offset = utils::buf_to_u32(&wad_bytes[8..12]) as usize;
         let wad_len = wad_bytes.len();
@@ -105,9 +241,10 @@ impl WadData {
             let lump_name = extract_lump_name(&wad_bytes[(offs + 8)..(offs + 16)], lump_idx)?.to_string();
             let lump_end = lump_start + lump_size;
             if lump_end >= wad_len {
-                return Err(format!("Lump {lump_name} too big: its end goes beyond the WAD"));
+                return Err(WadError::LumpOutOfBounds { name: lump_name });
             }
             let lump_bytes = wad_bytes.slice(lump_start..lump_end);
+            self.lumps.insert(lump_name.clone(), lump_bytes.clone());
 
             // parse map lumps
             if currently_parsing_map.is_some() {
@@ -120,9 +257,20 @@ impl WadData {
                 // finished parsing one map
                 currently_parsing_map = None;
                 if !map.is_complete() {
-                    return Err(format!("Incomplete map in WAD: {}", map.name()));
+                    return Err(WadError::IncompleteMap {
+                        name: map.name().to_string(),
+                    });
+                }
+                if map.is_hexen_format() {
+                    // TODO support the Hexen map format
+                    self.warnings.push(format!(
+                        "Map {} is in Hexen format, which is not supported => skipped",
+                        map.name()
+                    ));
+                } else {
+                    self.warnings.extend(map.validation_warnings());
+                    self.maps.push(map);
                 }
-                self.maps.push(map);
             }
             if is_map_name(&lump_name) {
                 // starting to parse new map
@@ -133,10 +281,10 @@ impl WadData {
             // parse other lump types
             match lump_name.as_str() {
                 "PLAYPAL" => {
-                    self.pal.init_palettes(&lump_bytes);
+                    self.warnings.extend(self.pal.init_palettes(&lump_bytes));
                     self.font.compute_grayscale(&lump_bytes);
                 }
-                "COLORMAP" => self.pal.init_colormaps(&lump_bytes),
+                "COLORMAP" => self.warnings.extend(self.pal.init_colormaps(&lump_bytes)),
                 "PNAMES" => self.gfx.set_patch_names(&lump_bytes)?,
                 "F_START" => is_flats = true,
                 "F_END" => is_flats = false,
@@ -144,6 +292,12 @@ impl WadData {
                     if is_texture_name(&lump_name) {
                         self.gfx.add_textures(&lump_bytes)?;
                     } else if (lump_bytes.len() > 0) && is_flats {
+                        if !lump_bytes.len().is_multiple_of(64) {
+                            self.warnings.push(format!(
+                                "Flat {lump_name} has an invalid size ({} bytes) => it will be a placeholder",
+                                lump_bytes.len()
+                            ));
+                        }
                         self.gfx.add_flat(&lump_name, &lump_bytes);
                     } else if quick_check_if_lump_is_graphic(&lump_bytes) {
                         self.gfx.add_patch(&lump_name, &lump_bytes);
@@ -158,36 +312,96 @@ impl WadData {
         Ok(())
     }
 
-    fn validate_collected_data(&self) -> Result<(), String> {
+    fn validate_collected_data(&self) -> Result<(), WadError> {
         if !self.pal.is_initialized() {
-            Err(String::from("PLAYPAL or COLORMAP lump not found in WAD"))
+            Err(WadError::MissingLump {
+                name: String::from("PLAYPAL or COLORMAP"),
+            })
         } else if self.maps.len() == 0 {
-            Err(String::from("Maps not found in WAD"))
+            Err(WadError::NoMaps)
         } else if !self.font.is_complete() {
-            Err(String::from("Fonts not found in WAD"))
+            Err(WadError::MissingLump {
+                name: String::from("STCFN (font)"),
+            })
         } else {
             Ok(())
         }
     }
 }
 
+//-----------------------------
+
+/// The errors which can occur while loading a WAD.
+#[derive(Debug)]
+pub enum WadError {
+    Io(io::Error),
+    TooSmall { path: String },
+    BadMagic { expected: String, found: String },
+    InvalidLumpName { index: usize },
+    LumpOutOfBounds { name: String },
+    BadLump { name: String, reason: String },
+    MissingLump { name: String },
+    IncompleteMap { name: String },
+    NoMaps,
+}
+
+impl fmt::Display for WadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WadError::Io(err) => write!(f, "{err}"),
+            WadError::TooSmall { path } => write!(f, "WAD file {path} is too small"),
+            WadError::BadMagic { expected, found } => {
+                write!(f, "Invalid WAD type: expected {expected}, was {found}")
+            }
+            WadError::InvalidLumpName { index } => write!(f, "Invalid lump name at index {index}"),
+            WadError::LumpOutOfBounds { name } => write!(f, "Lump {name} too big: its end goes beyond the WAD"),
+            WadError::BadLump { name, reason } => write!(f, "Invalid {name} lump: {reason}"),
+            WadError::MissingLump { name } => write!(f, "{name} lump not found in WAD"),
+            WadError::IncompleteMap { name } => write!(f, "Incomplete map in WAD: {name}"),
+            WadError::NoMaps => write!(f, "Maps not found in WAD"),
+        }
+    }
+}
+
+impl std::error::Error for WadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WadError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for WadError {
+    fn from(err: io::Error) -> Self {
+        WadError::Io(err)
+    }
+}
+
+// the rest of the code (game, main) still uses plain string errors
+impl From<WadError> for String {
+    fn from(err: WadError) -> Self {
+        err.to_string()
+    }
+}
+
 //-----------------------------
 //  Internal utils
 
-fn extract_lump_name(name_bytes: &[u8], idx: usize) -> Result<&str, String> {
+fn extract_lump_name(name_bytes: &[u8], idx: usize) -> Result<&str, WadError> {
     // dismiss all null bytes at the name's end
     let mut idx_end = 0;
     for ch in name_bytes {
         if *ch == 0 {
             break;
         } else if *ch <= 32 || *ch >= 127 {
-            return Err(format!("Invalid lump name at index {idx}"));
+            return Err(WadError::InvalidLumpName { index: idx });
         } else {
             idx_end += 1;
         }
     }
     // all ok
-    std::str::from_utf8(&name_bytes[0..idx_end]).map_err(|e| e.to_string())
+    std::str::from_utf8(&name_bytes[0..idx_end]).map_err(|_| WadError::InvalidLumpName { index: idx })
 }
 
 #[inline]
@@ -265,3 +479,210 @@ fn quick_check_if_lump_is_graphic(bytes: &[u8]) -> bool {
         max_idx += post_len + 4;
     }
 }
+
+/// The sort key for the play order of a map: ExMy maps, then MAPxx maps, then all the others.
+fn map_order_key(name: &str) -> (u8, u32, u32) {
+    let b = name.as_bytes();
+    if b.len() == 4 && b[0] == b'E' && b[2] == b'M' && b[1].is_ascii_digit() && b[3].is_ascii_digit() {
+        (0, (b[1] - b'0') as u32, (b[3] - b'0') as u32)
+    } else if let Some(map) = name.strip_prefix("MAP").and_then(utils::atoi) {
+        (1, 0, map)
+    } else {
+        (2, 0, 0)
+    }
+}
+
+/// Helpers for building (small or deliberately broken) WADs, in tests.
+#[cfg(test)]
+pub(crate) mod test_wads {
+    use super::*;
+
+    /// All the lumps of DOOM1.WAD, as (name, bytes), in directory order.
+    pub fn doom1_lumps() -> Vec<(String, Vec<u8>)> {
+        let wad_bytes = std::fs::read("DOOM1.WAD").unwrap();
+        let lump_count = utils::buf_to_u32(&wad_bytes[4..8]) as usize;
+        let dir_offset = utils::buf_to_u32(&wad_bytes[8..12]) as usize;
+        (0..lump_count)
+            .map(|idx| {
+                let offs = dir_offset + 16 * idx;
+                let start = utils::buf_to_u32(&wad_bytes[offs..offs + 4]) as usize;
+                let size = utils::buf_to_u32(&wad_bytes[offs + 4..offs + 8]) as usize;
+                let name = extract_lump_name(&wad_bytes[offs + 8..offs + 16], idx).unwrap();
+                (name.to_string(), wad_bytes[start..start + size].to_vec())
+            })
+            .collect()
+    }
+
+    /// The index of a map's lump (e.g. "LINEDEFS" of "E1M1"), in a list of lumps.
+    pub fn map_lump_idx(lumps: &[(String, Vec<u8>)], map_name: &str, lump_name: &str) -> usize {
+        let map_idx = lumps.iter().position(|(name, _)| name == map_name).unwrap();
+        map_idx + lumps[map_idx..].iter().position(|(name, _)| name == lump_name).unwrap()
+    }
+
+    /// Build an IWAD from a list of lumps (the directory goes at the end).
+    pub fn build_wad(lumps: &[(String, Vec<u8>)]) -> Result<WadData, WadError> {
+        let mut bytes = b"IWAD".to_vec();
+        let data_len: usize = lumps.iter().map(|(_, data)| data.len()).sum();
+        bytes.extend_from_slice(&(lumps.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&((12 + data_len) as u32).to_le_bytes());
+        let mut directory = Vec::new();
+        for (name, data) in lumps {
+            directory.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            directory.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            let mut name_bytes = [0; 8];
+            name_bytes[..name.len()].copy_from_slice(name.as_bytes());
+            directory.extend_from_slice(&name_bytes);
+            bytes.extend_from_slice(data);
+        }
+        bytes.extend_from_slice(&directory);
+        WadData::from_bytes("TEST.WAD", true, Bytes::from(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_wads::*;
+    use super::*;
+
+    #[test]
+    fn heretic_wads_are_recognized_by_their_music_lumps() {
+        let mut lumps = doom1_lumps();
+        assert_eq!(build_wad(&lumps).unwrap().game_kind(), GameKind::Doom);
+        lumps.insert(0, (String::from("MUS_E1M1"), vec![0; 16]));
+        assert_eq!(build_wad(&lumps).unwrap().game_kind(), GameKind::Heretic);
+    }
+
+    #[test]
+    fn hexen_maps_are_skipped_but_doom_maps_still_load() {
+        let mut lumps = doom1_lumps();
+        let blockmap_idx = map_lump_idx(&lumps, "E1M2", "BLOCKMAP");
+        lumps.insert(blockmap_idx + 1, (String::from("BEHAVIOR"), vec![0; 16]));
+        let wad_data = build_wad(&lumps).unwrap();
+
+        assert!(wad_data
+            .warnings()
+            .iter()
+            .any(|w| w.contains("E1M2 is in Hexen format")));
+        assert_eq!(wad_data.map_count(), 8);
+        assert_eq!(wad_data.map_index("E1M2"), None);
+        let e1m3 = wad_data.map(wad_data.map_index("E1M3").unwrap());
+        assert!(!e1m3.is_hexen_format() && e1m3.is_complete());
+    }
+
+    #[test]
+    fn load_errors_have_specific_variants() {
+        assert!(matches!(WadData::load("NO_SUCH_FILE.WAD", true), Err(WadError::Io(_))));
+        assert!(matches!(
+            WadData::from_bytes("X.WAD", true, Bytes::from_static(b"IWAD")),
+            Err(WadError::TooSmall { .. })
+        ));
+        match WadData::from_bytes(
+            "X.WAD",
+            false,
+            Bytes::from(vec![b'I', b'W', b'A', b'D', 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+        ) {
+            Err(WadError::BadMagic { expected, found }) => {
+                assert_eq!((expected.as_str(), found.as_str()), ("PWAD", "IWAD"))
+            }
+            _ => panic!("expected BadMagic"),
+        }
+
+        // a lump which goes beyond the end of the file
+        let mut wad_bytes = std::fs::read("DOOM1.WAD").unwrap();
+        let dir_offset = utils::buf_to_u32(&wad_bytes[8..12]) as usize;
+        wad_bytes[dir_offset + 4..dir_offset + 8].copy_from_slice(&u32::MAX.to_le_bytes());
+        match WadData::from_bytes("X.WAD", true, Bytes::from(wad_bytes)) {
+            Err(WadError::LumpOutOfBounds { name }) => assert_eq!(name, "PLAYPAL"),
+            _ => panic!("expected LumpOutOfBounds"),
+        }
+
+        // missing and incomplete lumps
+        let mut lumps = doom1_lumps();
+        lumps.retain(|(name, _)| name != "COLORMAP");
+        assert!(matches!(build_wad(&lumps), Err(WadError::MissingLump { .. })));
+        let mut lumps = doom1_lumps();
+        lumps.remove(map_lump_idx(&lumps, "E1M4", "SEGS"));
+        match build_wad(&lumps) {
+            Err(WadError::IncompleteMap { name }) => assert_eq!(name, "E1M4"),
+            _ => panic!("expected IncompleteMap"),
+        }
+        let mut lumps = doom1_lumps();
+        lumps.retain(|(name, _)| !name.starts_with("E1M"));
+        assert!(matches!(build_wad(&lumps), Err(WadError::NoMaps)));
+    }
+
+    #[test]
+    fn changing_a_lump_changes_its_hash() {
+        let original = WadData::load("DOOM1.WAD", true).unwrap();
+        let mut lumps = doom1_lumps();
+        let stbar_idx = lumps.iter().position(|(name, _)| name == "STBAR").unwrap();
+        lumps[stbar_idx].1[100] ^= 0xFF;
+        let changed = build_wad(&lumps).unwrap();
+
+        assert_eq!(changed.lump_size("STBAR"), original.lump_size("STBAR"));
+        assert_ne!(changed.lump_hash("STBAR"), original.lump_hash("STBAR"));
+        assert_eq!(changed.lump_hash("PLAYPAL"), original.lump_hash("PLAYPAL"));
+        assert_eq!(changed.lump_hash("NOSUCHLUMP"), None);
+        assert_eq!(original.changed_lumps(&changed), vec!["STBAR"]);
+    }
+
+    #[test]
+    fn flawed_wad_loads_with_warnings() {
+        let mut lumps = doom1_lumps();
+        let reject_idx = map_lump_idx(&lumps, "E1M1", "REJECT");
+        lumps[reject_idx].1.truncate(10);
+        // the light level of the first sector
+        let sectors_idx = map_lump_idx(&lumps, "E1M2", "SECTORS");
+        lumps[sectors_idx].1[20..22].copy_from_slice(&300_i16.to_le_bytes());
+        // turn the player 1 start into a player 2 start
+        let things_idx = map_lump_idx(&lumps, "E1M3", "THINGS");
+        for thing in lumps[things_idx].1.chunks_exact_mut(10) {
+            if thing[6..8] == [1, 0] {
+                thing[6] = 2;
+            }
+        }
+        let flat_idx = lumps.iter().position(|(name, _)| name == "FLOOR4_8").unwrap();
+        lumps[flat_idx].1.truncate(100);
+
+        let wad_data = build_wad(&lumps).unwrap();
+        let expected = [
+            "Map E1M1: the REJECT lump is truncated (10 < ",
+            "Map E1M2: sector #0 has an out of range light level: 300",
+            "Map E1M3: there is no player 1 start",
+            "Flat FLOOR4_8 has an invalid size (100 bytes)",
+        ];
+        for warning in expected {
+            assert!(wad_data.warnings().iter().any(|w| w.starts_with(warning)), "{warning}");
+        }
+        assert_eq!(wad_data.warnings().len(), expected.len());
+    }
+
+    #[test]
+    fn shuffled_maps_are_still_ordered_by_their_numbers() {
+        // put the maps (each is its marker lump + the 10 lumps after it) in reverse order,
+        // with E1M4 renamed to MAP01 (the MAPxx maps come after the ExMy ones)
+        let mut lumps = doom1_lumps();
+        let first_map_idx = map_lump_idx(&lumps, "E1M1", "E1M1");
+        let map_lumps: Vec<_> = lumps.drain(first_map_idx..first_map_idx + 9 * 11).collect();
+        // (each map is inserted before the previous ones)
+        for map in map_lumps.chunks(11) {
+            let mut map = map.to_vec();
+            if map[0].0 == "E1M4" {
+                map[0].0 = String::from("MAP01");
+            }
+            lumps.splice(first_map_idx..first_map_idx, map);
+        }
+        let wad_data = build_wad(&lumps).unwrap();
+        assert_eq!(wad_data.map_name(0), "E1M9");
+
+        let names: Vec<_> = wad_data
+            .ordered_map_indices()
+            .into_iter()
+            .map(|idx| wad_data.map_name(idx))
+            .collect();
+        assert_eq!(
+            names,
+            ["E1M1", "E1M2", "E1M3", "E1M5", "E1M6", "E1M7", "E1M8", "E1M9", "MAP01"]
+        );
+    }
+}
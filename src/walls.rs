@@ -0,0 +1,459 @@
+//! Wall rendering helpers: all the data needed to render the walls of a seg.
+
+use crate::map_items::*;
+use crate::painter::RGB;
+use crate::utils::hash_lump_name;
+
+/// Light levels go in steps of 16 (vanilla DOOM only uses the upper 4 bits).
+const LIGHT_LEVEL_STEP: u16 = 16;
+pub const MAX_LIGHT_LEVEL: u16 = 255;
+
+/// Everything needed to render the wall(s) of a seg, as seen from the seg's side.
+pub struct SegRenderData {
+    #[allow(dead_code)] // for the linedef flags, once the textured wall renderer uses SegRenderBands
+    pub linedef: LineDef,
+    /// The sidedef on the same side of the linedef as the seg.
+    #[allow(dead_code)] // only read by `SegRenderBands` and `texture_u_start` (not wired in yet)
+    pub front_side: SideDef,
+    pub front_sector: Sector,
+    /// The sidedef on the opposite side (only for two-sided linedefs).
+    #[allow(dead_code)] // TODO needed for the back side's texture offsets
+    pub back_side: Option<SideDef>,
+    pub back_sector: Option<Sector>,
+    /// The seg's distance from the start of its linedef, as seen from the seg's side:
+    /// for segs going the same direction as the linedef (front side) it is measured from `v1`,
+    /// otherwise (back side) it is measured from `v2`, since back side textures run "backwards".
+    #[allow(dead_code)] // only read by `texture_u_start`
+    pub seg_offset: i16,
+}
+
+impl SegRenderData {
+    #[inline]
+    pub fn is_two_sided(&self) -> bool {
+        self.back_sector.is_some()
+    }
+
+    /// The horizontal texture coordinate (U) at the start of the seg.
+    /// Linedefs are often split into several segs, so the seg offset must be added to the sidedef's
+    /// offset, for the textures to line up across all the segs of the same wall.
+    #[inline]
+    #[allow(dead_code)] // TODO use it when painting the textured wall columns
+    pub fn texture_u_start(&self) -> i32 {
+        self.front_side.x_offset as i32 + self.seg_offset as i32
+    }
+}
+
+//----------------------------
+
+/// Where the texture of a wall band is anchored (pegged), vertically.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(dead_code)] // only built by `SegRenderBands`
+pub enum TextureAnchor {
+    /// The top of the texture is at the given height.
+    Top(i32),
+    /// The bottom of the texture is at the given height.
+    Bottom(i32),
+}
+
+/// A vertical band of a wall, between two heights (in map units), painted with one texture.
+#[allow(dead_code)] // only built by `SegRenderBands`
+pub struct WallBand {
+    pub texture_key: u64,
+    pub top: i32,
+    pub bottom: i32,
+    pub anchor: TextureAnchor,
+    /// The sidedef's vertical texture offset.
+    pub y_offset: i32,
+    /// Middle textures of two-sided walls do not tile vertically: they are drawn only once.
+    pub tiles_vertically: bool,
+    /// The band should show the sky instead of its texture (see `SegRenderBands`).
+    /// NOTE: this is only the flag - the renderer does not paint textured bands yet.
+    pub is_sky: bool,
+}
+
+// TODO use these when painting the textured wall columns
+#[allow(dead_code)]
+impl WallBand {
+    /// The screen Y extents (top, bottom) of this band, for a wall column at the given scale
+    /// (= projection distance / wall distance), as seen from the given eye height.
+    pub fn screen_y_extents(&self, eye_z: i32, center_y: i32, scale: f64) -> (i32, i32) {
+        let y_top = center_y as f64 - ((self.top - eye_z) as f64) * scale;
+        let y_bottom = center_y as f64 - ((self.bottom - eye_z) as f64) * scale;
+        (y_top.round() as i32, y_bottom.round() as i32)
+    }
+
+    /// False if the band has no texture on purpose ("-"), so it is left see-through
+    /// (unlike a texture missing from the WAD, which should be painted with a placeholder).
+    #[inline]
+    pub fn has_texture(&self) -> bool {
+        !is_no_texture(self.texture_key)
+    }
+
+    /// The heights (top, bottom) where the band is actually drawn: the whole band for tiling textures,
+    /// but only one texture height for non-tiling ones (the rest of the band stays see-through).
+    /// Returns None if nothing is drawn.
+    pub fn drawn_heights(&self, texture_height: i32) -> Option<(i32, i32)> {
+        if self.tiles_vertically {
+            return Some((self.top, self.bottom));
+        }
+        let texture_top = self.top + self.texture_v_start(texture_height);
+        let top = Ord::min(self.top, texture_top);
+        let bottom = Ord::max(self.bottom, texture_top - texture_height);
+        (top > bottom).then_some((top, bottom))
+    }
+
+    /// The vertical texture coordinate (V) at the top of the band.
+    pub fn texture_v_start(&self, texture_height: i32) -> i32 {
+        let texture_top = match self.anchor {
+            TextureAnchor::Top(height) => height,
+            TextureAnchor::Bottom(height) => height + texture_height,
+        };
+        texture_top - self.top + self.y_offset
+    }
+}
+
+/// The texture bands to draw for a seg:
+/// * one-sided walls only have a middle band, between the floor and the ceiling
+/// * two-sided walls have an upper band (between the two ceilings) and a lower band (between
+///   the two floors) only where the heights differ, plus an optional (non-tiling) middle texture
+///
+/// The textures are pegged like in vanilla DOOM:
+/// * upper textures hang from the lower ceiling, or start at the higher ceiling if "upper unpegged"
+/// * lower textures start at the higher floor, or are aligned to the ceiling if "lower unpegged"
+/// * middle textures start at the ceiling, or sit on the floor if "lower unpegged"
+///
+/// This way, door and step textures do not "slide" when the sectors move.
+///
+/// Also, like vanilla DOOM's "sky hack": if both sectors have a sky ceiling, the upper band is flagged
+/// to show the sky instead of the upper texture (so the outdoor areas look like they have no walls up there).
+#[allow(dead_code)] // TODO not used by `paint_3d_view` yet, which only paints placeholder walls
+pub struct SegRenderBands {
+    pub upper: Option<WallBand>,
+    pub middle: Option<WallBand>,
+    pub lower: Option<WallBand>,
+}
+
+#[allow(dead_code)]
+impl SegRenderBands {
+    pub fn new(data: &SegRenderData) -> Self {
+        let side = &data.front_side;
+        let flags = data.linedef.flags;
+        let upper_unpegged = (flags & LINE_UPPER_UNPEGGED) != 0;
+        let lower_unpegged = (flags & LINE_LOWER_UNPEGGED) != 0;
+        let y_offset = side.y_offset as i32;
+        let front_floor = data.front_sector.floor_height as i32;
+        let front_ceiling = data.front_sector.ceiling_height as i32;
+        let sky_key = sky_flat_key();
+        match &data.back_sector {
+            None => Self {
+                upper: None,
+                middle: Some(WallBand {
+                    texture_key: side.middle_texture_key,
+                    top: front_ceiling,
+                    bottom: front_floor,
+                    anchor: if lower_unpegged {
+                        TextureAnchor::Bottom(front_floor)
+                    } else {
+                        TextureAnchor::Top(front_ceiling)
+                    },
+                    y_offset,
+                    tiles_vertically: true,
+                    is_sky: false,
+                }),
+                lower: None,
+            },
+            Some(back) => {
+                let back_floor = back.floor_height as i32;
+                let back_ceiling = back.ceiling_height as i32;
+                let upper = (back_ceiling < front_ceiling).then_some(WallBand {
+                    texture_key: side.upper_texture_key,
+                    top: front_ceiling,
+                    bottom: back_ceiling,
+                    anchor: if upper_unpegged {
+                        TextureAnchor::Top(front_ceiling)
+                    } else {
+                        TextureAnchor::Bottom(back_ceiling)
+                    },
+                    y_offset,
+                    tiles_vertically: true,
+                    is_sky: data.front_sector.ceiling_flat_key == sky_key && back.ceiling_flat_key == sky_key,
+                });
+                let lower = (back_floor > front_floor).then_some(WallBand {
+                    texture_key: side.lower_texture_key,
+                    top: back_floor,
+                    bottom: front_floor,
+                    anchor: if lower_unpegged {
+                        TextureAnchor::Top(front_ceiling)
+                    } else {
+                        TextureAnchor::Top(back_floor)
+                    },
+                    y_offset,
+                    tiles_vertically: true,
+                    is_sky: false,
+                });
+                let mid_top = Ord::min(front_ceiling, back_ceiling);
+                let mid_bottom = Ord::max(front_floor, back_floor);
+                let middle = side.has_middle().then_some(WallBand {
+                    texture_key: side.middle_texture_key,
+                    top: mid_top,
+                    bottom: mid_bottom,
+                    anchor: if lower_unpegged {
+                        TextureAnchor::Bottom(mid_bottom)
+                    } else {
+                        TextureAnchor::Top(mid_top)
+                    },
+                    y_offset,
+                    tiles_vertically: false,
+                    is_sky: false,
+                });
+                Self { upper, middle, lower }
+            }
+        }
+    }
+}
+
+/// The key of the sky flat: sectors with this ceiling flat show the sky instead.
+#[inline]
+#[allow(dead_code)] // only used by `SegRenderBands` (and the sky should also use it, later)
+pub fn sky_flat_key() -> u64 {
+    hash_lump_name(b"F_SKY1")
+}
+
+//----------------------------
+
+/// The effective light level of a seg's wall.
+/// With "fake contrast", like in vanilla DOOM, walls running east-west are one step darker and
+/// walls running north-south are one step brighter than their sector, for better readability.
+pub fn wall_light_level(seg: &Seg, sector_light: u16, fake_contrast: bool) -> u16 {
+    if !fake_contrast {
+        sector_light
+    } else if seg.start.y == seg.end.y {
+        sector_light.saturating_sub(LIGHT_LEVEL_STEP)
+    } else if seg.start.x == seg.end.x {
+        Ord::min(sector_light + LIGHT_LEVEL_STEP, MAX_LIGHT_LEVEL)
+    } else {
+        sector_light
+    }
+}
+
+//----------------------------
+
+/// Distance fog (depth cueing): the colors fade towards the fog color, as the distance grows.
+/// Not in vanilla DOOM - it is applied on top of the colormap light diminishing.
+#[derive(Clone, Copy)]
+pub struct DistanceFog {
+    pub color: RGB,
+    /// How quickly the fog thickens with the distance (per map unit).
+    pub density: f64,
+}
+
+impl DistanceFog {
+    /// How much of the fog color is seen at a distance: 0 (none) to 1 (only fog).
+    /// Uses exponential fog, so it never fully covers anything, and it never "pops".
+    #[inline]
+    pub fn amount(&self, distance: f64) -> f64 {
+        1.0 - (-self.density * distance.max(0.0)).exp()
+    }
+
+    #[inline]
+    pub fn apply(&self, color: RGB, distance: f64) -> RGB {
+        color.lerp(self.color, self.amount(distance))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::angle::Angle;
+
+    fn sector(floor_height: i16, ceiling_height: i16) -> Sector {
+        Sector {
+            floor_height,
+            ceiling_height,
+            floor_flat_key: hash_lump_name(b"FLOOR4_8"),
+            ceiling_flat_key: hash_lump_name(b"CEIL3_5"),
+            light_level: 160,
+            special_type: 0,
+            tag_nr: 0,
+        }
+    }
+
+    fn side(x_offset: i16, middle_texture: &[u8]) -> SideDef {
+        SideDef {
+            x_offset,
+            y_offset: 0,
+            upper_texture_key: hash_lump_name(b"STARTAN3"),
+            lower_texture_key: hash_lump_name(b"STEP6"),
+            middle_texture_key: hash_lump_name(middle_texture),
+            sector_idx: 0,
+        }
+    }
+
+    fn render_data(
+        flags: u16,
+        front_side: SideDef,
+        front_sector: Sector,
+        back_sector: Option<Sector>,
+    ) -> SegRenderData {
+        SegRenderData {
+            linedef: LineDef {
+                v1: Vertex { x: 0, y: 0 },
+                v2: Vertex { x: 128, y: 0 },
+                flags,
+                special_type: 0,
+                sector_tag: 0,
+                right_side_idx: 0,
+                left_side_idx: if back_sector.is_some() { 1 } else { NO_SIDEDEF },
+            },
+            back_side: back_sector.as_ref().map(|_| side(0, b"-")),
+            front_side,
+            front_sector,
+            back_sector,
+            seg_offset: 0,
+        }
+    }
+
+    #[test]
+    fn texture_u_starts_at_the_seg_offset() {
+        let mut data = render_data(LINE_BLOCKS, side(16, b"STARTAN3"), sector(0, 128), None);
+        assert_eq!(data.texture_u_start(), 16);
+        // the second seg of a split linedef continues the texture where the first one ended
+        data.seg_offset = 40;
+        assert_eq!(data.texture_u_start(), 56);
+    }
+
+    #[test]
+    fn window_has_upper_and_lower_bands() {
+        // a window: the back sector's floor is higher and its ceiling is lower
+        let data = render_data(LINE_TWO_SIDED, side(0, b"-"), sector(0, 128), Some(sector(32, 96)));
+        let bands = SegRenderBands::new(&data);
+        assert!(bands.middle.is_none());
+        let upper = bands.upper.unwrap();
+        assert_eq!((upper.top, upper.bottom), (128, 96));
+        assert_eq!(upper.texture_key, hash_lump_name(b"STARTAN3"));
+        let lower = bands.lower.unwrap();
+        assert_eq!((lower.top, lower.bottom), (32, 0));
+        assert_eq!(lower.texture_key, hash_lump_name(b"STEP6"));
+        // seen from eye height 41, at scale 1, with the screen center at row 100
+        assert_eq!(upper.screen_y_extents(41, 100, 1.0), (13, 45));
+        assert_eq!(lower.screen_y_extents(41, 100, 1.0), (109, 141));
+    }
+
+    #[test]
+    fn solid_wall_has_only_a_tiling_middle_band() {
+        let data = render_data(LINE_BLOCKS, side(0, b"STARTAN3"), sector(0, 128), None);
+        let bands = SegRenderBands::new(&data);
+        assert!(bands.upper.is_none() && bands.lower.is_none());
+        let middle = bands.middle.unwrap();
+        assert_eq!((middle.top, middle.bottom), (128, 0));
+        assert!(middle.tiles_vertically);
+        assert_eq!(middle.drawn_heights(72), Some((128, 0)));
+    }
+
+    #[test]
+    fn two_sided_middle_texture_does_not_tile() {
+        let data = render_data(
+            LINE_TWO_SIDED,
+            side(0, b"MIDGRATE"),
+            sector(0, 128),
+            Some(sector(0, 128)),
+        );
+        let bands = SegRenderBands::new(&data);
+        assert!(bands.upper.is_none() && bands.lower.is_none());
+        let middle = bands.middle.unwrap();
+        assert!(!middle.tiles_vertically);
+        // hanging from the ceiling, only one texture height is drawn
+        assert_eq!(middle.drawn_heights(72), Some((128, 56)));
+    }
+
+    #[test]
+    fn pegging_moves_the_texture_start() {
+        let bands = |flags: u16, middle_texture: &[u8], front: Sector, back: Option<Sector>| {
+            SegRenderBands::new(&render_data(flags, side(0, middle_texture), front, back))
+        };
+
+        // a door's upper texture hangs from the door (= the lower ceiling), so it moves with it
+        let door = bands(LINE_TWO_SIDED, b"-", sector(0, 128), Some(sector(0, 96)));
+        assert_eq!(door.upper.unwrap().texture_v_start(72), 40);
+        // unpegged, it starts at the higher ceiling, like the walls around it
+        let flags = LINE_TWO_SIDED | LINE_UPPER_UNPEGGED;
+        let door = bands(flags, b"-", sector(0, 128), Some(sector(0, 96)));
+        assert_eq!(door.upper.unwrap().texture_v_start(72), 0);
+
+        // a step: the lower texture starts at the step, or is aligned to the ceiling if unpegged
+        let step = bands(LINE_TWO_SIDED, b"-", sector(0, 128), Some(sector(32, 128)));
+        assert_eq!(step.lower.unwrap().texture_v_start(72), 0);
+        let flags = LINE_TWO_SIDED | LINE_LOWER_UNPEGGED;
+        let step = bands(flags, b"-", sector(0, 128), Some(sector(32, 128)));
+        assert_eq!(step.lower.unwrap().texture_v_start(72), 96);
+
+        // a door track sits on the floor, instead of hanging from the ceiling
+        let flags = LINE_BLOCKS | LINE_LOWER_UNPEGGED;
+        let track = bands(flags, b"DOORTRAK", sector(0, 128), None);
+        assert_eq!(track.middle.unwrap().texture_v_start(128), 0);
+        let track = bands(flags, b"DOORTRAK", sector(0, 96), None);
+        assert_eq!(track.middle.unwrap().texture_v_start(128), 32);
+    }
+
+    #[test]
+    fn fake_contrast_shades_axis_aligned_walls() {
+        let seg = |end: Vertex| Seg {
+            start: Vertex { x: 0, y: 0 },
+            end,
+            angle: Angle::from_vector(Vertex { x: 0, y: 0 }, end),
+            linedef_idx: 0,
+            direction_same: true,
+            offset: 0,
+        };
+        let east_west = seg(Vertex { x: 64, y: 0 });
+        let north_south = seg(Vertex { x: 0, y: 64 });
+        let diagonal = seg(Vertex { x: 64, y: 64 });
+        assert_eq!(wall_light_level(&east_west, 160, true), 144);
+        assert_eq!(wall_light_level(&north_south, 160, true), 176);
+        assert_eq!(wall_light_level(&diagonal, 160, true), 160);
+        // the light stays in range
+        assert_eq!(wall_light_level(&north_south, 250, true), MAX_LIGHT_LEVEL);
+        // without fake contrast, all the walls have the sector's light
+        assert_eq!(wall_light_level(&east_west, 160, false), 160);
+        assert_eq!(wall_light_level(&north_south, 160, false), 160);
+    }
+
+    #[test]
+    fn upper_band_between_two_sky_ceilings_is_flagged_as_sky() {
+        let sky_sector = |floor_height: i16, ceiling_height: i16| Sector {
+            ceiling_flat_key: sky_flat_key(),
+            ..sector(floor_height, ceiling_height)
+        };
+        let data = render_data(
+            LINE_TWO_SIDED,
+            side(0, b"-"),
+            sky_sector(0, 256),
+            Some(sky_sector(0, 192)),
+        );
+        assert!(SegRenderBands::new(&data).upper.unwrap().is_sky);
+        // with a normal ceiling on one side, the upper texture is drawn
+        let data = render_data(LINE_TWO_SIDED, side(0, b"-"), sky_sector(0, 256), Some(sector(0, 192)));
+        assert!(!SegRenderBands::new(&data).upper.unwrap().is_sky);
+    }
+
+    #[test]
+    fn distant_walls_are_closer_to_the_fog_color() {
+        let fog = DistanceFog {
+            color: RGB::from(200, 200, 220),
+            density: 0.002,
+        };
+        let wall = RGB::from(0, 96, 96);
+        let fog_distance = |c: RGB| {
+            let diff = |a: u8, b: u8| (a as i32 - b as i32).abs();
+            diff(c.r, fog.color.r) + diff(c.g, fog.color.g) + diff(c.b, fog.color.b)
+        };
+        // no fog right in front of the player
+        assert!(fog.apply(wall, 0.0) == wall);
+        let near = fog.apply(wall, 64.0);
+        let far = fog.apply(wall, 1024.0);
+        assert!(fog_distance(far) < fog_distance(near));
+        assert!(fog_distance(near) < fog_distance(wall));
+        // (exponential fog: never completely foggy)
+        assert!(fog.amount(1e4) < 1.0 && fog.amount(1024.0) > fog.amount(1000.0));
+    }
+}